@@ -1,23 +1,45 @@
 use crate::cli_args::{GeneralSort, GeneralSortable};
 use crate::error::{Error, Result};
+use crate::pins;
+use crate::session::{self, ScannedPeripheral, SessionEvent};
 use crate::tui::ui::StableListItem;
 use crate::Ctx;
-use btleplug::api::{BDAddr, Central, CharPropFlags, Manager as _, Peripheral, ScanFilter};
-use btleplug::platform::PeripheralId;
-use futures::future::try_join_all;
+use backend::{AdapterEvent, BleAdapter, BlePeripheral, GattAttribute, PeripheralId};
+use btleplug::api::{
+    AddressType, BDAddr, CharPropFlags, Characteristic, Descriptor, PeripheralProperties, WriteType,
+};
+use futures::StreamExt;
 use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::iter::Iterator;
 use std::ops::DerefMut;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use tokio::time::{self, sleep, timeout};
 
+pub mod backend;
+pub mod beacon;
 pub mod ble_default_services;
+pub mod connections;
+pub mod decoder;
+pub mod gatt_decoders;
+pub mod history;
+pub mod payload;
+pub mod replay;
+pub mod scan_filter;
+pub mod simulator;
 
 const DEFAULT_DEVICE_NAME: &str = "Unknown device";
-const TIMEOUT: Duration = Duration::from_secs(10);
+pub const TIMEOUT: Duration = Duration::from_secs(10);
 
-pub async fn disconnect_with_timeout(peripheral: &btleplug::platform::Peripheral) {
+/// Single connection attempt, retrying is up to the caller
+pub async fn connect_with_timeout(peripheral: &dyn BlePeripheral) -> Result<()> {
+    timeout(TIMEOUT, peripheral.connect()).await??;
+
+    Ok(())
+}
+
+pub async fn disconnect_with_timeout(peripheral: &dyn BlePeripheral) {
     match timeout(TIMEOUT, peripheral.is_connected()).await {
         Ok(Ok(false)) => {
             return;
@@ -41,21 +63,134 @@ pub async fn disconnect_with_timeout(peripheral: &btleplug::platform::Peripheral
     }
 }
 
+pub fn is_writable(props: CharPropFlags) -> bool {
+    props.intersects(CharPropFlags::WRITE | CharPropFlags::WRITE_WITHOUT_RESPONSE)
+}
+
+/// Writes the data picking the write type supported by characteristic. Write with response is preferred.
+pub async fn write_with_timeout(
+    peripheral: &dyn BlePeripheral,
+    characteristic: &Characteristic,
+    data: &[u8],
+) -> Result<()> {
+    let write_type = if characteristic.properties.contains(CharPropFlags::WRITE) {
+        WriteType::WithResponse
+    } else if characteristic
+        .properties
+        .contains(CharPropFlags::WRITE_WITHOUT_RESPONSE)
+    {
+        WriteType::WithoutResponse
+    } else {
+        return Err(Error::client("Characteristic does not support writes"));
+    };
+
+    timeout(TIMEOUT, peripheral.write(characteristic, data, write_type)).await??;
+
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
-pub struct HandledPeripheral<TPer: Peripheral = btleplug::platform::Peripheral> {
+pub struct HandledPeripheral {
     pub name_unset: bool,
-    pub ble_peripheral: TPer,
+    /// Pinned or favorite, set when the scan snapshot is published
+    pub pinned: bool,
+    /// Index of the adapter which reported the peripheral
+    pub adapter_index: usize,
+    pub ble_peripheral: Arc<dyn BlePeripheral>,
     pub address: BDAddr,
     pub name: String,
     pub rssi: Option<i16>,
+    pub services: Vec<uuid::Uuid>,
     pub services_names: Vec<Cow<'static, str>>,
+    /// Advertised manufacturer specific data keyed by the company identifier
+    pub manufacturer_data: HashMap<u16, Vec<u8>>,
+    pub service_data: HashMap<uuid::Uuid, Vec<u8>>,
+    pub tx_power_level: Option<i16>,
+    pub address_type: Option<AddressType>,
+    /// Beacon frames decoded from the advertisement data
+    pub beacons: Vec<beacon::BeaconFrame>,
+}
+
+impl HandledPeripheral {
+    fn from_properties(
+        context: &Ctx,
+        ble_peripheral: Arc<dyn BlePeripheral>,
+        adapter_index: usize,
+        properties: PeripheralProperties,
+    ) -> Self {
+        let name_unset = properties.local_name.is_none();
+        let name = properties
+            .local_name
+            .unwrap_or_else(|| DEFAULT_DEVICE_NAME.to_string());
+
+        let services_names = properties
+            .services
+            .iter()
+            .flat_map(|uuid| {
+                let custom_name = context
+                    .config
+                    .service_name(&name, &properties.address, uuid)
+                    .map(str::to_owned);
+
+                let standard_name = ble_default_services::SPECIAL_SERVICES_NAMES
+                    .get(uuid)
+                    .copied();
+
+                let uuid_str = uuid.to_string();
+                vec![
+                    custom_name.map(Cow::from),
+                    standard_name.map(Cow::from),
+                    Some(Cow::from(uuid_str)),
+                ]
+            })
+            .flatten()
+            .collect();
+
+        HandledPeripheral {
+            ble_peripheral,
+            adapter_index,
+            address: properties.address,
+            rssi: properties.rssi,
+            name,
+            name_unset,
+            pinned: false,
+            services: properties.services.clone(),
+            beacons: beacon::BeaconFrame::parse_advertisement(
+                &properties.manufacturer_data,
+                &properties.service_data,
+            ),
+            manufacturer_data: properties.manufacturer_data,
+            service_data: properties.service_data,
+            tx_power_level: properties.tx_power_level,
+            address_type: properties.address_type,
+            services_names,
+        }
+    }
+
+    /// With the duplicates filtered only the events changing the advertised data count as
+    /// new advertisements, rssi alone changes with every repeated advertisement
+    fn advertised_data_changed(&self, previous: &HandledPeripheral) -> bool {
+        self.name != previous.name
+            || self.tx_power_level != previous.tx_power_level
+            || self.manufacturer_data != previous.manufacturer_data
+            || self.service_data != previous.service_data
+            || self.services != previous.services
+    }
 }
 
 impl GeneralSortable for HandledPeripheral {
-    const AVAILABLE_SORTS: &'static [GeneralSort] = &[GeneralSort::Name, GeneralSort::DefaultSort];
+    const AVAILABLE_SORTS: &'static [GeneralSort] = &[
+        GeneralSort::Name,
+        GeneralSort::Pinned,
+        GeneralSort::DefaultSort,
+    ];
 
     fn cmp(&self, sort: &GeneralSort, a: &Self, b: &Self) -> std::cmp::Ordering {
         match sort {
+            GeneralSort::Pinned => b
+                .pinned
+                .cmp(&a.pinned)
+                .then_with(|| self.cmp(&GeneralSort::Name, a, b)),
             // Specifically put all the "unknown devices" to the end of the list.
             GeneralSort::Name if a.name == b.name && a.name == DEFAULT_DEVICE_NAME => {
                 std::cmp::Ordering::Equal
@@ -83,15 +218,27 @@ pub struct ConnectedCharacteristic {
     /// Name that is coming from custom name map file (if any) from user.
     pub custom_char_name: Option<String>,
     pub custom_service_name: Option<String>,
+    /// Characteristic User Description (0x2901) read from the device
+    pub user_description: Option<String>,
+    /// Refreshed on every sort
+    pub pinned: bool,
     pub uuid: uuid::Uuid,
     pub service_uuid: uuid::Uuid,
 }
 
 impl GeneralSortable for ConnectedCharacteristic {
-    const AVAILABLE_SORTS: &'static [GeneralSort] = &[GeneralSort::Name, GeneralSort::DefaultSort];
+    const AVAILABLE_SORTS: &'static [GeneralSort] = &[
+        GeneralSort::Name,
+        GeneralSort::Pinned,
+        GeneralSort::DefaultSort,
+    ];
 
     fn cmp(&self, sort: &GeneralSort, a: &Self, b: &Self) -> std::cmp::Ordering {
         match sort {
+            GeneralSort::Pinned => b
+                .pinned
+                .cmp(&a.pinned)
+                .then_with(|| self.cmp(&GeneralSort::Name, a, b)),
             GeneralSort::Name => {
                 if a.service_name() == b.service_name() && a.char_name() == b.char_name() {
                     return std::cmp::Ordering::Equal;
@@ -127,7 +274,9 @@ impl StableListItem<uuid::Uuid> for ConnectedCharacteristic {
 
 impl ConnectedCharacteristic {
     pub fn has_readable_char_name(&self) -> bool {
-        self.custom_char_name.is_some() || self.standard_gatt_char_name.is_some()
+        self.custom_char_name.is_some()
+            || self.user_description.is_some()
+            || self.standard_gatt_char_name.is_some()
     }
 
     pub fn has_readable_service_name(&self) -> bool {
@@ -143,6 +292,10 @@ impl ConnectedCharacteristic {
             return Cow::from(custom_name.as_str());
         }
 
+        if let Some(user_description) = &self.user_description {
+            return Cow::from(format!("{} ({})", user_description, self.uuid));
+        }
+
         if let Some(standard_name) = self.standard_gatt_char_name {
             return Cow::from(standard_name);
         }
@@ -166,11 +319,25 @@ impl ConnectedCharacteristic {
 #[derive(Debug, Clone)]
 pub struct ConnectedPeripheral {
     pub peripheral: HandledPeripheral,
+    /// Discovered services including the ones without characteristics
+    pub services: Vec<btleplug::api::Service>,
     pub characteristics: Vec<ConnectedCharacteristic>,
+    /// ATT handles of the attributes when the backend exposes them
+    pub handles: BTreeMap<GattAttribute, u16>,
+    /// Latest values of the descriptors that have a decoder, shared by every copy of the peripheral
+    pub descriptor_values: Arc<RwLock<BTreeMap<Descriptor, Vec<u8>>>>,
 }
 
 impl ConnectedPeripheral {
+    pub fn handle(&self, attribute: &GattAttribute) -> Option<u16> {
+        self.handles.get(attribute).copied()
+    }
+
     pub fn apply_sort(&mut self, ctx: &Ctx) {
+        for characteristic in &mut self.characteristics {
+            characteristic.pinned = pins::is_characteristic_pinned(ctx, characteristic);
+        }
+
         let options = ctx.general_options.read();
 
         if let Ok(options) = options.as_ref() {
@@ -184,21 +351,21 @@ impl ConnectedPeripheral {
             .into_iter()
             .map(|char| ConnectedCharacteristic {
                 custom_char_name: ctx
-                    .args
-                    .names_map_file
-                    .as_ref()
-                    .and_then(|names| names.get(&char.uuid).cloned()),
+                    .config
+                    .characteristic_name(&peripheral.name, &peripheral.address, &char.uuid)
+                    .map(str::to_owned),
                 custom_service_name: ctx
-                    .args
-                    .names_map_file
-                    .as_ref()
-                    .and_then(|names| names.get(&char.uuid).cloned()),
+                    .config
+                    .service_name(&peripheral.name, &peripheral.address, &char.service_uuid)
+                    .map(str::to_owned),
                 standard_gatt_char_name: ble_default_services::SPECIAL_CHARACTERISTICS_NAMES
                     .get(&char.uuid)
                     .copied(),
                 standard_gatt_service_name: ble_default_services::SPECIAL_SERVICES_NAMES
                     .get(&char.service_uuid)
                     .copied(),
+                user_description: None,
+                pinned: false,
                 uuid: char.uuid,
                 service_uuid: char.service_uuid,
                 ble_characteristic: char,
@@ -206,13 +373,100 @@ impl ConnectedPeripheral {
             .collect();
 
         let mut view = Self {
+            services: peripheral.ble_peripheral.services().into_iter().collect(),
+            handles: peripheral.ble_peripheral.attribute_handles(),
             peripheral,
             characteristics,
+            descriptor_values: Default::default(),
         };
 
         view.apply_sort(ctx);
         view
     }
+
+    /// Reads every descriptor blendr can decode, the user description names the characteristic
+    /// unless the names map has a name for it
+    pub async fn read_descriptors(&mut self, ctx: &Ctx) {
+        for characteristic in &mut self.characteristics {
+            for descriptor in &characteristic.ble_characteristic.descriptors {
+                if !gatt_decoders::DESCRIPTOR_DECODERS.contains_key(&descriptor.uuid) {
+                    continue;
+                }
+
+                let data = match timeout(
+                    TIMEOUT,
+                    self.peripheral.ble_peripheral.read_descriptor(descriptor),
+                )
+                .await
+                {
+                    Ok(Ok(data)) => data,
+                    e => {
+                        tracing::warn!(?e, %descriptor, "Failed to read descriptor.");
+                        continue;
+                    }
+                };
+
+                if descriptor.uuid == gatt_decoders::USER_DESCRIPTION {
+                    characteristic.user_description = std::str::from_utf8(&data)
+                        .ok()
+                        .map(|name| name.trim_end_matches('\0').to_owned())
+                        .filter(|name| !name.is_empty());
+                }
+
+                if let Ok(mut values) = self.descriptor_values.write() {
+                    values.insert(descriptor.clone(), data);
+                }
+            }
+        }
+
+        self.apply_sort(ctx);
+    }
+
+    /// Notification (0b01) and indication (0b10) bits of the characteristic as they were last read
+    pub fn client_configuration(&self, characteristic: &ConnectedCharacteristic) -> Option<u16> {
+        let values = self.descriptor_values.read().ok()?;
+
+        characteristic
+            .ble_characteristic
+            .descriptors
+            .iter()
+            .find(|descriptor| descriptor.uuid == gatt_decoders::CLIENT_CONFIGURATION)
+            .and_then(|descriptor| values.get(descriptor))
+            .and_then(|data| Some(u16::from_le_bytes(data.get(0..2)?.try_into().ok()?)))
+    }
+
+    /// Writes the Client Characteristic Configuration bits and reads back what the device accepted
+    pub async fn write_client_configuration(
+        &self,
+        characteristic: &ConnectedCharacteristic,
+        bits: u16,
+    ) -> Result<()> {
+        let descriptor = characteristic
+            .ble_characteristic
+            .descriptors
+            .iter()
+            .find(|descriptor| descriptor.uuid == gatt_decoders::CLIENT_CONFIGURATION)
+            .ok_or_else(|| {
+                Error::client(format!(
+                    "{} has no Client Characteristic Configuration descriptor",
+                    characteristic.char_name()
+                ))
+            })?;
+
+        let peripheral = &self.peripheral.ble_peripheral;
+        timeout(
+            TIMEOUT,
+            peripheral.write_descriptor(descriptor, &bits.to_le_bytes()),
+        )
+        .await??;
+        let data = timeout(TIMEOUT, peripheral.read_descriptor(descriptor)).await??;
+
+        self.descriptor_values
+            .write()?
+            .insert(descriptor.clone(), data);
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -221,90 +475,281 @@ pub struct BleScan {
     pub sync_time: chrono::DateTime<chrono::Local>,
 }
 
-pub async fn start_scan(context: Arc<Ctx>) -> Result<()> {
-    let adapter_list = context.ble_manager.adapters().await?;
-
+/// Adapters at the given indexes, fails with the list of available adapters if any index is out of range
+pub async fn pick_adapters(
+    adapter_list: &[Arc<dyn BleAdapter>],
+    indexes: &[usize],
+) -> Result<Vec<(usize, Arc<dyn BleAdapter>)>> {
     if adapter_list.is_empty() {
         return Err(
             Error::client("No BLE adapters found. Looks like your device doesn't have a Bluetooth Low Energy adapter or drivers are not installed.")
         );
     }
 
-    let adapter = &adapter_list[context.args.adapter_index];
-    adapter.start_scan(ScanFilter::default()).await?;
+    if let Some(index) = indexes.iter().find(|index| **index >= adapter_list.len()) {
+        let mut available = Vec::with_capacity(adapter_list.len());
+        for (i, adapter) in adapter_list.iter().enumerate() {
+            let info = adapter.adapter_info().await.unwrap_or_default();
+            available.push(format!("{i}: {info}"));
+        }
 
-    loop {
-        let peripherals = adapter.peripherals().await?;
+        return Err(Error::client(format!(
+            "Adapter index {index} is out of range. Available adapters:\n{}",
+            available.join("\n")
+        )));
+    }
 
-        let properties_futures = peripherals
-            .iter()
-            .map(Peripheral::properties)
-            .collect::<Vec<_>>();
+    Ok(indexes
+        .iter()
+        .map(|index| (*index, Arc::clone(&adapter_list[*index])))
+        .collect())
+}
 
-        let mut peripherals = try_join_all(properties_futures)
-            .await?
-            .into_iter()
-            .zip(peripherals.into_iter())
-            .flat_map(|(properties, peripheral)| {
-                properties.map(|properties| {
-                    let name_unset = properties.local_name.is_none();
-                    let name = properties
-                        .local_name
-                        .unwrap_or_else(|| DEFAULT_DEVICE_NAME.to_string());
-
-                    HandledPeripheral {
-                        ble_peripheral: peripheral,
-                        address: properties.address,
-                        rssi: properties.rssi,
-                        name,
-                        name_unset,
-                        services_names: properties
-                            .services
-                            .iter()
-                            .flat_map(|uuid| {
-                                let custom_name = context
-                                    .args
-                                    .names_map_file
-                                    .as_ref()
-                                    .and_then(|names| names.get(uuid).cloned());
-
-                                let standard_name = ble_default_services::SPECIAL_SERVICES_NAMES
-                                    .get(uuid)
-                                    .copied();
-
-                                let uuid_str = uuid.to_string();
-                                vec![
-                                    custom_name.map(Cow::from),
-                                    standard_name.map(Cow::from),
-                                    Some(Cow::from(uuid_str)),
-                                ]
-                            })
-                            .flatten()
-                            .collect(),
-                    }
-                })
-            })
-            .collect::<Vec<_>>();
+pub async fn start_scan(context: Arc<Ctx>) -> Result<()> {
+    let adapter_list = context.ble_manager.adapters().await?;
 
-        let sort = context.general_options.read()?.sort;
-        sort.sort(&mut peripherals);
+    let mut adapter_infos = Vec::with_capacity(adapter_list.len());
+    for adapter in &adapter_list {
+        adapter_infos.push(adapter.adapter_info().await?);
+    }
+    *context.adapters.write()? = adapter_infos;
 
-        context.latest_scan.write()?.replace(BleScan {
-            peripherals,
-            sync_time: chrono::Local::now(),
-        });
+    // latest state of every peripheral seen, some platforms forget peripherals which stop advertising
+    let mut devices: HashMap<PeripheralId, HandledPeripheral> = HashMap::new();
 
-        time::sleep(Duration::from_millis(context.args.scan_interval)).await;
+    loop {
+        *context.request_scan_restart.lock()?.deref_mut() = false;
+
+        let selected_adapters = context.selected_adapters.read()?.clone();
+        let adapters = pick_adapters(&adapter_list, &selected_adapters).await?;
+        devices.retain(|_, peripheral| selected_adapters.contains(&peripheral.adapter_index));
 
-        if matches!(context.request_scan_restart.lock().as_deref(), Ok(true)) {
+        scan_until_restart(&context, &adapters, &mut devices).await?;
+
+        for (_, adapter) in &adapters {
             adapter.stop_scan().await?;
-            adapter.start_scan(ScanFilter::default()).await?;
+        }
+    }
+}
+
+/// Keeps the device table up to date with the adapter events and publishes it to the UI
+/// at most once per scan interval, returns once the scan restart is requested
+async fn scan_until_restart(
+    context: &Arc<Ctx>,
+    adapters: &[(usize, Arc<dyn BleAdapter>)],
+    devices: &mut HashMap<PeripheralId, HandledPeripheral>,
+) -> Result<()> {
+    let mut streams = Vec::with_capacity(adapters.len());
+    for (adapter_index, adapter) in adapters {
+        let adapter_index = *adapter_index;
+        streams.push(
+            adapter
+                .events()
+                .await?
+                .map(move |event| (adapter_index, event)),
+        );
+    }
+    let mut events = futures::stream::select_all(streams);
+
+    let filter = context.scan_options.read()?.ble_filter();
+    for (_, adapter) in adapters {
+        adapter.start_scan(filter.clone()).await?;
+    }
 
-            *context.request_scan_restart.lock()?.deref_mut() = true
+    // properties of the peripherals changed since the last published snapshot
+    let mut changed = HashMap::new();
+    // adapters do not announce the peripherals they already knew before the scan started
+    for (adapter_index, adapter) in adapters {
+        for peripheral in adapter.peripherals().await? {
+            update_device(context, devices, &mut changed, *adapter_index, peripheral).await?;
+        }
+    }
+
+    let mut snapshots = time::interval(Duration::from_millis(context.args.scan_interval));
+    snapshots.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    let mut published_view = None;
+
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                let Some((adapter_index, event)) = event else {
+                    return Err(Error::client("Bluetooth adapter stopped reporting events"));
+                };
+
+                handle_event(context, adapters, devices, &mut changed, adapter_index, event).await?;
+            }
+            _ = snapshots.tick() => {
+                if matches!(context.request_scan_restart.lock().as_deref(), Ok(true)) {
+                    return Ok(());
+                }
+
+                let dropped_stale = context.scan_options.read()?.drop_stale
+                    && drop_stale_devices(context, devices)?;
+
+                let view = (
+                    context.scan_options.read()?.clone(),
+                    context.general_options.read()?.sort,
+                    context.pins.read()?.revision(),
+                );
+                if changed.is_empty() && !dropped_stale && published_view.as_ref() == Some(&view) {
+                    continue;
+                }
+
+                publish_snapshot(context, devices, &mut changed, &view.0, view.1)?;
+                published_view = Some(view);
+            }
         }
     }
 }
 
+/// Removes the peripherals silent for --stale-after seconds from the device table and their
+/// history, connected peripherals stop advertising so they are kept. Returns if anything was dropped.
+fn drop_stale_devices(
+    context: &Ctx,
+    devices: &mut HashMap<PeripheralId, HandledPeripheral>,
+) -> Result<bool> {
+    let mut history = context.peripheral_history.write()?;
+    let stale = devices
+        .keys()
+        .filter(|id| {
+            history
+                .get(*id)
+                .is_some_and(|history| history.is_stale(context.args.stale_after))
+                && connections::state(context, id).is_none()
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for id in &stale {
+        devices.remove(id);
+        history.remove(id);
+    }
+
+    Ok(!stale.is_empty())
+}
+
+async fn handle_event(
+    context: &Arc<Ctx>,
+    adapters: &[(usize, Arc<dyn BleAdapter>)],
+    devices: &mut HashMap<PeripheralId, HandledPeripheral>,
+    changed: &mut HashMap<PeripheralId, PeripheralProperties>,
+    adapter_index: usize,
+    event: AdapterEvent,
+) -> Result<()> {
+    let id = match event {
+        AdapterEvent::DeviceDisconnected(id) => {
+            connections::notify_disconnected(context, &id);
+            return Ok(());
+        }
+        // the properties of the peripheral already include the advertised data of the event
+        AdapterEvent::DeviceDiscovered(id)
+        | AdapterEvent::DeviceUpdated(id)
+        | AdapterEvent::ManufacturerDataAdvertisement { id, .. }
+        | AdapterEvent::ServiceDataAdvertisement { id, .. } => id,
+    };
+
+    let peripheral = match devices.get(&id) {
+        Some(device) => Arc::clone(&device.ble_peripheral),
+        None => {
+            let Some((_, adapter)) = adapters.iter().find(|(index, _)| *index == adapter_index)
+            else {
+                return Ok(());
+            };
+
+            match adapter.peripheral(&id).await {
+                Ok(peripheral) => peripheral,
+                Err(e) => {
+                    tracing::warn!(?e, %id, "Peripheral of the adapter event is gone.");
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    update_device(context, devices, changed, adapter_index, peripheral).await
+}
+
+/// Refreshes the peripheral in the device table and counts the received advertisement
+async fn update_device(
+    context: &Ctx,
+    devices: &mut HashMap<PeripheralId, HandledPeripheral>,
+    changed: &mut HashMap<PeripheralId, PeripheralProperties>,
+    adapter_index: usize,
+    ble_peripheral: Arc<dyn BlePeripheral>,
+) -> Result<()> {
+    let Some(properties) = ble_peripheral.properties().await? else {
+        return Ok(());
+    };
+
+    let id = ble_peripheral.id();
+    let peripheral = HandledPeripheral::from_properties(
+        context,
+        ble_peripheral,
+        adapter_index,
+        properties.clone(),
+    );
+
+    let now = chrono::Local::now();
+    let filter_duplicates = context.scan_options.read()?.filter_duplicates;
+    let advertised = devices
+        .get(&id)
+        .is_none_or(|previous| !filter_duplicates || peripheral.advertised_data_changed(previous));
+
+    if advertised {
+        context
+            .peripheral_history
+            .write()?
+            .entry(id.clone())
+            .or_insert_with(|| history::PeripheralHistory::new(now))
+            .record_advertisement(now, peripheral.rssi, context.args.rssi_history);
+    }
+
+    changed.insert(id.clone(), properties);
+    devices.insert(id, peripheral);
+
+    Ok(())
+}
+
+fn publish_snapshot(
+    context: &Ctx,
+    devices: &HashMap<PeripheralId, HandledPeripheral>,
+    changed: &mut HashMap<PeripheralId, PeripheralProperties>,
+    scan_options: &scan_filter::ScanOptions,
+    sort: GeneralSort,
+) -> Result<()> {
+    if !changed.is_empty() {
+        session::record_event(context, || SessionEvent::Scan {
+            peripherals: changed
+                .iter()
+                .map(|(id, properties)| ScannedPeripheral {
+                    id: id.to_string(),
+                    properties: properties.clone(),
+                })
+                .collect(),
+        });
+        changed.clear();
+    }
+
+    let mut peripherals = devices
+        .values()
+        .filter(|peripheral| scan_options.matches(peripheral))
+        .map(|peripheral| HandledPeripheral {
+            pinned: pins::is_peripheral_pinned(context, &peripheral.name, &peripheral.address),
+            ..peripheral.clone()
+        })
+        .collect::<Vec<_>>();
+    // the table has no order of its own, keep the peripherals with equal sort keys in place
+    peripherals.sort_by_key(|peripheral| peripheral.ble_peripheral.id());
+    sort.sort(&mut peripherals);
+
+    context.latest_scan.write()?.replace(BleScan {
+        peripherals,
+        sync_time: chrono::Local::now(),
+    });
+
+    Ok(())
+}
+
 pub fn display_properties(props: CharPropFlags) -> String {
     let mut labels = Vec::new();
 
@@ -314,13 +759,101 @@ pub fn display_properties(props: CharPropFlags) -> String {
     if props.contains(CharPropFlags::READ) {
         labels.push("Read");
     }
-    if props.contains(CharPropFlags::WRITE) || props.contains(CharPropFlags::WRITE_WITHOUT_RESPONSE)
-    {
+    if is_writable(props) {
         labels.push("Write");
     }
     if props.contains(CharPropFlags::NOTIFY) {
         labels.push("Notify");
     }
+    if props.contains(CharPropFlags::INDICATE) {
+        labels.push("Indicate");
+    }
 
     labels.join(", ")
 }
+
+#[tokio::test]
+async fn scans_and_connects_simulated_peripherals() {
+    use simulator::{scan_once, simulated_ctx, TEST_SIMULATION};
+
+    let ctx = Arc::new(simulated_ctx(TEST_SIMULATION, &[]));
+    let peripheral = scan_once(&ctx, 1).await.remove(0);
+    assert_eq!(peripheral.name, "Thermometer");
+    assert!(peripheral
+        .services_names
+        .iter()
+        .any(|name| name.starts_with("Environmental Sensing")));
+
+    {
+        let history = ctx.peripheral_history.read().unwrap();
+        let history = &history[&peripheral.id()];
+        assert!(history.advertisement_count >= 1);
+        assert!(history.first_seen <= history.last_seen);
+        assert!(!history.rssi.is_empty());
+    }
+
+    while peripheral.ble_peripheral.connect().await.is_err() {}
+    peripheral.ble_peripheral.discover_services().await.unwrap();
+
+    let mut connected = ConnectedPeripheral::new(&ctx, peripheral);
+    assert_eq!(connected.characteristics.len(), 2);
+
+    connected.read_descriptors(&ctx).await;
+    assert!(connected
+        .characteristics
+        .iter()
+        .any(|char| char.char_name().starts_with("Mode (")));
+
+    let notifying = connected.characteristics[0].clone();
+    assert_eq!(connected.client_configuration(&notifying), Some(0));
+    connected
+        .write_client_configuration(&notifying, 0b01)
+        .await
+        .unwrap();
+    assert_eq!(connected.client_configuration(&notifying), Some(0b01));
+}
+
+#[tokio::test]
+async fn counts_advertisement_events() {
+    use simulator::{simulated_ctx, wait_for, TEST_SIMULATION};
+
+    // the same simulation scanned side by side with and without the duplicates
+    let all = Arc::new(simulated_ctx(TEST_SIMULATION, &[]));
+    let filtered = Arc::new(simulated_ctx(TEST_SIMULATION, &[]));
+    filtered.scan_options.write().unwrap().filter_duplicates = true;
+
+    let scanners = [&all, &filtered].map(|ctx| tokio::spawn(start_scan(Arc::clone(ctx))));
+    let advertisement_count = |ctx: &Ctx| {
+        ctx.peripheral_history
+            .read()
+            .unwrap()
+            .values()
+            .next()
+            .map(|history| history.advertisement_count)
+    };
+    wait_for(|| advertisement_count(&all).filter(|count| *count >= 3)).await;
+    for scanner in scanners {
+        scanner.abort();
+    }
+
+    // every simulated advertisement repeats the same data
+    assert_eq!(advertisement_count(&filtered), Some(1));
+}
+
+#[tokio::test]
+async fn drops_stale_devices_with_their_history() {
+    use simulator::{scan_once, simulated_ctx, TEST_SIMULATION};
+
+    let ctx = Arc::new(simulated_ctx(TEST_SIMULATION, &["--stale-after", "0"]));
+    let mut devices = scan_once(&ctx, 1)
+        .await
+        .into_iter()
+        .map(|peripheral| (peripheral.id(), peripheral))
+        .collect::<HashMap<_, _>>();
+    assert!(!devices.is_empty());
+
+    assert!(drop_stale_devices(&ctx, &mut devices).unwrap());
+    assert!(devices.is_empty());
+    assert!(ctx.peripheral_history.read().unwrap().is_empty());
+    assert!(!drop_stale_devices(&ctx, &mut devices).unwrap());
+}
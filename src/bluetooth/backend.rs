@@ -0,0 +1,294 @@
+use async_trait::async_trait;
+use btleplug::api::{
+    BDAddr, Central, CentralEvent, Characteristic, Descriptor, PeripheralProperties, ScanFilter,
+    Service, ValueNotification, WriteType,
+};
+use btleplug::Result;
+use futures::{Stream, StreamExt};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Debug,
+    pin::Pin,
+    sync::Arc,
+};
+use uuid::Uuid;
+
+pub type BoxStream<T> = Pin<Box<dyn Stream<Item = T> + Send>>;
+
+/// Stable identifier of the peripheral, unique across all the backends
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PeripheralId {
+    Platform(btleplug::platform::PeripheralId),
+    Simulated {
+        adapter_index: usize,
+        address: BDAddr,
+    },
+    /// Id of the peripheral as it was written into the recorded session
+    Replayed(String),
+}
+
+impl std::fmt::Display for PeripheralId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeripheralId::Platform(id) => write!(f, "{id:?}"),
+            PeripheralId::Simulated {
+                adapter_index: 0,
+                address,
+            } => write!(f, "{address}"),
+            PeripheralId::Simulated {
+                adapter_index,
+                address,
+            } => write!(f, "{address}@{adapter_index}"),
+            PeripheralId::Replayed(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+/// Entry of the GATT table
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GattAttribute {
+    Service(Uuid),
+    Characteristic {
+        service_uuid: Uuid,
+        uuid: Uuid,
+    },
+    Descriptor {
+        service_uuid: Uuid,
+        characteristic_uuid: Uuid,
+        uuid: Uuid,
+    },
+}
+
+impl From<&Descriptor> for GattAttribute {
+    fn from(descriptor: &Descriptor) -> Self {
+        GattAttribute::Descriptor {
+            service_uuid: descriptor.service_uuid,
+            characteristic_uuid: descriptor.characteristic_uuid,
+            uuid: descriptor.uuid,
+        }
+    }
+}
+
+/// Subset of the btleplug `CentralEvent` the scanner reacts to
+#[derive(Debug, Clone)]
+pub enum AdapterEvent {
+    DeviceDiscovered(PeripheralId),
+    /// Any advertised property changed, including rssi
+    DeviceUpdated(PeripheralId),
+    DeviceDisconnected(PeripheralId),
+    ManufacturerDataAdvertisement {
+        id: PeripheralId,
+        manufacturer_data: HashMap<u16, Vec<u8>>,
+    },
+    ServiceDataAdvertisement {
+        id: PeripheralId,
+        service_data: HashMap<Uuid, Vec<u8>>,
+    },
+}
+
+impl AdapterEvent {
+    pub fn id(&self) -> &PeripheralId {
+        match self {
+            AdapterEvent::DeviceDiscovered(id)
+            | AdapterEvent::DeviceUpdated(id)
+            | AdapterEvent::DeviceDisconnected(id)
+            | AdapterEvent::ManufacturerDataAdvertisement { id, .. }
+            | AdapterEvent::ServiceDataAdvertisement { id, .. } => id,
+        }
+    }
+}
+
+/// Abstraction over the BLE stack. The app talks only to these traits so the same views and
+/// side effects work with both the platform adapter provided by btleplug and the simulator.
+#[async_trait]
+pub trait BleManager: Debug + Send + Sync {
+    async fn adapters(&self) -> Result<Vec<Arc<dyn BleAdapter>>>;
+}
+
+#[async_trait]
+pub trait BleAdapter: Debug + Send + Sync {
+    async fn adapter_info(&self) -> Result<String>;
+    async fn start_scan(&self, filter: ScanFilter) -> Result<()>;
+    async fn stop_scan(&self) -> Result<()>;
+    async fn peripherals(&self) -> Result<Vec<Arc<dyn BlePeripheral>>>;
+    async fn peripheral(&self, id: &PeripheralId) -> Result<Arc<dyn BlePeripheral>>;
+    /// Stream of the adapter events, subscribe before starting the scan to get every discovery
+    async fn events(&self) -> Result<BoxStream<AdapterEvent>>;
+}
+
+#[async_trait]
+pub trait BlePeripheral: Debug + Send + Sync {
+    fn id(&self) -> PeripheralId;
+    fn services(&self) -> BTreeSet<Service>;
+    fn characteristics(&self) -> BTreeSet<Characteristic>;
+    async fn properties(&self) -> Result<Option<PeripheralProperties>>;
+    async fn is_connected(&self) -> Result<bool>;
+    async fn connect(&self) -> Result<()>;
+    async fn disconnect(&self) -> Result<()>;
+    async fn discover_services(&self) -> Result<()>;
+    async fn read(&self, characteristic: &Characteristic) -> Result<Vec<u8>>;
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> Result<()>;
+    async fn subscribe(&self, characteristic: &Characteristic) -> Result<()>;
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> Result<()>;
+    async fn notifications(&self) -> Result<BoxStream<ValueNotification>>;
+    async fn read_descriptor(&self, descriptor: &Descriptor) -> Result<Vec<u8>>;
+    async fn write_descriptor(&self, descriptor: &Descriptor, data: &[u8]) -> Result<()>;
+
+    /// ATT handles of the discovered attributes, characteristics are keyed by the value handle.
+    /// btleplug does not expose the handles so only the simulator knows them.
+    fn attribute_handles(&self) -> BTreeMap<GattAttribute, u16> {
+        BTreeMap::new()
+    }
+}
+
+#[async_trait]
+impl BleManager for btleplug::platform::Manager {
+    async fn adapters(&self) -> Result<Vec<Arc<dyn BleAdapter>>> {
+        Ok(btleplug::api::Manager::adapters(self)
+            .await?
+            .into_iter()
+            .map(|adapter| Arc::new(adapter) as Arc<dyn BleAdapter>)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl BleAdapter for btleplug::platform::Adapter {
+    async fn adapter_info(&self) -> Result<String> {
+        Central::adapter_info(self).await
+    }
+
+    async fn start_scan(&self, filter: ScanFilter) -> Result<()> {
+        Central::start_scan(self, filter).await
+    }
+
+    async fn stop_scan(&self) -> Result<()> {
+        Central::stop_scan(self).await
+    }
+
+    async fn peripherals(&self) -> Result<Vec<Arc<dyn BlePeripheral>>> {
+        Ok(Central::peripherals(self)
+            .await?
+            .into_iter()
+            .map(|peripheral| Arc::new(peripheral) as Arc<dyn BlePeripheral>)
+            .collect())
+    }
+
+    async fn peripheral(&self, id: &PeripheralId) -> Result<Arc<dyn BlePeripheral>> {
+        match id {
+            PeripheralId::Platform(id) => Ok(Arc::new(Central::peripheral(self, id).await?)),
+            _ => Err(btleplug::Error::DeviceNotFound),
+        }
+    }
+
+    async fn events(&self) -> Result<BoxStream<AdapterEvent>> {
+        Ok(Box::pin(Central::events(self).await?.filter_map(
+            |event| async move {
+                match event {
+                    CentralEvent::DeviceDiscovered(id) => {
+                        Some(AdapterEvent::DeviceDiscovered(PeripheralId::Platform(id)))
+                    }
+                    CentralEvent::DeviceUpdated(id)
+                    | CentralEvent::ServicesAdvertisement { id, .. } => {
+                        Some(AdapterEvent::DeviceUpdated(PeripheralId::Platform(id)))
+                    }
+                    CentralEvent::DeviceDisconnected(id) => {
+                        Some(AdapterEvent::DeviceDisconnected(PeripheralId::Platform(id)))
+                    }
+                    CentralEvent::ManufacturerDataAdvertisement {
+                        id,
+                        manufacturer_data,
+                    } => Some(AdapterEvent::ManufacturerDataAdvertisement {
+                        id: PeripheralId::Platform(id),
+                        manufacturer_data,
+                    }),
+                    CentralEvent::ServiceDataAdvertisement { id, service_data } => {
+                        Some(AdapterEvent::ServiceDataAdvertisement {
+                            id: PeripheralId::Platform(id),
+                            service_data,
+                        })
+                    }
+                    CentralEvent::DeviceConnected(_) => None,
+                }
+            },
+        )))
+    }
+}
+
+#[async_trait]
+impl BlePeripheral for btleplug::platform::Peripheral {
+    fn id(&self) -> PeripheralId {
+        PeripheralId::Platform(btleplug::api::Peripheral::id(self))
+    }
+
+    fn services(&self) -> BTreeSet<Service> {
+        btleplug::api::Peripheral::services(self)
+    }
+
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        btleplug::api::Peripheral::characteristics(self)
+    }
+
+    async fn properties(&self) -> Result<Option<PeripheralProperties>> {
+        btleplug::api::Peripheral::properties(self).await
+    }
+
+    async fn is_connected(&self) -> Result<bool> {
+        btleplug::api::Peripheral::is_connected(self).await
+    }
+
+    async fn connect(&self) -> Result<()> {
+        btleplug::api::Peripheral::connect(self).await
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        btleplug::api::Peripheral::disconnect(self).await
+    }
+
+    async fn discover_services(&self) -> Result<()> {
+        btleplug::api::Peripheral::discover_services(self).await
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> Result<Vec<u8>> {
+        btleplug::api::Peripheral::read(self, characteristic).await
+    }
+
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> Result<()> {
+        btleplug::api::Peripheral::write(self, characteristic, data, write_type).await
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> Result<()> {
+        btleplug::api::Peripheral::subscribe(self, characteristic).await
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> Result<()> {
+        btleplug::api::Peripheral::unsubscribe(self, characteristic).await
+    }
+
+    async fn notifications(&self) -> Result<BoxStream<ValueNotification>> {
+        btleplug::api::Peripheral::notifications(self).await
+    }
+
+    async fn read_descriptor(&self, descriptor: &Descriptor) -> Result<Vec<u8>> {
+        btleplug::api::Peripheral::read_descriptor(self, descriptor).await
+    }
+
+    async fn write_descriptor(&self, descriptor: &Descriptor, data: &[u8]) -> Result<()> {
+        btleplug::api::Peripheral::write_descriptor(self, descriptor, data).await
+    }
+}
+
+/// Creates the manager of the real bluetooth stack available on this platform
+pub async fn platform_manager() -> Result<Arc<dyn BleManager>> {
+    Ok(Arc::new(btleplug::platform::Manager::new().await?))
+}
@@ -0,0 +1,281 @@
+use crate::bluetooth::{
+    ble_default_services::create_ble_uuid, history::ONE_METER_LOSS, payload::to_hex,
+};
+use byteorder::{BigEndian, ByteOrder};
+use std::{collections::HashMap, fmt::Display};
+use uuid::Uuid;
+
+const APPLE_COMPANY_ID: u16 = 0x004C;
+
+lazy_static::lazy_static! {
+    static ref EDDYSTONE_SERVICE: Uuid = create_ble_uuid(0xFEAA);
+}
+
+const EDDYSTONE_URL_SCHEMES: [&str; 4] = ["http://www.", "https://www.", "http://", "https://"];
+const EDDYSTONE_URL_EXPANSIONS: [&str; 14] = [
+    ".com/", ".org/", ".edu/", ".net/", ".info/", ".biz/", ".gov/", ".com", ".org", ".edu", ".net",
+    ".info", ".biz", ".gov",
+];
+
+/// Identity of the beacon decoded from its advertisement frame
+#[derive(Debug, Clone, PartialEq)]
+pub enum BeaconFrame {
+    IBeacon {
+        uuid: Uuid,
+        major: u16,
+        minor: u16,
+        /// RSSI in dBm expected at 1 meter distance
+        measured_power: i8,
+    },
+    AltBeacon {
+        manufacturer_id: u16,
+        beacon_id: [u8; 20],
+        /// RSSI in dBm expected at 1 meter distance
+        reference_rssi: i8,
+    },
+    EddystoneUid {
+        namespace: [u8; 10],
+        instance: [u8; 6],
+        /// RSSI in dBm expected at 0 meters distance
+        tx_power: i8,
+    },
+    EddystoneUrl {
+        url: String,
+        tx_power: i8,
+    },
+    EddystoneTlm {
+        battery_mv: u16,
+        /// `None` when the beacon does not support temperature sensing
+        temperature: Option<f32>,
+        advertisement_count: u32,
+        uptime_secs: u32,
+    },
+    /// Telemetry frame encrypted for the owner of the ephemeral id
+    EddystoneEncryptedTlm,
+    EddystoneEid {
+        ephemeral_id: [u8; 8],
+        tx_power: i8,
+    },
+}
+
+impl Display for BeaconFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BeaconFrame::IBeacon {
+                uuid,
+                major,
+                minor,
+                measured_power,
+            } => write!(
+                f,
+                "iBeacon {uuid} major {major} minor {minor} power {measured_power} dBm"
+            ),
+            BeaconFrame::AltBeacon {
+                manufacturer_id,
+                beacon_id,
+                reference_rssi,
+            } => {
+                // first 16 bytes are conventionally an uuid followed by two 2 byte identifiers
+                let (uuid, rest) = beacon_id.split_at(16);
+                write!(
+                    f,
+                    "AltBeacon {} {} {} mfg 0x{manufacturer_id:04X} ref {reference_rssi} dBm",
+                    Uuid::from_slice(uuid).unwrap_or_default(),
+                    BigEndian::read_u16(&rest[..2]),
+                    BigEndian::read_u16(&rest[2..])
+                )
+            }
+            BeaconFrame::EddystoneUid {
+                namespace,
+                instance,
+                tx_power,
+            } => write!(
+                f,
+                "Eddystone UID {} {} tx {tx_power} dBm",
+                to_hex(namespace),
+                to_hex(instance)
+            ),
+            BeaconFrame::EddystoneUrl { url, tx_power } => {
+                write!(f, "Eddystone URL {url} tx {tx_power} dBm")
+            }
+            BeaconFrame::EddystoneTlm {
+                battery_mv,
+                temperature,
+                advertisement_count,
+                uptime_secs,
+            } => {
+                write!(f, "Eddystone TLM battery {battery_mv}mV")?;
+                if let Some(temperature) = temperature {
+                    write!(f, " temp {temperature}°C")?;
+                }
+
+                write!(f, " adv {advertisement_count} uptime {uptime_secs}s")
+            }
+            BeaconFrame::EddystoneEncryptedTlm => write!(f, "Eddystone TLM (encrypted)"),
+            BeaconFrame::EddystoneEid {
+                ephemeral_id,
+                tx_power,
+            } => write!(
+                f,
+                "Eddystone EID {} tx {tx_power} dBm",
+                to_hex(ephemeral_id)
+            ),
+        }
+    }
+}
+
+impl BeaconFrame {
+    /// Parses manufacturer specific data without the company identifier, as btleplug provides it
+    pub fn parse_manufacturer_data(company_id: u16, data: &[u8]) -> Option<Self> {
+        match data {
+            [0x02, 0x15, rest @ ..] if company_id == APPLE_COMPANY_ID && rest.len() == 21 => {
+                Some(BeaconFrame::IBeacon {
+                    uuid: Uuid::from_slice(&rest[..16]).ok()?,
+                    major: BigEndian::read_u16(&rest[16..18]),
+                    minor: BigEndian::read_u16(&rest[18..20]),
+                    measured_power: rest[20] as i8,
+                })
+            }
+            // the last byte is reserved for the manufacturer use
+            [0xBE, 0xAC, rest @ ..] if rest.len() == 22 => Some(BeaconFrame::AltBeacon {
+                manufacturer_id: company_id,
+                beacon_id: rest[..20].try_into().ok()?,
+                reference_rssi: rest[20] as i8,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn parse_eddystone(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x00, tx_power, rest @ ..] if rest.len() >= 16 => Some(BeaconFrame::EddystoneUid {
+                namespace: rest[..10].try_into().ok()?,
+                instance: rest[10..16].try_into().ok()?,
+                tx_power: *tx_power as i8,
+            }),
+            [0x10, tx_power, scheme, encoded @ ..] => {
+                let mut url = EDDYSTONE_URL_SCHEMES.get(*scheme as usize)?.to_string();
+                for byte in encoded {
+                    match EDDYSTONE_URL_EXPANSIONS.get(*byte as usize) {
+                        Some(expansion) => url.push_str(expansion),
+                        None if byte.is_ascii_graphic() => url.push(*byte as char),
+                        None => return None,
+                    }
+                }
+
+                Some(BeaconFrame::EddystoneUrl {
+                    url,
+                    tx_power: *tx_power as i8,
+                })
+            }
+            [0x20, 0x00, rest @ ..] if rest.len() >= 12 => {
+                let temperature = BigEndian::read_i16(&rest[2..4]);
+
+                Some(BeaconFrame::EddystoneTlm {
+                    battery_mv: BigEndian::read_u16(&rest[..2]),
+                    // signed 8.8 fixed point, 0x8000 means not supported
+                    temperature: (temperature != i16::MIN).then_some(temperature as f32 / 256.0),
+                    advertisement_count: BigEndian::read_u32(&rest[4..8]),
+                    // counted in 0.1 second units
+                    uptime_secs: BigEndian::read_u32(&rest[8..12]) / 10,
+                })
+            }
+            [0x20, 0x01, ..] => Some(BeaconFrame::EddystoneEncryptedTlm),
+            [0x30, tx_power, rest @ ..] if rest.len() >= 8 => Some(BeaconFrame::EddystoneEid {
+                ephemeral_id: rest[..8].try_into().ok()?,
+                tx_power: *tx_power as i8,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Decodes all the beacon frames found in the advertisement
+    pub fn parse_advertisement(
+        manufacturer_data: &HashMap<u16, Vec<u8>>,
+        service_data: &HashMap<Uuid, Vec<u8>>,
+    ) -> Vec<Self> {
+        let mut beacons = manufacturer_data
+            .iter()
+            .filter_map(|(company_id, data)| Self::parse_manufacturer_data(*company_id, data))
+            .collect::<Vec<_>>();
+
+        beacons.extend(
+            service_data
+                .get(&EDDYSTONE_SERVICE)
+                .and_then(|data| Self::parse_eddystone(data)),
+        );
+
+        beacons
+    }
+
+    /// RSSI in dBm the beacon is calibrated to be received with at 1 meter
+    pub fn reference_rssi(&self) -> Option<i16> {
+        match self {
+            BeaconFrame::IBeacon { measured_power, .. } => Some(*measured_power as i16),
+            BeaconFrame::AltBeacon { reference_rssi, .. } => Some(*reference_rssi as i16),
+            BeaconFrame::EddystoneUid { tx_power, .. }
+            | BeaconFrame::EddystoneUrl { tx_power, .. }
+            | BeaconFrame::EddystoneEid { tx_power, .. } => Some(*tx_power as i16 - ONE_METER_LOSS),
+            BeaconFrame::EddystoneTlm { .. } | BeaconFrame::EddystoneEncryptedTlm => None,
+        }
+    }
+}
+
+#[test]
+fn parses_beacon_frames() {
+    let mut ibeacon = vec![0x02, 0x15];
+    ibeacon.extend(
+        Uuid::parse_str("f7826da6-4fa2-4e98-8024-bc5b71e0893e")
+            .unwrap()
+            .as_bytes(),
+    );
+    ibeacon.extend([0x00, 0x01, 0x00, 0x2a, 0xc5]);
+
+    assert_eq!(
+        BeaconFrame::parse_manufacturer_data(0x004C, &ibeacon)
+            .unwrap()
+            .to_string(),
+        "iBeacon f7826da6-4fa2-4e98-8024-bc5b71e0893e major 1 minor 42 power -59 dBm"
+    );
+    // same payload from another company is not an iBeacon
+    assert_eq!(BeaconFrame::parse_manufacturer_data(0x0059, &ibeacon), None);
+
+    let mut altbeacon = vec![0xBE, 0xAC];
+    altbeacon.extend([0x11; 16]);
+    altbeacon.extend([0x00, 0x02, 0x00, 0x03, 0xc0, 0x00]);
+    assert_eq!(
+        BeaconFrame::parse_manufacturer_data(0x0118, &altbeacon)
+            .unwrap()
+            .to_string(),
+        "AltBeacon 11111111-1111-1111-1111-111111111111 2 3 mfg 0x0118 ref -64 dBm"
+    );
+
+    let mut url = vec![0x10, 0xeb, 0x03];
+    url.extend(b"example");
+    url.extend([0x00, b'x']);
+    assert_eq!(
+        BeaconFrame::parse_eddystone(&url).unwrap().to_string(),
+        "Eddystone URL https://example.com/x tx -21 dBm"
+    );
+
+    let mut uid = vec![0x00, 0xee];
+    uid.extend(1..=16);
+    assert_eq!(
+        BeaconFrame::parse_eddystone(&uid).unwrap().to_string(),
+        "Eddystone UID 0102030405060708090a 0b0c0d0e0f10 tx -18 dBm"
+    );
+
+    let tlm = [
+        0x20, 0x00, 0x0b, 0xb8, 0x15, 0x80, 0x00, 0x00, 0x00, 0x64, 0x00, 0x00, 0x01, 0x2c,
+    ];
+    assert_eq!(
+        BeaconFrame::parse_eddystone(&tlm).unwrap().to_string(),
+        "Eddystone TLM battery 3000mV temp 21.5°C adv 100 uptime 30s"
+    );
+
+    let eid = [0x30, 0xf0, 1, 2, 3, 4, 5, 6, 7, 8];
+    assert_eq!(
+        BeaconFrame::parse_eddystone(&eid).unwrap().to_string(),
+        "Eddystone EID 0102030405060708 tx -16 dBm"
+    );
+}
@@ -9,6 +9,20 @@ pub(crate) const fn create_ble_uuid(uuid: u16) -> Uuid {
     Uuid::from_u128(converted_uuid)
 }
 
+/// Parses both full 128bit uuids and short 16bit form of standard uuids e.g. "2a19" or "0x2A19"
+pub fn parse_ble_uuid(value: &str) -> Result<Uuid, uuid::Error> {
+    let value = value.trim();
+    let short_uuid = value.trim_start_matches("0x").trim_start_matches("0X");
+
+    if short_uuid.len() == 4 {
+        if let Ok(short_uuid) = u16::from_str_radix(short_uuid, 16) {
+            return Ok(create_ble_uuid(short_uuid));
+        }
+    }
+
+    Uuid::parse_str(value)
+}
+
 lazy_static::lazy_static! {
     pub static ref SPECIAL_SERVICES_NAMES: HashMap<uuid::Uuid, &'static str> = HashMap::from([
         (create_ble_uuid(0x1800), "Generic Access (0x1800)"),
@@ -190,6 +204,65 @@ lazy_static::lazy_static! {
       (create_ble_uuid(0x2AB3), "Altitude (0x2AB3)"),
     (create_ble_uuid(0x2AB4), "Uncertainty (0x2AB4)"),
   ]);
+
+    pub static ref SPECIAL_DESCRIPTORS_NAMES: HashMap<uuid::Uuid, &'static str> = HashMap::from([
+        (create_ble_uuid(0x2900), "Extended Properties (0x2900)"),
+        (create_ble_uuid(0x2901), "User Description (0x2901)"),
+        (create_ble_uuid(0x2902), "Client Characteristic Configuration (0x2902)"),
+        (create_ble_uuid(0x2903), "Server Characteristic Configuration (0x2903)"),
+        (create_ble_uuid(0x2904), "Presentation Format (0x2904)"),
+        (create_ble_uuid(0x2905), "Aggregate Format (0x2905)"),
+        (create_ble_uuid(0x2906), "Valid Range (0x2906)"),
+    ]);
+
+    /// Most common company identifiers used in the manufacturer specific advertisement data
+    pub static ref COMPANY_NAMES: HashMap<u16, &'static str> = HashMap::from([
+        (0x0000, "Ericsson Technology Licensing"),
+        (0x0001, "Nokia Mobile Phones"),
+        (0x0002, "Intel Corp."),
+        (0x0003, "IBM Corp."),
+        (0x0004, "Toshiba Corp."),
+        (0x0006, "Microsoft"),
+        (0x0008, "Motorola"),
+        (0x0009, "Infineon Technologies AG"),
+        (0x000A, "Qualcomm Technologies International, Ltd. (QTIL)"),
+        (0x000D, "Texas Instruments Inc."),
+        (0x000F, "Broadcom Corporation"),
+        (0x001D, "Qualcomm"),
+        (0x0025, "NXP Semiconductors"),
+        (0x0030, "ST Microelectronics"),
+        (0x0046, "MediaTek, Inc."),
+        (0x004C, "Apple, Inc."),
+        (0x0057, "Harman International Industries, Inc."),
+        (0x0059, "Nordic Semiconductor ASA"),
+        (0x005D, "Realtek Semiconductor Corporation"),
+        (0x0065, "HP, Inc."),
+        (0x0075, "Samsung Electronics Co. Ltd."),
+        (0x0078, "Nike, Inc."),
+        (0x0087, "Garmin International, Inc."),
+        (0x009E, "Bose Corporation"),
+        (0x00C4, "LG Electronics"),
+        (0x00D2, "Dialog Semiconductor B.V."),
+        (0x00E0, "Google"),
+        (0x0118, "Radius Networks, Inc."),
+        (0x012D, "Sony Corporation"),
+        (0x0131, "Cypress Semiconductor"),
+        (0x0157, "Anhui Huami Information Technology Co., Ltd."),
+        (0x0171, "Amazon.com Services, LLC"),
+        (0x01DA, "Logitech International SA"),
+        (0x02E5, "Espressif Systems (Shanghai) Co., Ltd."),
+        (0x038F, "Xiaomi Inc."),
+        (0x0499, "Ruuvi Innovations Ltd."),
+        (0x05A7, "Sonos Inc"),
+    ]);
+}
+
+/// Formats the manufacturer company identifier same way as services are named e.g. "Apple, Inc. (0x004C)"
+pub fn format_company_id(company_id: u16) -> String {
+    match COMPANY_NAMES.get(&company_id) {
+        Some(name) => format!("{name} (0x{company_id:04X})"),
+        None => format!("Unknown company (0x{company_id:04X})"),
+    }
 }
 
 #[test]
@@ -199,3 +272,20 @@ pub(crate) fn resolves_special_services() {
         Some(&"Device Information (0x1800)")
     );
 }
+
+#[test]
+fn parses_short_and_full_uuids() {
+    assert_eq!(parse_ble_uuid("2a19").unwrap(), create_ble_uuid(0x2A19));
+    assert_eq!(parse_ble_uuid("0x180D").unwrap(), create_ble_uuid(0x180D));
+    assert_eq!(
+        parse_ble_uuid("0000FFE0-0000-1000-8000-00805F9B34FB").unwrap(),
+        create_ble_uuid(0xFFE0)
+    );
+    assert!(parse_ble_uuid("not a uuid").is_err());
+}
+
+#[test]
+fn formats_company_ids() {
+    assert_eq!(format_company_id(0x004C), "Apple, Inc. (0x004C)");
+    assert_eq!(format_company_id(0xFFFF), "Unknown company (0xFFFF)");
+}
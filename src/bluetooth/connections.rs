@@ -0,0 +1,733 @@
+use crate::{
+    bluetooth::{
+        backend::PeripheralId, ConnectedCharacteristic, ConnectedPeripheral, HandledPeripheral,
+    },
+    error::{Error, Result},
+    export,
+    route::{CharacteristicValue, Route, ValueDirection, ValueUpdateMode},
+    session::{self, ValueSource},
+    Ctx,
+};
+use btleplug::api::CharPropFlags;
+use futures::StreamExt;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    ops::Deref,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::{sync::Notify, task::JoinHandle, time};
+use uuid::Uuid;
+
+/// Delay after the first failed connection attempt, doubled after every next one
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How often the supervisor checks whether the peripheral is still connected, adapters
+/// reporting the disconnection wake it up earlier
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Ring buffer of the latest characteristic values, bounded by --history-limit
+pub type ValueHistory = Arc<RwLock<VecDeque<CharacteristicValue>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// Number of the reconnection attempt in progress
+    Reconnecting(u32),
+    /// Reconnection gave up after --connect-attempts, kept until disconnected by the user
+    Failed,
+}
+
+impl Display for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionState::Connected => write!(f, "connected"),
+            ConnectionState::Reconnecting(attempt) => write!(f, "reconnecting #{attempt}"),
+            ConnectionState::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// Background task streaming the values of a single characteristic into its history
+#[derive(Debug)]
+struct Watcher {
+    mode: ValueUpdateMode,
+    /// Kept to restart the watcher after reconnecting
+    characteristic: ConnectedCharacteristic,
+    history: ValueHistory,
+    handle: JoinHandle<()>,
+}
+
+/// Peripheral kept connected while the user works with other ones. Opened characteristics
+/// keep receiving values in the background until the peripheral is disconnected.
+#[derive(Debug)]
+pub struct Connection {
+    pub peripheral: ConnectedPeripheral,
+    pub connected_at: chrono::DateTime<chrono::Local>,
+    pub state: ConnectionState,
+    histories: HashMap<Uuid, ValueHistory>,
+    watchers: HashMap<Uuid, Watcher>,
+    /// Reconnects the peripheral when it drops, finished once the reconnection failed
+    supervisor: Option<JoinHandle<()>>,
+    disconnected: Arc<Notify>,
+}
+
+impl Connection {
+    pub fn id(&self) -> PeripheralId {
+        self.peripheral.peripheral.ble_peripheral.id()
+    }
+
+    pub fn is_watched(&self, uuid: &Uuid) -> bool {
+        self.watchers
+            .get(uuid)
+            .is_some_and(|watcher| !watcher.handle.is_finished())
+    }
+
+    /// Amount of characteristics receiving values in the background
+    pub fn watched_count(&self) -> usize {
+        self.watchers
+            .values()
+            .filter(|watcher| !watcher.handle.is_finished())
+            .count()
+    }
+
+    /// Amount of values received or written over all the characteristics
+    pub fn values_count(&self) -> usize {
+        self.histories
+            .values()
+            .map(|history| history.read().map_or(0, |history| history.len()))
+            .sum()
+    }
+
+    /// Stops the value streams returning what is needed to restart them
+    fn stop_watchers(&mut self) -> Vec<(ConnectedCharacteristic, ValueUpdateMode, ValueHistory)> {
+        self.watchers
+            .drain()
+            .map(|(_, watcher)| {
+                watcher.handle.abort();
+                (watcher.characteristic, watcher.mode, watcher.history)
+            })
+            .collect()
+    }
+
+    fn stop(&mut self) {
+        self.stop_watchers();
+
+        if let Some(supervisor) = self.supervisor.take() {
+            supervisor.abort();
+        }
+    }
+}
+
+/// Adds the freshly connected peripheral, reconnected one keeps the collected history
+pub fn register(ctx: &Arc<Ctx>, peripheral: ConnectedPeripheral) -> Result<()> {
+    let mut connections = ctx.connections.write()?;
+    let id = peripheral.peripheral.ble_peripheral.id();
+
+    let connection = match connections
+        .iter()
+        .position(|connection| connection.id() == id)
+    {
+        Some(index) => &mut connections[index],
+        None => {
+            connections.push(Connection {
+                peripheral: peripheral.clone(),
+                connected_at: chrono::Local::now(),
+                state: ConnectionState::Connected,
+                histories: HashMap::new(),
+                watchers: HashMap::new(),
+                supervisor: None,
+                disconnected: Arc::new(Notify::new()),
+            });
+
+            connections.last_mut().expect("connection was just added")
+        }
+    };
+
+    connection.state = ConnectionState::Connected;
+    connection.peripheral = peripheral.clone();
+    if connection
+        .supervisor
+        .as_ref()
+        .is_none_or(|supervisor| supervisor.is_finished())
+    {
+        connection.supervisor = Some(tokio::spawn(supervise(
+            Arc::clone(ctx),
+            peripheral.peripheral,
+            Arc::clone(&connection.disconnected),
+        )));
+    }
+
+    Ok(())
+}
+
+/// Called by the scanner once the adapter reported the peripheral disconnected
+pub fn notify_disconnected(ctx: &Ctx, id: &PeripheralId) {
+    if let Ok(connections) = ctx.connections.read() {
+        if let Some(connection) = connections.iter().find(|connection| &connection.id() == id) {
+            connection.disconnected.notify_one();
+        }
+    }
+}
+
+pub fn state(ctx: &Ctx, id: &PeripheralId) -> Option<ConnectionState> {
+    ctx.connections.read().ok().and_then(|connections| {
+        connections
+            .iter()
+            .find(|connection| &connection.id() == id)
+            .map(|connection| connection.state)
+    })
+}
+
+fn set_state(ctx: &Ctx, id: &PeripheralId, state: ConnectionState) {
+    if let Ok(mut connections) = ctx.connections.write() {
+        if let Some(connection) = connections
+            .iter_mut()
+            .find(|connection| &connection.id() == id)
+        {
+            connection.state = state;
+        }
+    }
+}
+
+/// Delay before the next connection attempt after the given amount of failed ones
+pub fn backoff_delay(failed_attempts: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(failed_attempts.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+/// Connects and discovers services, failed attempts are retried with exponential backoff
+/// until --connect-attempts is reached
+pub async fn connect_with_backoff(
+    ctx: &Ctx,
+    peripheral: &HandledPeripheral,
+    mut on_attempt: impl FnMut(u32),
+) -> Result<()> {
+    let mut attempt = 0;
+
+    while !peripheral
+        .ble_peripheral
+        .is_connected()
+        .await
+        .unwrap_or(false)
+    {
+        attempt += 1;
+        on_attempt(attempt);
+
+        tracing::debug!(attempt, "Connecting to peripheral.");
+        let result = super::connect_with_timeout(peripheral.ble_peripheral.as_ref()).await;
+        session::record_connect_attempt(ctx, peripheral, &result);
+
+        if let Err(e) = result {
+            tracing::error!(?e, attempt, "Failed to connect to peripheral.");
+
+            if ctx.args.connect_attempts != 0 && attempt >= ctx.args.connect_attempts {
+                return Err(Error::client(format!(
+                    "Failed to connect to {} after {attempt} attempts: {e}",
+                    peripheral.name
+                )));
+            }
+
+            time::sleep(backoff_delay(attempt)).await;
+        }
+    }
+
+    peripheral.ble_peripheral.discover_services().await?;
+    session::record_services(ctx, peripheral);
+
+    Ok(())
+}
+
+/// Watches the connection of the peripheral and brings it back with all the value streams once it drops
+async fn supervise(ctx: Arc<Ctx>, peripheral: HandledPeripheral, disconnected: Arc<Notify>) {
+    let id = peripheral.ble_peripheral.id();
+
+    loop {
+        tokio::select! {
+            _ = time::sleep(HEALTH_CHECK_INTERVAL) => {}
+            _ = disconnected.notified() => {}
+        }
+
+        if peripheral
+            .ble_peripheral
+            .is_connected()
+            .await
+            .unwrap_or(false)
+        {
+            continue;
+        }
+
+        tracing::warn!(%id, "Connection lost, reconnecting.");
+        let watched = match ctx.connections.write() {
+            Ok(mut connections) => connections
+                .iter_mut()
+                .find(|connection| connection.id() == id)
+                .map(Connection::stop_watchers)
+                .unwrap_or_default(),
+            Err(_) => return,
+        };
+
+        if let Err(e) = connect_with_backoff(&ctx, &peripheral, |attempt| {
+            set_state(&ctx, &id, ConnectionState::Reconnecting(attempt))
+        })
+        .await
+        {
+            tracing::error!(?e, "Failed to reconnect to peripheral.");
+            set_state(&ctx, &id, ConnectionState::Failed);
+            return;
+        }
+
+        let mut connected = ConnectedPeripheral::new(&ctx, peripheral.clone());
+        connected.read_descriptors(&ctx).await;
+        if let Ok(mut connections) = ctx.connections.write() {
+            if let Some(connection) = connections
+                .iter_mut()
+                .find(|connection| connection.id() == id)
+            {
+                connection.peripheral = connected.clone();
+                connection.state = ConnectionState::Connected;
+            }
+        }
+
+        for (characteristic, mode, history) in watched {
+            if let Err(e) = watch(&ctx, &connected, &characteristic, mode, history).await {
+                tracing::error!(?e, "Failed to resubscribe to characteristic.");
+            }
+        }
+    }
+}
+
+pub fn find(ctx: &Ctx, id: &PeripheralId) -> Option<ConnectedPeripheral> {
+    ctx.connections.read().ok().and_then(|connections| {
+        connections
+            .iter()
+            .find(|connection| &connection.id() == id)
+            .map(|connection| connection.peripheral.clone())
+    })
+}
+
+pub fn is_watched(ctx: &Ctx, id: &PeripheralId, uuid: &Uuid) -> bool {
+    ctx.connections.read().is_ok_and(|connections| {
+        connections
+            .iter()
+            .any(|connection| &connection.id() == id && connection.is_watched(uuid))
+    })
+}
+
+/// History shared by every view of the characteristic and the mode its values are streamed with
+pub fn open_characteristic(
+    ctx: &Ctx,
+    id: &PeripheralId,
+    characteristic: &ConnectedCharacteristic,
+) -> (ValueHistory, ValueUpdateMode) {
+    let default_mode = ValueUpdateMode::default_for(characteristic.ble_characteristic.properties);
+    let Ok(mut connections) = ctx.connections.write() else {
+        return (Default::default(), default_mode);
+    };
+
+    match connections
+        .iter_mut()
+        .find(|connection| &connection.id() == id)
+    {
+        Some(connection) => {
+            let mode = connection
+                .watchers
+                .get(&characteristic.uuid)
+                .filter(|watcher| !watcher.handle.is_finished())
+                .map_or(default_mode, |watcher| watcher.mode);
+
+            let history = connection.histories.entry(characteristic.uuid).or_default();
+
+            (Arc::clone(history), mode)
+        }
+        None => (Default::default(), default_mode),
+    }
+}
+
+/// Starts streaming the characteristic values in the background, running watcher with
+/// the same mode is kept as is and the one with another mode is replaced
+pub async fn watch(
+    ctx: &Arc<Ctx>,
+    peripheral: &ConnectedPeripheral,
+    characteristic: &ConnectedCharacteristic,
+    mode: ValueUpdateMode,
+    history: ValueHistory,
+) -> Result<()> {
+    let id = peripheral.peripheral.ble_peripheral.id();
+    let replaced_mode = {
+        let mut connections = ctx.connections.write()?;
+        let Some(connection) = connections
+            .iter_mut()
+            .find(|connection| connection.id() == id)
+        else {
+            return Ok(());
+        };
+
+        match connection.watchers.get(&characteristic.uuid) {
+            Some(watcher) if watcher.mode == mode && !watcher.handle.is_finished() => return Ok(()),
+            _ => {}
+        }
+
+        let replaced = connection
+            .watchers
+            .remove(&characteristic.uuid)
+            .map(|watcher| {
+                watcher.handle.abort();
+                watcher.mode
+            });
+
+        if mode == ValueUpdateMode::Poll
+            && !characteristic
+                .ble_characteristic
+                .properties
+                .contains(CharPropFlags::READ)
+        {
+            return Ok(());
+        }
+
+        let handle = tokio::spawn(run_watcher(
+            Arc::clone(ctx),
+            peripheral.peripheral.clone(),
+            characteristic.clone(),
+            mode,
+            Arc::clone(&history),
+        ));
+
+        connection.watchers.insert(
+            characteristic.uuid,
+            Watcher {
+                mode,
+                characteristic: characteristic.clone(),
+                history,
+                handle,
+            },
+        );
+
+        replaced
+    };
+
+    // resubscribing on the finished stream must not be undone
+    if replaced_mode == Some(ValueUpdateMode::Subscribe) && mode != ValueUpdateMode::Subscribe {
+        let ble_peripheral = &peripheral.peripheral.ble_peripheral;
+        if let Err(e) = ble_peripheral
+            .unsubscribe(&characteristic.ble_characteristic)
+            .await
+        {
+            tracing::error!(?e, "Failed to unsubscribe from characteristic.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Stops all the background watchers of the peripheral and disconnects from it
+pub async fn disconnect(ctx: &Ctx, id: &PeripheralId) -> Result<()> {
+    let connection = {
+        let mut connections = ctx.connections.write()?;
+        connections
+            .iter()
+            .position(|connection| &connection.id() == id)
+            .map(|index| connections.remove(index))
+    };
+
+    if let Some(mut connection) = connection {
+        // supervisor must not bring the peripheral back
+        connection.stop();
+        super::disconnect_with_timeout(connection.peripheral.peripheral.ble_peripheral.as_ref())
+            .await;
+    }
+
+    Ok(())
+}
+
+/// Runs disconnect in the background so the UI is not blocked by the slow adapters
+pub fn spawn_disconnect(ctx: &Arc<Ctx>, id: PeripheralId) {
+    let ctx = Arc::clone(ctx);
+
+    tokio::spawn(async move {
+        if let Err(e) = disconnect(&ctx, &id).await {
+            tracing::error!(?e, "Failed to disconnect from peripheral.");
+        }
+    });
+}
+
+fn push_value(
+    ctx: &Ctx,
+    peripheral: &HandledPeripheral,
+    characteristic: &ConnectedCharacteristic,
+    source: ValueSource,
+    data: Vec<u8>,
+    history: &ValueHistory,
+) {
+    session::record_value(ctx, peripheral, characteristic, source, &data);
+
+    let value = CharacteristicValue {
+        time: chrono::Local::now(),
+        data,
+        direction: ValueDirection::Incoming,
+    };
+
+    export::record_value(ctx, peripheral.address, characteristic, &value);
+    push_history(ctx, history, value);
+}
+
+/// Appends the value dropping the oldest ones over --history-limit, the browsed historical
+/// value of the opened characteristic stays the same
+pub fn push_history(ctx: &Ctx, history: &ValueHistory, value: CharacteristicValue) {
+    let mut dropped = 0;
+    if let Ok(mut values) = history.write() {
+        while values.len() >= ctx.args.history_limit.max(1) {
+            values.pop_front();
+            dropped += 1;
+        }
+
+        values.push_back(value);
+    }
+
+    // history lock is released first, the rendering takes the route lock before the history one
+    if dropped > 0 {
+        if let Ok(route) = ctx.active_route.read() {
+            if let Route::CharacteristicView {
+                history: viewed,
+                historical_view_index,
+                ..
+            } = route.deref()
+            {
+                if Arc::ptr_eq(viewed, history) {
+                    historical_view_index.shift_back(dropped);
+                }
+            }
+        }
+    }
+}
+
+async fn run_watcher(
+    ctx: Arc<Ctx>,
+    peripheral: HandledPeripheral,
+    characteristic: ConnectedCharacteristic,
+    mode: ValueUpdateMode,
+    history: ValueHistory,
+) {
+    let result = match mode {
+        ValueUpdateMode::Subscribe => {
+            stream_notifications(&ctx, &peripheral, &characteristic, &history).await
+        }
+        ValueUpdateMode::Poll => poll_values(&ctx, &peripheral, &characteristic, &history).await,
+    };
+
+    if let Err(e) = result {
+        tracing::error!(?e, "Failed to stream characteristic values.");
+
+        if let Ok(global_error) = ctx.global_error.lock().as_deref_mut() {
+            *global_error = Some(e);
+        }
+    }
+}
+
+async fn stream_notifications(
+    ctx: &Ctx,
+    peripheral: &HandledPeripheral,
+    characteristic: &ConnectedCharacteristic,
+    history: &ValueHistory,
+) -> Result<()> {
+    let ble_peripheral = &peripheral.ble_peripheral;
+    // stream must be acquired before subscribing to not miss the first notification
+    let mut notifications = ble_peripheral.notifications().await?;
+    ble_peripheral
+        .subscribe(&characteristic.ble_characteristic)
+        .await?;
+
+    while let Some(notification) = notifications.next().await {
+        if notification.uuid != characteristic.uuid {
+            continue;
+        }
+
+        push_value(
+            ctx,
+            peripheral,
+            characteristic,
+            ValueSource::Notification,
+            notification.value,
+            history,
+        );
+    }
+
+    Ok(())
+}
+
+async fn poll_values(
+    ctx: &Ctx,
+    peripheral: &HandledPeripheral,
+    characteristic: &ConnectedCharacteristic,
+    history: &ValueHistory,
+) -> Result<()> {
+    let ble_peripheral = &peripheral.ble_peripheral;
+
+    loop {
+        match ble_peripheral
+            .read(&characteristic.ble_characteristic)
+            .await
+        {
+            Ok(data) => push_value(
+                ctx,
+                peripheral,
+                characteristic,
+                ValueSource::Read,
+                data,
+                history,
+            ),
+            Err(e) => tracing::error!(?e, "Failed to read characteristic."),
+        }
+
+        if !ble_peripheral.is_connected().await.unwrap_or(false) {
+            return Ok(());
+        }
+
+        time::sleep(Duration::from_millis(ctx.args.scan_interval)).await;
+    }
+}
+
+#[tokio::test]
+async fn keeps_streaming_values_in_background() {
+    use crate::bluetooth::{
+        ble_default_services::create_ble_uuid,
+        simulator::{scan_once, simulated_ctx, wait_for, TEST_SIMULATION},
+    };
+
+    let ctx = Arc::new(simulated_ctx(TEST_SIMULATION, &["--history-limit", "3"]));
+    let peripheral = scan_once(&ctx, 1).await.remove(0);
+    let ble_peripheral = Arc::clone(&peripheral.ble_peripheral);
+    while ble_peripheral.connect().await.is_err() {}
+    ble_peripheral.discover_services().await.unwrap();
+
+    let peripheral = ConnectedPeripheral::new(&ctx, peripheral);
+    let id = ble_peripheral.id();
+    register(&ctx, peripheral.clone()).unwrap();
+    assert_eq!(state(&ctx, &id), Some(ConnectionState::Connected));
+
+    let characteristic = peripheral
+        .characteristics
+        .iter()
+        .find(|char| char.uuid == create_ble_uuid(0x2A6E))
+        .unwrap()
+        .clone();
+    let (history, mode) = open_characteristic(&ctx, &id, &characteristic);
+    assert_eq!(mode, ValueUpdateMode::Subscribe);
+
+    watch(
+        &ctx,
+        &peripheral,
+        &characteristic,
+        mode,
+        Arc::clone(&history),
+    )
+    .await
+    .unwrap();
+    assert!(is_watched(&ctx, &id, &characteristic.uuid));
+
+    // reopening the characteristic shares the history collected in the background
+    let (reopened, _) = open_characteristic(&ctx, &id, &characteristic);
+    assert!(Arc::ptr_eq(&history, &reopened));
+    // the history is capped by --history-limit while the values keep coming
+    let received = wait_for(|| {
+        let history = history.read().unwrap();
+        (history.len() == 3).then(|| history.back().unwrap().time)
+    })
+    .await;
+    wait_for(|| (history.read().unwrap().back().unwrap().time > received).then_some(())).await;
+    assert_eq!(history.read().unwrap().len(), 3);
+
+    disconnect(&ctx, &id).await.unwrap();
+    assert_eq!(state(&ctx, &id), None);
+    assert!(!ble_peripheral.is_connected().await.unwrap());
+}
+
+#[tokio::test]
+async fn reconnects_dropped_peripherals() {
+    use crate::bluetooth::{
+        ble_default_services::create_ble_uuid,
+        simulator::{scan_once, simulated_ctx, wait_for},
+    };
+
+    assert_eq!(backoff_delay(1), Duration::from_millis(500));
+    assert_eq!(backoff_delay(3), Duration::from_secs(2));
+    assert_eq!(backoff_delay(20), MAX_BACKOFF);
+
+    let simulation = r#"
+[[device]]
+name = "Rebooting"
+address = "AA:BB:CC:DD:EE:01"
+disconnect_after_ms = 300
+
+[[device.characteristic]]
+service = "181a"
+uuid = "2a6e"
+properties = ["notify"]
+notify_interval_ms = 10
+
+[[device]]
+name = "Broken"
+address = "AA:BB:CC:DD:EE:02"
+disconnect_after_ms = 300
+failed_connections = 1
+"#;
+    let ctx = Arc::new(simulated_ctx(simulation, &["--connect-attempts", "1"]));
+
+    let mut connected = vec![];
+    for peripheral in scan_once(&ctx, 2).await {
+        while connect_with_backoff(&ctx, &peripheral, |_| {})
+            .await
+            .is_err()
+        {}
+        let peripheral = ConnectedPeripheral::new(&ctx, peripheral);
+        register(&ctx, peripheral.clone()).unwrap();
+        connected.push(peripheral);
+    }
+
+    let rebooting = connected
+        .iter()
+        .find(|peripheral| peripheral.peripheral.name == "Rebooting")
+        .unwrap();
+    let broken = connected
+        .iter()
+        .find(|peripheral| peripheral.peripheral.name == "Broken")
+        .unwrap();
+    let rebooting_id = rebooting.peripheral.ble_peripheral.id();
+    let broken_id = broken.peripheral.ble_peripheral.id();
+
+    let characteristic = rebooting
+        .characteristics
+        .iter()
+        .find(|char| char.uuid == create_ble_uuid(0x2A6E))
+        .unwrap()
+        .clone();
+    let (history, mode) = open_characteristic(&ctx, &rebooting_id, &characteristic);
+    watch(&ctx, rebooting, &characteristic, mode, Arc::clone(&history))
+        .await
+        .unwrap();
+
+    let mut rebooting_states = vec![];
+    wait_for(|| {
+        let state = state(&ctx, &rebooting_id).unwrap();
+        if rebooting_states.last() != Some(&state) {
+            rebooting_states.push(state);
+        }
+
+        rebooting_states
+            .ends_with(&[ConnectionState::Reconnecting(1), ConnectionState::Connected])
+            .then_some(())
+    })
+    .await;
+
+    // the value stream is resubscribed after reconnecting
+    let received = history.read().unwrap().len();
+    wait_for(|| (history.read().unwrap().len() > received).then_some(())).await;
+
+    // a single attempt to reconnect is refused by the device
+    wait_for(|| (state(&ctx, &broken_id) == Some(ConnectionState::Failed)).then_some(())).await;
+
+    disconnect(&ctx, &rebooting_id).await.unwrap();
+    disconnect(&ctx, &broken_id).await.unwrap();
+}
@@ -0,0 +1,365 @@
+use crate::bluetooth::{ble_default_services::parse_ble_uuid, payload::to_hex};
+use crate::error::{Error, Result};
+use std::{collections::HashMap, path::Path, str::FromStr};
+use uuid::Uuid;
+
+/// Single named value decoded from the characteristic payload
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedField {
+    pub name: String,
+    /// Human readable value or the reason why it could not be decoded
+    pub value: std::result::Result<String, String>,
+    pub unit: Option<String>,
+}
+
+impl DecodedField {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: Ok(value.into()),
+            unit: None,
+        }
+    }
+
+    pub fn with_unit(mut self, unit: impl Into<String>) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    /// Unsigned integer of `size` bytes where every set bit is displayed by its name from `bits`
+    Bitfield,
+    /// Fixed length UTF-8 string, trailing zero bytes are trimmed
+    String,
+    /// Raw bytes displayed as hex
+    Bytes,
+}
+
+impl FieldType {
+    fn fixed_size(self) -> Option<usize> {
+        match self {
+            FieldType::U8 | FieldType::I8 => Some(1),
+            FieldType::U16 | FieldType::I16 => Some(2),
+            FieldType::U32 | FieldType::I32 | FieldType::F32 => Some(4),
+            FieldType::U64 | FieldType::I64 | FieldType::F64 => Some(8),
+            FieldType::Bitfield | FieldType::String | FieldType::Bytes => None,
+        }
+    }
+
+    fn is_signed(self) -> bool {
+        matches!(
+            self,
+            FieldType::I8 | FieldType::I16 | FieldType::I32 | FieldType::I64
+        )
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    /// Overrides the endianness of the characteristic for this field
+    pub endian: Option<Endian>,
+    /// Size in bytes of bitfields, strings and bytes. Strings and bytes take the rest of payload if not set.
+    #[serde(alias = "length")]
+    pub size: Option<usize>,
+    /// Displayed value is `raw * scale + offset`
+    pub scale: Option<f64>,
+    pub offset: Option<f64>,
+    pub unit: Option<String>,
+    /// Names of the bitfield bits starting from the least significant one, empty names are skipped
+    #[serde(default)]
+    pub bits: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CharacteristicSchema {
+    pub uuid: String,
+    #[serde(default)]
+    pub endian: Endian,
+    #[serde(default, rename = "field")]
+    pub fields: Vec<FieldSchema>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct SchemaFile {
+    #[serde(default, rename = "characteristic")]
+    characteristics: Vec<CharacteristicSchema>,
+}
+
+/// User provided layouts of the characteristic payloads loaded from --decoders file
+#[derive(Debug, Default)]
+pub struct DecoderSchema {
+    characteristics: HashMap<Uuid, CharacteristicSchema>,
+}
+
+fn read_unsigned(bytes: &[u8], endian: Endian) -> u64 {
+    let fold = |value: u64, byte: &u8| (value << 8) | *byte as u64;
+
+    match endian {
+        Endian::Big => bytes.iter().fold(0, fold),
+        Endian::Little => bytes.iter().rev().fold(0, fold),
+    }
+}
+
+fn format_number(value: f64) -> String {
+    // avoid printing floating noise like 21.500000000000004
+    let rounded = format!("{value:.6}");
+    rounded
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_owned()
+}
+
+impl FieldSchema {
+    fn validate(&self) -> Result<()> {
+        match self.field_type {
+            FieldType::Bitfield => match self.size {
+                Some(1..=8) if self.bits.len() <= self.size.unwrap_or_default() * 8 => Ok(()),
+                Some(1..=8) => Err(Error::client(format!(
+                    "Bitfield \"{}\" names more bits than it has",
+                    self.name
+                ))),
+                _ => Err(Error::client(format!(
+                    "Bitfield \"{}\" must have size from 1 to 8 bytes",
+                    self.name
+                ))),
+            },
+            field_type if field_type.fixed_size().is_some() && self.size.is_some() => {
+                Err(Error::client(format!(
+                    "Field \"{}\" of type {field_type:?} has fixed size, remove the size",
+                    self.name
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn scaled(&self, raw: f64) -> String {
+        format_number(raw * self.scale.unwrap_or(1.0) + self.offset.unwrap_or(0.0))
+    }
+
+    fn format(&self, bytes: &[u8], endian: Endian) -> String {
+        let endian = self.endian.unwrap_or(endian);
+        let scaled = self.scale.is_some() || self.offset.is_some();
+
+        match self.field_type {
+            FieldType::F32 => {
+                let value = f32::from_bits(read_unsigned(bytes, endian) as u32) as f64;
+                self.scaled(value)
+            }
+            FieldType::F64 => self.scaled(f64::from_bits(read_unsigned(bytes, endian))),
+            FieldType::String => String::from_utf8_lossy(bytes)
+                .trim_end_matches('\0')
+                .to_owned(),
+            FieldType::Bytes => to_hex(bytes),
+            FieldType::Bitfield => {
+                let raw = read_unsigned(bytes, endian);
+                let set_bits = (0..bytes.len() * 8)
+                    .filter(|bit| raw & (1 << bit) != 0)
+                    .map(|bit| match self.bits.get(bit) {
+                        Some(name) if !name.is_empty() => name.clone(),
+                        _ => format!("bit {bit}"),
+                    })
+                    .collect::<Vec<_>>();
+
+                format!(
+                    "0x{raw:0width$x} [{}]",
+                    set_bits.join(", "),
+                    width = bytes.len() * 2
+                )
+            }
+            field_type => {
+                let raw = read_unsigned(bytes, endian);
+                let shift = 64 - bytes.len() * 8;
+
+                match (field_type.is_signed(), scaled) {
+                    // sign extend the value to the full i64
+                    (true, true) => self.scaled((((raw << shift) as i64) >> shift) as f64),
+                    (true, false) => (((raw << shift) as i64) >> shift).to_string(),
+                    (false, true) => self.scaled(raw as f64),
+                    (false, false) => raw.to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl CharacteristicSchema {
+    /// Decodes fields one by one, decoding stops at the first field that does not fit the payload
+    pub fn decode(&self, data: &[u8]) -> Vec<DecodedField> {
+        let mut fields = Vec::with_capacity(self.fields.len());
+        let mut position = 0;
+
+        for field in &self.fields {
+            let rest = &data[position.min(data.len())..];
+            let size = field
+                .field_type
+                .fixed_size()
+                .or(field.size)
+                .unwrap_or(rest.len());
+
+            let Some(bytes) = rest.get(..size) else {
+                fields.push(DecodedField {
+                    name: field.name.clone(),
+                    value: Err(format!("needs {size} bytes, only {} left", rest.len())),
+                    unit: field.unit.clone(),
+                });
+
+                break;
+            };
+
+            fields.push(DecodedField {
+                name: field.name.clone(),
+                value: Ok(field.format(bytes, self.endian)),
+                unit: field.unit.clone(),
+            });
+            position += size;
+        }
+
+        if position < data.len() && fields.iter().all(|field| field.value.is_ok()) {
+            fields.push(DecodedField {
+                name: "(unparsed)".to_owned(),
+                value: Err(format!("{} trailing bytes", data.len() - position)),
+                unit: None,
+            });
+        }
+
+        fields
+    }
+}
+
+impl FromStr for DecoderSchema {
+    type Err = Error;
+
+    fn from_str(content: &str) -> Result<Self> {
+        let file: SchemaFile = toml::from_str(content)
+            .map_err(|e| Error::client(format!("Failed to parse decoders file: {e}")))?;
+
+        let characteristics = file
+            .characteristics
+            .into_iter()
+            .map(|char| {
+                for field in &char.fields {
+                    field.validate()?;
+                }
+
+                let uuid = parse_ble_uuid(&char.uuid)
+                    .map_err(|e| Error::client(format!("Invalid uuid \"{}\": {e}", char.uuid)))?;
+
+                Ok((uuid, char))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { characteristics })
+    }
+}
+
+impl DecoderSchema {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        std::fs::read_to_string(path)
+            .map_err(|e| Error::client(format!("Failed to read {}: {e}", path.display())))?
+            .parse()
+    }
+
+    pub fn get(&self, uuid: &Uuid) -> Option<&CharacteristicSchema> {
+        self.characteristics.get(uuid)
+    }
+}
+
+#[test]
+fn decodes_packed_struct() {
+    use crate::bluetooth::ble_default_services::create_ble_uuid;
+
+    let schema: DecoderSchema = r#"
+        [[characteristic]]
+        uuid = "ffe1"
+
+        [[characteristic.field]]
+        name = "temperature"
+        type = "i16"
+        scale = 0.01
+        unit = "°C"
+
+        [[characteristic.field]]
+        name = "pressure"
+        type = "u32"
+        endian = "big"
+
+        [[characteristic.field]]
+        name = "status"
+        type = "bitfield"
+        size = 1
+        bits = ["charging", "", "error"]
+
+        [[characteristic.field]]
+        name = "label"
+        type = "string"
+        size = 4
+
+        [[characteristic.field]]
+        name = "ratio"
+        type = "f32"
+    "#
+    .parse()
+    .unwrap();
+
+    let char = schema.get(&create_ble_uuid(0xFFE1)).unwrap();
+    let mut payload = vec![0x66, 0xf7, 0x00, 0x01, 0x86, 0xa0, 0b0000_0111];
+    payload.extend(b"ab\0\0");
+    payload.extend(0.5f32.to_le_bytes());
+
+    let values = char
+        .decode(&payload)
+        .into_iter()
+        .map(|field| field.value.unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        values,
+        vec![
+            "-22.02",
+            "100000",
+            "0x07 [charging, bit 1, error]",
+            "ab",
+            "0.5"
+        ]
+    );
+
+    let truncated = char.decode(&payload[..3]);
+    assert_eq!(truncated.len(), 2);
+    assert!(truncated[1].value.is_err());
+
+    assert!("[[characteristic]]\nuuid = \"ffe1\"\n[[characteristic.field]]\nname = \"a\"\ntype = \"bitfield\""
+        .parse::<DecoderSchema>()
+        .is_err());
+}
+
+#[test]
+fn parses_example_decoders_file() {
+    DecoderSchema::from_file(Path::new("decoders_example.toml")).unwrap();
+}
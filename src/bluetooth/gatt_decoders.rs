@@ -0,0 +1,611 @@
+//! Value decoders of the standard characteristics defined by Bluetooth SIG
+
+use crate::bluetooth::{ble_default_services::create_ble_uuid, decoder::DecodedField};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::{collections::HashMap, io::Cursor};
+
+/// Decodes the payload into fields or returns the reason why payload is malformed
+pub type GattDecoder = fn(&[u8]) -> Result<Vec<DecodedField>, String>;
+
+const APPEARANCE: uuid::Uuid = create_ble_uuid(0x2A01);
+const BATTERY_LEVEL: uuid::Uuid = create_ble_uuid(0x2A19);
+const TEMPERATURE_MEASUREMENT: uuid::Uuid = create_ble_uuid(0x2A1C);
+const MODEL_NUMBER: uuid::Uuid = create_ble_uuid(0x2A24);
+const SERIAL_NUMBER: uuid::Uuid = create_ble_uuid(0x2A25);
+const FIRMWARE_REVISION: uuid::Uuid = create_ble_uuid(0x2A26);
+const HARDWARE_REVISION: uuid::Uuid = create_ble_uuid(0x2A27);
+const SOFTWARE_REVISION: uuid::Uuid = create_ble_uuid(0x2A28);
+const MANUFACTURER_NAME: uuid::Uuid = create_ble_uuid(0x2A29);
+const CURRENT_TIME: uuid::Uuid = create_ble_uuid(0x2A2B);
+const HEART_RATE_MEASUREMENT: uuid::Uuid = create_ble_uuid(0x2A37);
+const PNP_ID: uuid::Uuid = create_ble_uuid(0x2A50);
+
+lazy_static::lazy_static! {
+    pub static ref STANDARD_DECODERS: HashMap<uuid::Uuid, GattDecoder> = HashMap::from([
+        (BATTERY_LEVEL, decode_battery_level as GattDecoder),
+        (HEART_RATE_MEASUREMENT, decode_heart_rate_measurement),
+        (TEMPERATURE_MEASUREMENT, decode_temperature_measurement),
+        (MANUFACTURER_NAME, decode_string),
+        (MODEL_NUMBER, decode_string),
+        (SERIAL_NUMBER, decode_string),
+        (FIRMWARE_REVISION, decode_string),
+        (HARDWARE_REVISION, decode_string),
+        (SOFTWARE_REVISION, decode_string),
+        (APPEARANCE, decode_appearance),
+        (PNP_ID, decode_pnp_id),
+        (CURRENT_TIME, decode_current_time),
+    ]);
+}
+
+pub const USER_DESCRIPTION: uuid::Uuid = create_ble_uuid(0x2901);
+pub const CLIENT_CONFIGURATION: uuid::Uuid = create_ble_uuid(0x2902);
+pub const PRESENTATION_FORMAT: uuid::Uuid = create_ble_uuid(0x2904);
+
+lazy_static::lazy_static! {
+    /// Descriptors that are read right after connecting to the peripheral
+    pub static ref DESCRIPTOR_DECODERS: HashMap<uuid::Uuid, GattDecoder> = HashMap::from([
+        (USER_DESCRIPTION, decode_string as GattDecoder),
+        (CLIENT_CONFIGURATION, decode_client_configuration),
+        (PRESENTATION_FORMAT, decode_presentation_format),
+    ]);
+}
+
+fn too_short(_: std::io::Error) -> String {
+    "payload is too short".to_owned()
+}
+
+fn read_u8(cursor: &mut Cursor<&[u8]>) -> Result<u8, String> {
+    cursor.read_u8().map_err(too_short)
+}
+
+fn read_u16(cursor: &mut Cursor<&[u8]>) -> Result<u16, String> {
+    cursor.read_u16::<LittleEndian>().map_err(too_short)
+}
+
+fn flag(flags: u8, bit: u8) -> bool {
+    flags & (1 << bit) != 0
+}
+
+/// IEEE-11073 32-bit FLOAT: 24-bit signed mantissa and 8-bit signed base 10 exponent
+pub fn ieee11073_float(raw: u32) -> Result<f64, &'static str> {
+    match raw & 0x00FF_FFFF {
+        0x007F_FFFF => return Err("NaN"),
+        0x0080_0000 => return Err("NRes"),
+        0x007F_FFFE => return Err("+INFINITY"),
+        0x0080_0002 => return Err("-INFINITY"),
+        0x0080_0001 => return Err("Reserved"),
+        _ => (),
+    }
+
+    // sign extend the 24-bit mantissa
+    let mantissa = ((raw << 8) as i32) >> 8;
+    let exponent = (raw >> 24) as i8;
+
+    Ok(mantissa as f64 * 10f64.powi(exponent as i32))
+}
+
+/// Date Time characteristic layout (0x2A08) shared by many other characteristics
+fn read_date_time(cursor: &mut Cursor<&[u8]>) -> Result<String, String> {
+    let year = read_u16(cursor)?;
+    let [month, day, hours, minutes, seconds] = [
+        read_u8(cursor)?,
+        read_u8(cursor)?,
+        read_u8(cursor)?,
+        read_u8(cursor)?,
+        read_u8(cursor)?,
+    ];
+
+    Ok(format!(
+        "{year:04}-{month:02}-{day:02} {hours:02}:{minutes:02}:{seconds:02}"
+    ))
+}
+
+pub fn decode_battery_level(data: &[u8]) -> Result<Vec<DecodedField>, String> {
+    let level = read_u8(&mut Cursor::new(data))?;
+
+    Ok(vec![
+        DecodedField::new("Battery level", level.to_string()).with_unit("%")
+    ])
+}
+
+pub fn decode_heart_rate_measurement(data: &[u8]) -> Result<Vec<DecodedField>, String> {
+    let mut cursor = Cursor::new(data);
+    let flags = read_u8(&mut cursor)?;
+
+    let heart_rate = if flag(flags, 0) {
+        read_u16(&mut cursor)?
+    } else {
+        read_u8(&mut cursor)? as u16
+    };
+
+    let mut fields = vec![DecodedField::new("Heart rate", heart_rate.to_string()).with_unit("bpm")];
+
+    if flag(flags, 2) {
+        fields.push(DecodedField::new(
+            "Sensor contact",
+            if flag(flags, 1) {
+                "detected"
+            } else {
+                "not detected"
+            },
+        ));
+    }
+
+    if flag(flags, 3) {
+        fields.push(
+            DecodedField::new("Energy expended", read_u16(&mut cursor)?.to_string())
+                .with_unit("kJ"),
+        );
+    }
+
+    if flag(flags, 4) {
+        let mut intervals = vec![];
+        while (cursor.position() as usize) < data.len() {
+            // RR interval resolution is 1/1024 of second
+            intervals.push(format!(
+                "{:.0}",
+                read_u16(&mut cursor)? as f64 * 1000.0 / 1024.0
+            ));
+        }
+
+        fields.push(DecodedField::new("RR intervals", intervals.join(", ")).with_unit("ms"));
+    }
+
+    Ok(fields)
+}
+
+pub fn decode_temperature_measurement(data: &[u8]) -> Result<Vec<DecodedField>, String> {
+    let mut cursor = Cursor::new(data);
+    let flags = read_u8(&mut cursor)?;
+    let raw = cursor.read_u32::<LittleEndian>().map_err(too_short)?;
+
+    let temperature = match ieee11073_float(raw) {
+        Ok(value) => DecodedField::new("Temperature", format!("{value:.2}"))
+            .with_unit(if flag(flags, 0) { "°F" } else { "°C" }),
+        Err(special) => DecodedField::new("Temperature", special),
+    };
+
+    let mut fields = vec![temperature];
+    if flag(flags, 1) {
+        fields.push(DecodedField::new(
+            "Time stamp",
+            read_date_time(&mut cursor)?,
+        ));
+    }
+
+    if flag(flags, 2) {
+        let location = match read_u8(&mut cursor)? {
+            1 => "Armpit",
+            2 => "Body (general)",
+            3 => "Ear",
+            4 => "Finger",
+            5 => "Gastro-intestinal tract",
+            6 => "Mouth",
+            7 => "Rectum",
+            8 => "Toe",
+            9 => "Tympanum",
+            _ => "Reserved",
+        };
+
+        fields.push(DecodedField::new("Temperature type", location));
+    }
+
+    Ok(fields)
+}
+
+/// Device information characteristics are plain UTF-8 strings
+pub fn decode_string(data: &[u8]) -> Result<Vec<DecodedField>, String> {
+    let value = std::str::from_utf8(data).map_err(|e| e.to_string())?;
+
+    Ok(vec![DecodedField::new(
+        "Value",
+        value.trim_end_matches('\0'),
+    )])
+}
+
+fn appearance_category_name(category: u16) -> &'static str {
+    match category {
+        0x000 => "Unknown",
+        0x001 => "Phone",
+        0x002 => "Computer",
+        0x003 => "Watch",
+        0x004 => "Clock",
+        0x005 => "Display",
+        0x006 => "Remote Control",
+        0x007 => "Eye-glasses",
+        0x008 => "Tag",
+        0x009 => "Keyring",
+        0x00A => "Media Player",
+        0x00B => "Barcode Scanner",
+        0x00C => "Thermometer",
+        0x00D => "Heart Rate Sensor",
+        0x00E => "Blood Pressure",
+        0x00F => "Human Interface Device",
+        0x010 => "Glucose Meter",
+        0x011 => "Running Walking Sensor",
+        0x012 => "Cycling",
+        0x013 => "Control Device",
+        0x014 => "Network Device",
+        0x015 => "Sensor",
+        0x016 => "Light Fixtures",
+        0x017 => "Fan",
+        0x018 => "HVAC",
+        0x019 => "Air Conditioning",
+        0x01A => "Humidifier",
+        0x01B => "Heating",
+        0x01C => "Access Control",
+        0x01D => "Motorized Device",
+        0x01E => "Power Device",
+        0x01F => "Light Source",
+        0x020 => "Window Covering",
+        0x021 => "Audio Sink",
+        0x022 => "Audio Source",
+        0x023 => "Motorized Vehicle",
+        0x024 => "Domestic Appliance",
+        0x025 => "Wearable Audio Device",
+        0x026 => "Aircraft",
+        0x027 => "AV Equipment",
+        0x028 => "Display Equipment",
+        0x029 => "Hearing aid",
+        0x02A => "Gaming",
+        0x02B => "Signage",
+        0x031 => "Pulse Oximeter",
+        0x032 => "Weight Scale",
+        0x033 => "Personal Mobility Device",
+        0x034 => "Continuous Glucose Monitor",
+        0x035 => "Insulin Pump",
+        0x036 => "Medication Delivery",
+        0x037 => "Spirometer",
+        0x051 => "Outdoor Sports Activity",
+        _ => "Reserved",
+    }
+}
+
+pub fn decode_appearance(data: &[u8]) -> Result<Vec<DecodedField>, String> {
+    let appearance = read_u16(&mut Cursor::new(data))?;
+    // 10 bits of category and 6 bits of subcategory
+    let category = appearance >> 6;
+
+    Ok(vec![
+        DecodedField::new(
+            "Category",
+            format!("{} (0x{category:03x})", appearance_category_name(category)),
+        ),
+        DecodedField::new("Subcategory", format!("0x{:02x}", appearance & 0x3F)),
+    ])
+}
+
+pub fn decode_pnp_id(data: &[u8]) -> Result<Vec<DecodedField>, String> {
+    let mut cursor = Cursor::new(data);
+    let source = match read_u8(&mut cursor)? {
+        1 => "Bluetooth SIG",
+        2 => "USB Implementer's Forum",
+        _ => "Reserved",
+    };
+
+    let vendor = read_u16(&mut cursor)?;
+    let product = read_u16(&mut cursor)?;
+    let version = read_u16(&mut cursor)?;
+
+    Ok(vec![
+        DecodedField::new("Vendor ID source", source),
+        DecodedField::new("Vendor ID", format!("0x{vendor:04x}")),
+        DecodedField::new("Product ID", format!("0x{product:04x}")),
+        // binary coded decimal JJ.M.N, the major version takes two digits
+        DecodedField::new(
+            "Product version",
+            format!(
+                "{}.{}.{}",
+                (version >> 12) * 10 + ((version >> 8) & 0xF),
+                (version >> 4) & 0xF,
+                version & 0xF
+            ),
+        ),
+    ])
+}
+
+pub fn decode_current_time(data: &[u8]) -> Result<Vec<DecodedField>, String> {
+    let mut cursor = Cursor::new(data);
+    let date_time = read_date_time(&mut cursor)?;
+    let day_of_week = match read_u8(&mut cursor)? {
+        1 => "Monday",
+        2 => "Tuesday",
+        3 => "Wednesday",
+        4 => "Thursday",
+        5 => "Friday",
+        6 => "Saturday",
+        7 => "Sunday",
+        _ => "Unknown",
+    };
+
+    let fractions = read_u8(&mut cursor)?;
+    let adjust_reason = read_u8(&mut cursor)?;
+    let reasons = [
+        "manual time update",
+        "external reference time update",
+        "change of time zone",
+        "change of DST",
+    ]
+    .iter()
+    .enumerate()
+    .filter(|(bit, _)| flag(adjust_reason, *bit as u8))
+    .map(|(_, reason)| *reason)
+    .collect::<Vec<_>>();
+
+    Ok(vec![
+        DecodedField::new("Date time", date_time),
+        DecodedField::new("Day of week", day_of_week),
+        DecodedField::new("Fractions", format!("{}/256", fractions)),
+        DecodedField::new(
+            "Adjust reason",
+            if reasons.is_empty() {
+                "none".to_owned()
+            } else {
+                reasons.join(", ")
+            },
+        ),
+    ])
+}
+
+pub fn decode_client_configuration(data: &[u8]) -> Result<Vec<DecodedField>, String> {
+    let bits = read_u16(&mut Cursor::new(data))?;
+    let state = |enabled| if enabled { "on" } else { "off" };
+
+    Ok(vec![
+        DecodedField::new("Notifications", state(bits & 0b01 != 0)),
+        DecodedField::new("Indications", state(bits & 0b10 != 0)),
+    ])
+}
+
+fn format_type_name(format: u8) -> &'static str {
+    match format {
+        0x01 => "boolean",
+        0x02 => "2bit",
+        0x03 => "nibble",
+        0x04 => "uint8",
+        0x05 => "uint12",
+        0x06 => "uint16",
+        0x07 => "uint24",
+        0x08 => "uint32",
+        0x09 => "uint48",
+        0x0A => "uint64",
+        0x0B => "uint128",
+        0x0C => "sint8",
+        0x0D => "sint12",
+        0x0E => "sint16",
+        0x0F => "sint24",
+        0x10 => "sint32",
+        0x11 => "sint48",
+        0x12 => "sint64",
+        0x13 => "sint128",
+        0x14 => "float32",
+        0x15 => "float64",
+        0x16 => "SFLOAT",
+        0x17 => "FLOAT",
+        0x18 => "duint16",
+        0x19 => "utf8s",
+        0x1A => "utf16s",
+        0x1B => "struct",
+        0x1C => "medfloat16",
+        _ => "reserved",
+    }
+}
+
+fn unit_name(unit: u16) -> Option<&'static str> {
+    Some(match unit {
+        0x2700 => "unitless",
+        0x2701 => "metre",
+        0x2702 => "kilogram",
+        0x2703 => "second",
+        0x2704 => "ampere",
+        0x2705 => "kelvin",
+        0x2722 => "hertz",
+        0x2723 => "newton",
+        0x2724 => "pascal",
+        0x2725 => "joule",
+        0x2726 => "watt",
+        0x2727 => "coulomb",
+        0x2728 => "volt",
+        0x272F => "degree Celsius",
+        0x27A7 => "beats per minute",
+        0x27AD => "percentage",
+        _ => return None,
+    })
+}
+
+pub fn decode_presentation_format(data: &[u8]) -> Result<Vec<DecodedField>, String> {
+    let mut cursor = Cursor::new(data);
+    let format = read_u8(&mut cursor)?;
+    let exponent = read_u8(&mut cursor)? as i8;
+    let unit = read_u16(&mut cursor)?;
+    let namespace = read_u8(&mut cursor)?;
+    let description = read_u16(&mut cursor)?;
+
+    Ok(vec![
+        DecodedField::new(
+            "Format",
+            format!("{} (0x{format:02x})", format_type_name(format)),
+        ),
+        DecodedField::new("Exponent", exponent.to_string()),
+        DecodedField::new(
+            "Unit",
+            match unit_name(unit) {
+                Some(name) => format!("{name} (0x{unit:04x})"),
+                None => format!("0x{unit:04x}"),
+            },
+        ),
+        DecodedField::new(
+            "Namespace",
+            match namespace {
+                0x01 => "Bluetooth SIG".to_owned(),
+                _ => format!("0x{namespace:02x}"),
+            },
+        ),
+        DecodedField::new("Description", format!("0x{description:04x}")),
+    ])
+}
+
+/// Runs the standard decoder of the characteristic if there is one
+pub fn decode_standard(
+    uuid: &uuid::Uuid,
+    data: &[u8],
+) -> Option<Result<Vec<DecodedField>, String>> {
+    STANDARD_DECODERS.get(uuid).map(|decode| decode(data))
+}
+
+/// Decodes the descriptors listed in `DESCRIPTOR_DECODERS`
+pub fn decode_descriptor(
+    uuid: &uuid::Uuid,
+    data: &[u8],
+) -> Option<Result<Vec<DecodedField>, String>> {
+    DESCRIPTOR_DECODERS.get(uuid).map(|decode| decode(data))
+}
+
+#[cfg(test)]
+fn values(fields: Vec<DecodedField>) -> Vec<(String, String)> {
+    fields
+        .into_iter()
+        .map(|field| (field.name, field.value.unwrap()))
+        .collect()
+}
+
+#[test]
+fn decodes_battery_level() {
+    let fields = decode_battery_level(&[87]).unwrap();
+    assert_eq!(fields[0].value, Ok("87".to_owned()));
+    assert_eq!(fields[0].unit.as_deref(), Some("%"));
+    assert!(decode_battery_level(&[]).is_err());
+}
+
+#[test]
+fn decodes_heart_rate_measurement() {
+    // 8-bit heart rate, contact detected
+    assert_eq!(
+        values(decode_heart_rate_measurement(&[0b0000_0110, 72]).unwrap()),
+        vec![
+            ("Heart rate".to_owned(), "72".to_owned()),
+            ("Sensor contact".to_owned(), "detected".to_owned()),
+        ]
+    );
+
+    // 16-bit heart rate, energy expended and two RR intervals
+    let fields = values(
+        decode_heart_rate_measurement(&[
+            0b0001_1001,
+            0x2C,
+            0x01,
+            0x10,
+            0x00,
+            0x00,
+            0x04,
+            0x00,
+            0x02,
+        ])
+        .unwrap(),
+    );
+
+    assert_eq!(fields[0].1, "300");
+    assert_eq!(fields[1].1, "16");
+    assert_eq!(fields[2].1, "1000, 500");
+    assert!(decode_heart_rate_measurement(&[0x01, 0x2C]).is_err());
+}
+
+#[test]
+fn decodes_temperature_measurement() {
+    // 36.6 encoded as mantissa 366 and exponent -1
+    let mut data = vec![0b0000_0110];
+    data.extend(((0xFFu32 << 24) | 366).to_le_bytes());
+    data.extend([0xE7, 0x07, 6, 15, 10, 30, 0]);
+    data.push(6);
+
+    assert_eq!(
+        values(decode_temperature_measurement(&data).unwrap()),
+        vec![
+            ("Temperature".to_owned(), "36.60".to_owned()),
+            ("Time stamp".to_owned(), "2023-06-15 10:30:00".to_owned()),
+            ("Temperature type".to_owned(), "Mouth".to_owned()),
+        ]
+    );
+
+    assert_eq!(ieee11073_float(0x007F_FFFF), Err("NaN"));
+    assert_eq!(ieee11073_float(0x00FF_FFFF), Ok(-1.0));
+    assert_eq!(ieee11073_float(0x0200_0005), Ok(500.0));
+}
+
+#[test]
+fn decodes_device_information() {
+    assert_eq!(
+        values(decode_string(b"Nordic\0").unwrap()),
+        vec![("Value".to_owned(), "Nordic".to_owned())]
+    );
+    assert!(decode_string(&[0xFF, 0xFE]).is_err());
+}
+
+#[test]
+fn decodes_appearance_and_pnp_id() {
+    // heart rate sensor belt 0x0341
+    assert_eq!(
+        values(decode_appearance(&[0x41, 0x03]).unwrap()),
+        vec![
+            (
+                "Category".to_owned(),
+                "Heart Rate Sensor (0x00d)".to_owned()
+            ),
+            ("Subcategory".to_owned(), "0x01".to_owned()),
+        ]
+    );
+
+    assert_eq!(
+        values(decode_pnp_id(&[0x01, 0x59, 0x00, 0x01, 0x00, 0x23, 0x01]).unwrap()),
+        vec![
+            ("Vendor ID source".to_owned(), "Bluetooth SIG".to_owned()),
+            ("Vendor ID".to_owned(), "0x0059".to_owned()),
+            ("Product ID".to_owned(), "0x0001".to_owned()),
+            ("Product version".to_owned(), "1.2.3".to_owned()),
+        ]
+    );
+    // both digits of the major version are decimal
+    assert_eq!(
+        values(decode_pnp_id(&[0x02, 0x59, 0x00, 0x01, 0x00, 0x34, 0x12]).unwrap())[3].1,
+        "12.3.4"
+    );
+}
+
+#[test]
+fn decodes_current_time() {
+    let fields = values(
+        decode_current_time(&[0xE7, 0x07, 12, 31, 23, 59, 59, 7, 128, 0b0000_0101]).unwrap(),
+    );
+
+    assert_eq!(fields[0].1, "2023-12-31 23:59:59");
+    assert_eq!(fields[1].1, "Sunday");
+    assert_eq!(fields[2].1, "128/256");
+    assert_eq!(fields[3].1, "manual time update, change of time zone");
+}
+
+#[test]
+fn finds_decoders_by_standard_uuid() {
+    assert!(decode_standard(&BATTERY_LEVEL, &[50]).is_some());
+    assert!(decode_standard(&create_ble_uuid(0xFFE1), &[50]).is_none());
+}
+
+#[test]
+fn decodes_descriptors() {
+    assert_eq!(
+        values(decode_client_configuration(&[0x01, 0x00]).unwrap()),
+        vec![
+            ("Notifications".to_owned(), "on".to_owned()),
+            ("Indications".to_owned(), "off".to_owned())
+        ]
+    );
+
+    let fields =
+        values(decode_presentation_format(&[0x04, 0x00, 0xAD, 0x27, 0x01, 0x00, 0x00]).unwrap());
+    assert_eq!(fields[0].1, "uint8 (0x04)");
+    assert_eq!(fields[2].1, "percentage (0x27ad)");
+    assert_eq!(fields[3].1, "Bluetooth SIG");
+    assert!(decode_presentation_format(&[0x04, 0x00]).is_err());
+
+    assert!(decode_descriptor(&USER_DESCRIPTION, b"Level").is_some());
+    assert!(decode_descriptor(&create_ble_uuid(0x2900), &[0]).is_none());
+}
@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+
+const SPARKLINE_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// RSSI range mapped onto the sparkline bars, weaker or stronger signals are clamped
+const SPARKLINE_RSSI_RANGE: (i16, i16) = (-100, -30);
+/// Path loss exponent of the free space, indoor environments are usually between 2 and 4
+const PATH_LOSS_EXPONENT: f64 = 2.0;
+/// Eddystone and the advertised TX power level are calibrated at 0 meters,
+/// signal loses about 41 dBm in the first meter
+pub const ONE_METER_LOSS: i16 = 41;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RssiSample {
+    pub time: chrono::DateTime<chrono::Local>,
+    pub rssi: i16,
+}
+
+/// Registry record of everything observed about the peripheral across the scans
+#[derive(Debug)]
+pub struct PeripheralHistory {
+    pub first_seen: chrono::DateTime<chrono::Local>,
+    pub last_seen: chrono::DateTime<chrono::Local>,
+    /// Amount of advertisements reported by the adapter, only the ones changing the advertised
+    /// data when the duplicates are filtered
+    pub advertisement_count: u64,
+    /// Ring buffer of the latest RSSI readings, bounded by --rssi-history
+    pub rssi: VecDeque<RssiSample>,
+}
+
+impl PeripheralHistory {
+    pub fn new(time: chrono::DateTime<chrono::Local>) -> Self {
+        Self {
+            first_seen: time,
+            last_seen: time,
+            advertisement_count: 0,
+            rssi: VecDeque::new(),
+        }
+    }
+
+    pub fn record_advertisement(
+        &mut self,
+        time: chrono::DateTime<chrono::Local>,
+        rssi: Option<i16>,
+        capacity: usize,
+    ) {
+        self.last_seen = time;
+        self.advertisement_count += 1;
+
+        if let Some(rssi) = rssi {
+            self.push_rssi(RssiSample { time, rssi }, capacity);
+        }
+    }
+
+    /// Time passed since the last received advertisement
+    pub fn age(&self) -> chrono::Duration {
+        chrono::Local::now() - self.last_seen
+    }
+
+    pub fn is_stale(&self, stale_after_secs: u64) -> bool {
+        self.age().num_seconds() >= stale_after_secs as i64
+    }
+
+    fn push_rssi(&mut self, sample: RssiSample, capacity: usize) {
+        while self.rssi.len() >= capacity.max(1) {
+            self.rssi.pop_front();
+        }
+
+        self.rssi.push_back(sample);
+    }
+
+    /// Renders the latest `width` samples as unicode bars
+    pub fn sparkline(&self, width: usize) -> String {
+        let (min, max) = SPARKLINE_RSSI_RANGE;
+
+        self.rssi
+            .iter()
+            .skip(self.rssi.len().saturating_sub(width))
+            .map(|sample| {
+                let level = (sample.rssi.clamp(min, max) - min) as usize
+                    * (SPARKLINE_BARS.len() - 1)
+                    / (max - min) as usize;
+
+                SPARKLINE_BARS[level]
+            })
+            .collect()
+    }
+}
+
+/// Compact age like `42s`, `5m` or `3h` fitting into a narrow column
+pub fn format_age(age: chrono::Duration) -> String {
+    match age.num_seconds().max(0) {
+        seconds @ 0..=59 => format!("{seconds}s"),
+        seconds @ 60..=3599 => format!("{}m", seconds / 60),
+        seconds => format!("{}h", seconds / 3600),
+    }
+}
+
+/// Expected RSSI at 1 meter from the beacon calibration or the advertised TX power level
+pub fn reference_rssi(
+    beacons: &[super::beacon::BeaconFrame],
+    tx_power_level: Option<i16>,
+) -> Option<i16> {
+    beacons
+        .iter()
+        .find_map(|beacon| beacon.reference_rssi())
+        .or_else(|| tx_power_level.map(|tx_power| tx_power - ONE_METER_LOSS))
+}
+
+/// Log-distance path loss estimation in meters, only a rough guess due to reflections and obstacles
+pub fn estimate_distance(reference_rssi: i16, rssi: i16) -> f64 {
+    10f64.powf((reference_rssi - rssi) as f64 / (10.0 * PATH_LOSS_EXPONENT))
+}
+
+#[test]
+fn keeps_bounded_rssi_history() {
+    let first_seen = chrono::Local::now() - chrono::Duration::seconds(90);
+    let mut history = PeripheralHistory::new(first_seen);
+    assert!(history.is_stale(30));
+
+    for rssi in [-100, -90, -65, -30, -20] {
+        history.record_advertisement(chrono::Local::now(), Some(rssi), 4);
+    }
+
+    assert!(!history.is_stale(30));
+    assert_eq!(history.first_seen, first_seen);
+    assert_eq!(history.advertisement_count, 5);
+    assert_eq!(history.rssi.len(), 4);
+    assert_eq!(history.sparkline(10), "▂▄██");
+    assert_eq!(history.sparkline(2), "██");
+
+    assert_eq!(estimate_distance(-59, -59), 1.0);
+    assert!((estimate_distance(-59, -79) - 10.0).abs() < 1e-9);
+    assert_eq!(reference_rssi(&[], Some(-4)), Some(-45));
+    assert_eq!(format_age(chrono::Duration::seconds(125)), "2m");
+}
@@ -0,0 +1,189 @@
+use crate::error::{Error, Result};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use std::io::Cursor;
+
+/// Format of the user input that is going to be written to the characteristic
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum WriteFormat {
+    #[default]
+    Hex,
+    Utf8,
+    U8,
+    I16,
+    U32,
+    F32,
+}
+
+impl WriteFormat {
+    pub const ALL: [WriteFormat; 6] = [
+        WriteFormat::Hex,
+        WriteFormat::Utf8,
+        WriteFormat::U8,
+        WriteFormat::I16,
+        WriteFormat::U32,
+        WriteFormat::F32,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|f| *f == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            WriteFormat::Hex => "hex",
+            WriteFormat::Utf8 => "utf-8",
+            WriteFormat::U8 => "u8",
+            WriteFormat::I16 => "i16",
+            WriteFormat::U32 => "u32",
+            WriteFormat::F32 => "f32",
+        }
+    }
+
+    /// Endianness makes sense only for multi-byte numbers
+    pub fn is_endian_sensitive(&self) -> bool {
+        matches!(self, WriteFormat::I16 | WriteFormat::U32 | WriteFormat::F32)
+    }
+}
+
+pub fn parse_hex(input: &str) -> Result<Vec<u8>> {
+    let input = input.trim();
+    let input = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+        .unwrap_or(input);
+
+    let digits = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':' && *c != '-')
+        .map(|c| {
+            c.to_digit(16)
+                .map(|digit| digit as u8)
+                .ok_or_else(|| Error::client(format!("Invalid hex digit \"{c}\"")))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if !digits.len().is_multiple_of(2) {
+        return Err(Error::client(
+            "Hex value must contain an even number of digits",
+        ));
+    }
+
+    Ok(digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect())
+}
+
+fn encode_number<T: ByteOrder>(input: &str, format: WriteFormat) -> Result<Vec<u8>> {
+    let input = input.trim();
+    let invalid_number =
+        |_| Error::client(format!("\"{input}\" is not a valid {}", format.label()));
+
+    Ok(match format {
+        WriteFormat::U8 => vec![input.parse::<u8>().map_err(invalid_number)?],
+        WriteFormat::I16 => {
+            let mut buf = [0; 2];
+            T::write_i16(&mut buf, input.parse().map_err(invalid_number)?);
+            buf.to_vec()
+        }
+        WriteFormat::U32 => {
+            let mut buf = [0; 4];
+            T::write_u32(&mut buf, input.parse().map_err(invalid_number)?);
+            buf.to_vec()
+        }
+        WriteFormat::F32 => {
+            let mut buf = [0; 4];
+            T::write_f32(
+                &mut buf,
+                input
+                    .parse()
+                    .map_err(|_| Error::client(format!("\"{input}\" is not a valid f32")))?,
+            );
+            buf.to_vec()
+        }
+        WriteFormat::Hex | WriteFormat::Utf8 => unreachable!("not a numeric format"),
+    })
+}
+
+/// Encodes bytes as a lowercase hex string without separators, the inverse of [`parse_hex`]
+pub fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Converts user input into the raw bytes that will be sent to the device.
+pub fn parse_write_payload(input: &str, format: WriteFormat, big_endian: bool) -> Result<Vec<u8>> {
+    let payload = match format {
+        WriteFormat::Hex => parse_hex(input)?,
+        WriteFormat::Utf8 => input.as_bytes().to_vec(),
+        _ if big_endian => encode_number::<BigEndian>(input, format)?,
+        _ => encode_number::<LittleEndian>(input, format)?,
+    };
+
+    if payload.is_empty() {
+        return Err(Error::client("Nothing to write"));
+    }
+
+    Ok(payload)
+}
+
+/// Guesses the numeric value based on the payload length
+pub fn try_parse_numeric_value<T: ByteOrder>(
+    buf: &mut Cursor<&[u8]>,
+    float: bool,
+    unsigned: bool,
+) -> std::io::Result<(&'static str, String)> {
+    Ok(match buf.get_ref().len() {
+        // 1 byte
+        1 if unsigned => ("u8", buf.read_u8()?.to_string()),
+        1 => ("i8", buf.read_i8()?.to_string()),
+        // 2 bytes
+        2 if unsigned => ("u16", buf.read_u16::<T>()?.to_string()),
+        2 => ("i16", buf.read_i16::<T>()?.to_string()),
+        // 4 bytes
+        4 if unsigned => ("u32", buf.read_u32::<T>()?.to_string()),
+        4 if float => ("f32", buf.read_f32::<T>()?.to_string()),
+        4 => ("i32", buf.read_i32::<T>()?.to_string()),
+        // 8 bytes
+        8 if unsigned => ("u64", buf.read_u64::<T>()?.to_string()),
+        8 if float => ("f64", buf.read_f64::<T>()?.to_string()),
+        8 => ("i64", buf.read_i64::<T>()?.to_string()),
+        // 16 bytes
+        16 if unsigned => ("u128", buf.read_u128::<T>()?.to_string()),
+        16 => ("i128", buf.read_i128::<T>()?.to_string()),
+        _ => return Err(std::io::ErrorKind::InvalidData.into()),
+    })
+}
+
+#[test]
+fn parses_write_payloads() {
+    assert_eq!(
+        parse_write_payload("0x01 02:ff", WriteFormat::Hex, false).unwrap(),
+        vec![0x01, 0x02, 0xff]
+    );
+    assert!(parse_write_payload("012", WriteFormat::Hex, false).is_err());
+    assert!(parse_write_payload("zz", WriteFormat::Hex, false).is_err());
+    assert!(parse_write_payload("aéb", WriteFormat::Hex, false).is_err());
+    assert!(parse_write_payload("ééé", WriteFormat::Hex, false).is_err());
+    assert_eq!(
+        parse_write_payload("0XAbCd", WriteFormat::Hex, false).unwrap(),
+        vec![0xab, 0xcd]
+    );
+    assert_eq!(
+        parse_write_payload("hi", WriteFormat::Utf8, false).unwrap(),
+        b"hi".to_vec()
+    );
+    assert_eq!(
+        parse_write_payload("-2", WriteFormat::I16, false).unwrap(),
+        vec![0xfe, 0xff]
+    );
+    assert_eq!(
+        parse_write_payload("258", WriteFormat::U32, true).unwrap(),
+        vec![0, 0, 1, 2]
+    );
+    assert_eq!(
+        parse_write_payload("1.5", WriteFormat::F32, false).unwrap(),
+        1.5f32.to_le_bytes().to_vec()
+    );
+    assert!(parse_write_payload("256", WriteFormat::U8, false).is_err());
+}
@@ -0,0 +1,509 @@
+use crate::bluetooth::backend::{
+    AdapterEvent, BleAdapter, BleManager, BlePeripheral, BoxStream, PeripheralId,
+};
+use crate::error::Result;
+use crate::session::{read_session, SessionEntry, SessionEvent, ValueSource};
+use async_trait::async_trait;
+use btleplug::api::{
+    Characteristic, Descriptor, PeripheralProperties, ScanFilter, Service, ValueNotification,
+    WriteType,
+};
+use futures::StreamExt;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{sync::broadcast, task::JoinHandle};
+use uuid::Uuid;
+
+const CONNECT_DELAY: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone)]
+struct RecordedValue {
+    elapsed: Duration,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+struct PeripheralState {
+    connected: bool,
+    services_discovered: bool,
+    /// Outcomes of the recorded connection attempts that were not replayed yet
+    connect_attempts: VecDeque<Option<String>>,
+    read_cursors: HashMap<Uuid, usize>,
+    subscriptions: HashMap<Uuid, JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+pub struct ReplayPeripheral {
+    id: String,
+    started: Instant,
+    /// Advertisement snapshots of this peripheral in the order they were scanned
+    advertisements: Vec<(Duration, PeripheralProperties)>,
+    services: BTreeSet<Service>,
+    reads: HashMap<Uuid, Vec<RecordedValue>>,
+    notifications_log: HashMap<Uuid, Vec<RecordedValue>>,
+    state: Mutex<PeripheralState>,
+    notifications: broadcast::Sender<ValueNotification>,
+}
+
+/// Adapter replaying scans from the recorded session with the same timing they were recorded
+#[derive(Debug)]
+pub struct ReplayAdapter {
+    started: Instant,
+    /// Elapsed time of every recorded scan and ids of the peripherals updated since the previous one
+    scans: Vec<(Duration, Vec<String>)>,
+    peripherals: HashMap<String, Arc<ReplayPeripheral>>,
+}
+
+/// BLE stack driven by the session recorded with --record-session
+#[derive(Debug)]
+pub struct ReplayManager {
+    adapter: Arc<ReplayAdapter>,
+}
+
+impl ReplayPeripheral {
+    fn ensure_connected(&self) -> btleplug::Result<()> {
+        if self.state.lock().unwrap().connected {
+            Ok(())
+        } else {
+            Err(btleplug::Error::NotConnected)
+        }
+    }
+
+    fn stop_subscriptions(&self) {
+        let mut state = self.state.lock().unwrap();
+        for (_, handle) in state.subscriptions.drain() {
+            handle.abort();
+        }
+    }
+}
+
+impl ReplayManager {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Ok(Self::from_entries(read_session(path)?))
+    }
+
+    pub fn from_entries(entries: Vec<SessionEntry>) -> Self {
+        let started = Instant::now();
+        let mut scans = Vec::new();
+        let mut advertisements: HashMap<String, Vec<(Duration, PeripheralProperties)>> =
+            HashMap::new();
+        let mut connect_attempts: HashMap<String, VecDeque<Option<String>>> = HashMap::new();
+        let mut services: HashMap<String, BTreeSet<Service>> = HashMap::new();
+        let mut reads: HashMap<String, HashMap<Uuid, Vec<RecordedValue>>> = HashMap::new();
+        let mut notifications: HashMap<String, HashMap<Uuid, Vec<RecordedValue>>> = HashMap::new();
+
+        for entry in entries {
+            let elapsed = Duration::from_millis(entry.elapsed_ms);
+
+            match entry.event {
+                SessionEvent::Scan { peripherals } => {
+                    let ids = peripherals
+                        .into_iter()
+                        .map(|peripheral| {
+                            advertisements
+                                .entry(peripheral.id.clone())
+                                .or_default()
+                                .push((elapsed, peripheral.properties));
+
+                            peripheral.id
+                        })
+                        .collect();
+
+                    scans.push((elapsed, ids));
+                }
+                SessionEvent::ConnectAttempt { id, error } => {
+                    connect_attempts.entry(id).or_default().push_back(error);
+                }
+                SessionEvent::ServicesDiscovered {
+                    id,
+                    services: found,
+                } => {
+                    services.insert(id, found.iter().map(Service::from).collect());
+                }
+                SessionEvent::Value {
+                    id,
+                    characteristic,
+                    source,
+                    data,
+                    ..
+                } => {
+                    let log = match source {
+                        ValueSource::Read => &mut reads,
+                        ValueSource::Notification => &mut notifications,
+                        ValueSource::Write => continue,
+                    };
+
+                    log.entry(id)
+                        .or_default()
+                        .entry(characteristic)
+                        .or_default()
+                        .push(RecordedValue { elapsed, data });
+                }
+            }
+        }
+
+        let peripherals = advertisements
+            .into_iter()
+            .map(|(id, advertisements)| {
+                let peripheral = ReplayPeripheral {
+                    started,
+                    advertisements,
+                    services: services.remove(&id).unwrap_or_default(),
+                    reads: reads.remove(&id).unwrap_or_default(),
+                    notifications_log: notifications.remove(&id).unwrap_or_default(),
+                    state: Mutex::new(PeripheralState {
+                        connect_attempts: connect_attempts.remove(&id).unwrap_or_default(),
+                        ..Default::default()
+                    }),
+                    notifications: broadcast::channel(64).0,
+                    id: id.clone(),
+                };
+
+                (id, Arc::new(peripheral))
+            })
+            .collect();
+
+        Self {
+            adapter: Arc::new(ReplayAdapter {
+                started,
+                scans,
+                peripherals,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl BleManager for ReplayManager {
+    async fn adapters(&self) -> btleplug::Result<Vec<Arc<dyn BleAdapter>>> {
+        Ok(vec![Arc::clone(&self.adapter) as Arc<dyn BleAdapter>])
+    }
+}
+
+#[async_trait]
+impl BleAdapter for ReplayAdapter {
+    async fn adapter_info(&self) -> btleplug::Result<String> {
+        Ok("Session replay".to_owned())
+    }
+
+    async fn start_scan(&self, _filter: ScanFilter) -> btleplug::Result<()> {
+        Ok(())
+    }
+
+    async fn stop_scan(&self) -> btleplug::Result<()> {
+        Ok(())
+    }
+
+    async fn peripherals(&self) -> btleplug::Result<Vec<Arc<dyn BlePeripheral>>> {
+        let elapsed = self.started.elapsed();
+        let scanned_ids = self
+            .scans
+            .iter()
+            .take_while(|(scanned_at, _)| *scanned_at <= elapsed)
+            .flat_map(|(_, ids)| ids)
+            .collect::<BTreeSet<_>>();
+
+        Ok(scanned_ids
+            .into_iter()
+            .filter_map(|id| self.peripherals.get(id))
+            .map(|peripheral| Arc::new(Arc::clone(peripheral)) as Arc<dyn BlePeripheral>)
+            .collect())
+    }
+
+    async fn peripheral(&self, id: &PeripheralId) -> btleplug::Result<Arc<dyn BlePeripheral>> {
+        match id {
+            PeripheralId::Replayed(id) => self
+                .peripherals
+                .get(id)
+                .map(|peripheral| Arc::new(Arc::clone(peripheral)) as Arc<dyn BlePeripheral>)
+                .ok_or(btleplug::Error::DeviceNotFound),
+            _ => Err(btleplug::Error::DeviceNotFound),
+        }
+    }
+
+    /// Every peripheral of the recorded scan is reported as updated at the time it was scanned
+    async fn events(&self) -> btleplug::Result<BoxStream<AdapterEvent>> {
+        let mut discovered = HashSet::new();
+        let mut events = Vec::new();
+        for (elapsed, ids) in &self.scans {
+            for id in ids {
+                let id = PeripheralId::Replayed(id.clone());
+                let event = if discovered.insert(id.clone()) {
+                    AdapterEvent::DeviceDiscovered(id)
+                } else {
+                    AdapterEvent::DeviceUpdated(id)
+                };
+
+                events.push((self.started + *elapsed, event));
+            }
+        }
+
+        Ok(Box::pin(
+            futures::stream::iter(events)
+                .then(|(at, event)| async move {
+                    tokio::time::sleep_until(at.into()).await;
+                    event
+                })
+                // the adapter stays around after the recording ends
+                .chain(futures::stream::pending()),
+        ))
+    }
+}
+
+#[async_trait]
+impl BlePeripheral for Arc<ReplayPeripheral> {
+    fn id(&self) -> PeripheralId {
+        PeripheralId::Replayed(self.id.clone())
+    }
+
+    fn services(&self) -> BTreeSet<Service> {
+        if !self.state.lock().unwrap().services_discovered {
+            return BTreeSet::new();
+        }
+
+        self.services.clone()
+    }
+
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        self.services()
+            .into_iter()
+            .flat_map(|service| service.characteristics)
+            .collect()
+    }
+
+    async fn properties(&self) -> btleplug::Result<Option<PeripheralProperties>> {
+        let elapsed = self.started.elapsed();
+
+        Ok(self
+            .advertisements
+            .iter()
+            .take_while(|(scanned_at, _)| *scanned_at <= elapsed)
+            .last()
+            .or(self.advertisements.first())
+            .map(|(_, properties)| properties.clone()))
+    }
+
+    async fn is_connected(&self) -> btleplug::Result<bool> {
+        Ok(self.state.lock().unwrap().connected)
+    }
+
+    async fn connect(&self) -> btleplug::Result<()> {
+        tokio::time::sleep(CONNECT_DELAY).await;
+
+        let mut state = self.state.lock().unwrap();
+        // once the recorded attempts are over the connection always succeeds
+        if let Some(Some(error)) = state.connect_attempts.pop_front() {
+            return Err(btleplug::Error::Other(error.into()));
+        }
+
+        state.connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> btleplug::Result<()> {
+        self.stop_subscriptions();
+
+        let mut state = self.state.lock().unwrap();
+        state.connected = false;
+        state.services_discovered = false;
+
+        Ok(())
+    }
+
+    async fn discover_services(&self) -> btleplug::Result<()> {
+        self.ensure_connected()?;
+        self.state.lock().unwrap().services_discovered = true;
+
+        Ok(())
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> btleplug::Result<Vec<u8>> {
+        self.ensure_connected()?;
+
+        let values = self
+            .reads
+            .get(&characteristic.uuid)
+            .filter(|values| !values.is_empty())
+            .ok_or_else(|| {
+                btleplug::Error::NotSupported(
+                    "No reads of this characteristic were recorded".to_owned(),
+                )
+            })?;
+
+        let mut state = self.state.lock().unwrap();
+        let cursor = state.read_cursors.entry(characteristic.uuid).or_default();
+        let value = values[*cursor % values.len()].data.clone();
+        *cursor = cursor.wrapping_add(1);
+
+        Ok(value)
+    }
+
+    async fn write(
+        &self,
+        _characteristic: &Characteristic,
+        _data: &[u8],
+        _write_type: WriteType,
+    ) -> btleplug::Result<()> {
+        // writes can not change the recorded session, so they are only accepted
+        self.ensure_connected()
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> btleplug::Result<()> {
+        self.ensure_connected()?;
+
+        let uuid = characteristic.uuid;
+        let values = self
+            .notifications_log
+            .get(&uuid)
+            .cloned()
+            .unwrap_or_default();
+        let peripheral = Arc::clone(self);
+
+        let handle = tokio::spawn(async move {
+            // keep the original gaps between notifications
+            let mut previous = values.first().map(|value| value.elapsed);
+            for value in values {
+                if let Some(previous) = previous.replace(value.elapsed) {
+                    tokio::time::sleep(value.elapsed.saturating_sub(previous)).await;
+                }
+
+                let _ = peripheral.notifications.send(ValueNotification {
+                    uuid,
+                    value: value.data,
+                });
+            }
+        });
+
+        if let Some(previous) = self
+            .state
+            .lock()
+            .unwrap()
+            .subscriptions
+            .insert(uuid, handle)
+        {
+            previous.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> btleplug::Result<()> {
+        if let Some(handle) = self
+            .state
+            .lock()
+            .unwrap()
+            .subscriptions
+            .remove(&characteristic.uuid)
+        {
+            handle.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn notifications(&self) -> btleplug::Result<BoxStream<ValueNotification>> {
+        let receiver = self.notifications.subscribe();
+
+        Ok(Box::pin(futures::stream::unfold(
+            receiver,
+            |mut receiver| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(notification) => return Some((notification, receiver)),
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            },
+        )))
+    }
+
+    async fn read_descriptor(&self, _descriptor: &Descriptor) -> btleplug::Result<Vec<u8>> {
+        Err(btleplug::Error::NotSupported(
+            "Descriptor values are not recorded in the session".to_owned(),
+        ))
+    }
+
+    async fn write_descriptor(
+        &self,
+        _descriptor: &Descriptor,
+        _data: &[u8],
+    ) -> btleplug::Result<()> {
+        Err(btleplug::Error::NotSupported(
+            "Descriptors can not be written in the session replay".to_owned(),
+        ))
+    }
+}
+
+#[tokio::test]
+async fn replays_recorded_session() {
+    use crate::bluetooth::simulator::{simulated_ctx, TEST_SIMULATION};
+    use crate::{cli_args::Args, session::SessionRecorder, Ctx};
+    use clap::Parser;
+
+    let session_path = std::env::temp_dir().join("blendr-replay-test.jsonl");
+    let subscribe = [
+        "subscribe",
+        "--device",
+        "thermo",
+        "--char",
+        "2a6e",
+        "--count",
+        "2",
+    ];
+
+    let mut ctx = simulated_ctx(TEST_SIMULATION, &subscribe);
+    ctx.session_recorder = Some(Mutex::new(SessionRecorder::create(&session_path).unwrap()));
+    let command = ctx.args.command.clone().unwrap();
+    crate::commands::run(Arc::new(ctx), command, &mut std::io::sink())
+        .await
+        .unwrap();
+
+    let entries = read_session(&session_path).unwrap();
+    let connect_errors = entries
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            SessionEvent::ConnectAttempt { error, .. } => Some(error.is_some()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    // the simulated thermometer refuses the first connection
+    assert_eq!(connect_errors, vec![true, false]);
+
+    let manager = ReplayManager::from_entries(entries);
+    let adapter = &manager.adapters().await.unwrap()[0];
+    let peripheral = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(peripheral) = adapter.peripherals().await.unwrap().pop() {
+                break peripheral;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert!(peripheral.connect().await.is_err());
+    peripheral.connect().await.unwrap();
+    peripheral.discover_services().await.unwrap();
+    assert_eq!(peripheral.characteristics().len(), 2);
+    peripheral.disconnect().await.unwrap();
+
+    let args = Args::parse_from(["blendr", "--scan-interval", "10"].iter().chain(&subscribe));
+    let command = args.command.clone().unwrap();
+    let manager = ReplayManager::from_file(&session_path).unwrap();
+    crate::commands::run(
+        Arc::new(Ctx::new(args, Arc::new(manager))),
+        command,
+        &mut std::io::sink(),
+    )
+    .await
+    .unwrap();
+
+    std::fs::remove_file(session_path).unwrap();
+}
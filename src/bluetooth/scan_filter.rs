@@ -0,0 +1,166 @@
+use crate::{
+    bluetooth::{ble_default_services::parse_ble_uuid, HandledPeripheral},
+    cli_args::ScanFilterArgs,
+    error::{Error, Result},
+};
+use btleplug::api::ScanFilter;
+use uuid::Uuid;
+
+/// Scan restrictions configured with the command line flags
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanOptions {
+    pub services: Vec<Uuid>,
+    pub min_rssi: Option<i16>,
+    /// Upper case address prefix
+    pub address_prefix: Option<String>,
+    pub manufacturer_id: Option<u16>,
+    pub filter_duplicates: bool,
+    /// Forget peripherals silent for --stale-after seconds, toggled from the UI
+    pub drop_stale: bool,
+    /// Filter can be temporary disabled from the UI without losing the configuration
+    pub enabled: bool,
+}
+
+impl TryFrom<&ScanFilterArgs> for ScanOptions {
+    type Error = Error;
+
+    fn try_from(args: &ScanFilterArgs) -> Result<Self> {
+        let services = args
+            .services
+            .iter()
+            .map(|uuid| {
+                parse_ble_uuid(uuid).map_err(|e| {
+                    Error::client(format!("Invalid --scan-service uuid \"{uuid}\": {e}"))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut options = Self {
+            services,
+            min_rssi: args.min_rssi,
+            address_prefix: args
+                .address_prefix
+                .as_ref()
+                .map(|prefix| prefix.to_uppercase()),
+            manufacturer_id: args.manufacturer_id,
+            filter_duplicates: args.filter_duplicates,
+            drop_stale: false,
+            enabled: false,
+        };
+        options.enabled = options.is_configured();
+
+        Ok(options)
+    }
+}
+
+impl ScanOptions {
+    pub fn is_configured(&self) -> bool {
+        !self.services.is_empty()
+            || self.min_rssi.is_some()
+            || self.address_prefix.is_some()
+            || self.manufacturer_id.is_some()
+    }
+
+    /// Filter passed to the adapter, only services are supported by the backends
+    pub fn ble_filter(&self) -> ScanFilter {
+        ScanFilter {
+            services: if self.enabled {
+                self.services.clone()
+            } else {
+                vec![]
+            },
+        }
+    }
+
+    /// Not every platform respects the services filter so it is checked again with the rest of the options
+    pub fn matches(&self, peripheral: &HandledPeripheral) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        (self.services.is_empty()
+            || self
+                .services
+                .iter()
+                .any(|uuid| peripheral.services.contains(uuid)))
+            && self
+                .min_rssi
+                .is_none_or(|min_rssi| peripheral.rssi >= Some(min_rssi))
+            && self.address_prefix.as_ref().is_none_or(|prefix| {
+                peripheral
+                    .address
+                    .to_string()
+                    .to_uppercase()
+                    .starts_with(prefix.as_str())
+            })
+            && self
+                .manufacturer_id
+                .is_none_or(|company_id| peripheral.manufacturer_data.contains_key(&company_id))
+    }
+
+    /// Short summary of the active filter, `None` if nothing is filtered
+    pub fn describe(&self) -> Option<String> {
+        if !self.enabled || !self.is_configured() {
+            return None;
+        }
+
+        let mut parts = self
+            .services
+            .iter()
+            .map(|uuid| format!("service {uuid}"))
+            .collect::<Vec<_>>();
+
+        if let Some(min_rssi) = self.min_rssi {
+            parts.push(format!("rssi >= {min_rssi}"));
+        }
+
+        if let Some(prefix) = &self.address_prefix {
+            parts.push(format!("address {prefix}*"));
+        }
+
+        if let Some(company_id) = self.manufacturer_id {
+            parts.push(format!("manufacturer 0x{company_id:04X}"));
+        }
+
+        Some(parts.join(", "))
+    }
+}
+
+#[test]
+fn builds_scan_options_from_args() {
+    use clap::Parser;
+
+    let args = crate::cli_args::Args::parse_from([
+        "blendr",
+        "--scan-service",
+        "180d",
+        "--min-rssi=-70",
+        "--address-prefix",
+        "aa:bb",
+        "--manufacturer-id",
+        "0x004C",
+    ]);
+    let options = ScanOptions::try_from(&args.scan_filter).unwrap();
+
+    assert!(options.enabled);
+    assert_eq!(
+        options.ble_filter().services,
+        vec![crate::bluetooth::ble_default_services::create_ble_uuid(
+            0x180D
+        )]
+    );
+    assert_eq!(
+        options.describe().unwrap(),
+        "service 0000180d-0000-1000-8000-00805f9b34fb, rssi >= -70, address AA:BB*, manufacturer 0x004C"
+    );
+
+    let disabled = ScanOptions {
+        enabled: false,
+        ..options
+    };
+    assert!(disabled.ble_filter().services.is_empty());
+    assert_eq!(disabled.describe(), None);
+
+    let invalid = crate::cli_args::Args::parse_from(["blendr", "--scan-service", "nope"]);
+    assert!(ScanOptions::try_from(&invalid.scan_filter).is_err());
+}
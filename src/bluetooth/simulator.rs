@@ -0,0 +1,1206 @@
+use crate::bluetooth::{
+    backend::{
+        AdapterEvent, BleAdapter, BleManager, BlePeripheral, BoxStream, GattAttribute, PeripheralId,
+    },
+    ble_default_services::parse_ble_uuid,
+    gatt_decoders::CLIENT_CONFIGURATION,
+    payload::parse_hex,
+};
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use btleplug::api::{
+    AddressType, BDAddr, CharPropFlags, Characteristic, Descriptor, PeripheralProperties,
+    ScanFilter, Service, ValueNotification, WriteType,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{sync::broadcast, task::JoinHandle};
+use uuid::Uuid;
+
+const CONNECT_DELAY: Duration = Duration::from_millis(300);
+const DEFAULT_NOTIFY_INTERVAL_MS: u64 = 1000;
+const ADAPTER_RSSI_STEP: i16 = 10;
+const ADVERTISING_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, serde::Deserialize)]
+struct SimulationFile {
+    adapter: Option<String>,
+    /// Several adapters seeing the same devices, every next adapter receives 10 dBm weaker signal
+    #[serde(default)]
+    adapters: Vec<String>,
+    #[serde(default, rename = "device")]
+    devices: Vec<DeviceConfig>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DeviceConfig {
+    name: Option<String>,
+    address: String,
+    rssi: Option<i16>,
+    /// Advertised services uuids
+    #[serde(default)]
+    services: Vec<String>,
+    tx_power: Option<i16>,
+    /// Advertised manufacturer data, keys are company identifiers e.g. "0x004c" and values are hex payloads
+    #[serde(default)]
+    manufacturer_data: HashMap<String, String>,
+    /// Advertised service data, keys are service uuids and values are hex payloads
+    #[serde(default)]
+    service_data: HashMap<String, String>,
+    /// Amount of connection attempts that will fail before the device accepts connection
+    #[serde(default)]
+    failed_connections: u16,
+    /// Device goes silent after this time, adapter keeps reporting its last advertisement
+    advertise_for_ms: Option<u64>,
+    /// Device drops every connection after this time and refuses `failed_connections` attempts again,
+    /// simulating a power cycle
+    disconnect_after_ms: Option<u64>,
+    /// Services are created from the characteristics, declare them only to make them secondary
+    /// or to expose a service without characteristics
+    #[serde(default, rename = "service")]
+    gatt_services: Vec<ServiceConfig>,
+    #[serde(default, rename = "characteristic")]
+    characteristics: Vec<CharacteristicConfig>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ServiceConfig {
+    uuid: String,
+    #[serde(default)]
+    secondary: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct CharacteristicConfig {
+    service: String,
+    uuid: String,
+    properties: Vec<String>,
+    /// Hex encoded values that are returned one by one on every read or notification
+    #[serde(default)]
+    values: Vec<String>,
+    notify_interval_ms: Option<u64>,
+    /// Client characteristic configuration is added to the notifying characteristics on its own
+    #[serde(default, rename = "descriptor")]
+    descriptors: Vec<DescriptorConfig>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DescriptorConfig {
+    uuid: String,
+    /// Hex encoded value returned on every read
+    #[serde(default)]
+    value: String,
+}
+
+#[derive(Debug)]
+struct SimulatedCharacteristic {
+    characteristic: Characteristic,
+    values: Vec<Vec<u8>>,
+    notify_interval: Duration,
+    descriptors: HashMap<Uuid, Vec<u8>>,
+}
+
+#[derive(Debug, Default)]
+struct PeripheralState {
+    connected: bool,
+    /// Incremented on every connect so a scheduled drop does not affect the later connections
+    connection_generation: u64,
+    failed_connections_left: u16,
+    services_discovered: bool,
+    /// Values written by the user, takes precedence over the configured values
+    written_values: HashMap<Uuid, Vec<u8>>,
+    /// Written descriptor values keyed by the characteristic and descriptor uuids
+    written_descriptors: HashMap<(Uuid, Uuid), Vec<u8>>,
+    value_cursors: HashMap<Uuid, usize>,
+    subscriptions: HashMap<Uuid, JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+pub struct SimulatedPeripheral {
+    adapter_index: usize,
+    address: BDAddr,
+    name: Option<String>,
+    rssi: Option<i16>,
+    advertised_services: Vec<Uuid>,
+    tx_power: Option<i16>,
+    manufacturer_data: HashMap<u16, Vec<u8>>,
+    service_data: HashMap<Uuid, Vec<u8>>,
+    advertise_until: Option<Instant>,
+    failed_connections: u16,
+    disconnect_after: Option<Duration>,
+    /// Service uuids and whether they are primary in the order of the GATT table
+    services: Vec<(Uuid, bool)>,
+    characteristics: Vec<Arc<SimulatedCharacteristic>>,
+    handles: BTreeMap<GattAttribute, u16>,
+    state: Mutex<PeripheralState>,
+    notifications: broadcast::Sender<ValueNotification>,
+    adapter_events: broadcast::Sender<AdapterEvent>,
+}
+
+#[derive(Debug)]
+pub struct SimulatedAdapter {
+    name: String,
+    scanning: AtomicBool,
+    peripherals: Vec<Arc<SimulatedPeripheral>>,
+    events: broadcast::Sender<AdapterEvent>,
+    /// Emits the advertisements of every device while scanning
+    advertiser: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// In-process BLE stack serving fake devices described in a simulation file.
+#[derive(Debug)]
+pub struct SimulatedManager {
+    adapters: Vec<Arc<SimulatedAdapter>>,
+}
+
+fn parse_properties(properties: &[String]) -> Result<CharPropFlags> {
+    properties
+        .iter()
+        .try_fold(CharPropFlags::empty(), |flags, property| {
+            let flag = match property.to_lowercase().as_str() {
+                "broadcast" => CharPropFlags::BROADCAST,
+                "read" => CharPropFlags::READ,
+                "write" => CharPropFlags::WRITE,
+                "write_without_response" => CharPropFlags::WRITE_WITHOUT_RESPONSE,
+                "notify" => CharPropFlags::NOTIFY,
+                "indicate" => CharPropFlags::INDICATE,
+                unknown => {
+                    return Err(Error::client(format!(
+                        "Unknown characteristic property \"{unknown}\""
+                    )))
+                }
+            };
+
+            Ok(flags | flag)
+        })
+}
+
+fn parse_company_id(value: &str) -> Result<u16> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|e| Error::client(format!("Invalid company identifier \"{value}\": {e}")))
+}
+
+/// Numbers the attributes one by one like a GATT server does: service declaration, then
+/// characteristic declaration, its value and its descriptors
+fn assign_handles(
+    services: &[(Uuid, bool)],
+    characteristics: &[Arc<SimulatedCharacteristic>],
+) -> BTreeMap<GattAttribute, u16> {
+    let mut handles = BTreeMap::new();
+    let mut handle = 0;
+
+    for (service_uuid, _) in services {
+        handle += 1;
+        handles.insert(GattAttribute::Service(*service_uuid), handle);
+
+        for char in characteristics
+            .iter()
+            .filter(|char| char.characteristic.service_uuid == *service_uuid)
+        {
+            handle += 2;
+            handles.insert(
+                GattAttribute::Characteristic {
+                    service_uuid: *service_uuid,
+                    uuid: char.characteristic.uuid,
+                },
+                handle,
+            );
+
+            for descriptor in &char.characteristic.descriptors {
+                handle += 1;
+                handles.insert(GattAttribute::from(descriptor), handle);
+            }
+        }
+    }
+
+    handles
+}
+
+/// Lagging receivers skip the missed items, same as the platform streams drop the events
+fn broadcast_stream<T: Clone + Send + 'static>(receiver: broadcast::Receiver<T>) -> BoxStream<T> {
+    Box::pin(futures::stream::unfold(
+        receiver,
+        |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(item) => return Some((item, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        },
+    ))
+}
+
+fn parse_uuid(value: &str) -> Result<Uuid> {
+    parse_ble_uuid(value).map_err(|e| Error::client(format!("Invalid uuid \"{value}\": {e}")))
+}
+
+impl SimulatedPeripheral {
+    fn from_config(
+        config: &DeviceConfig,
+        adapter_index: usize,
+        adapter_events: broadcast::Sender<AdapterEvent>,
+    ) -> Result<Self> {
+        let characteristics: Vec<Arc<SimulatedCharacteristic>> = config
+            .characteristics
+            .iter()
+            .map(|char| {
+                let uuid = parse_uuid(&char.uuid)?;
+                let service_uuid = parse_uuid(&char.service)?;
+                let properties = parse_properties(&char.properties)?;
+
+                let mut descriptors = char
+                    .descriptors
+                    .iter()
+                    .map(|descriptor| {
+                        Ok((parse_uuid(&descriptor.uuid)?, parse_hex(&descriptor.value)?))
+                    })
+                    .collect::<Result<HashMap<_, _>>>()?;
+                if properties.intersects(CharPropFlags::NOTIFY | CharPropFlags::INDICATE) {
+                    descriptors
+                        .entry(CLIENT_CONFIGURATION)
+                        .or_insert_with(|| vec![0, 0]);
+                }
+
+                Ok(Arc::new(SimulatedCharacteristic {
+                    characteristic: Characteristic {
+                        uuid,
+                        service_uuid,
+                        properties,
+                        descriptors: descriptors
+                            .keys()
+                            .map(|descriptor_uuid| Descriptor {
+                                uuid: *descriptor_uuid,
+                                service_uuid,
+                                characteristic_uuid: uuid,
+                            })
+                            .collect(),
+                    },
+                    values: char
+                        .values
+                        .iter()
+                        .map(|value| parse_hex(value))
+                        .collect::<Result<_>>()?,
+                    notify_interval: Duration::from_millis(
+                        char.notify_interval_ms
+                            .unwrap_or(DEFAULT_NOTIFY_INTERVAL_MS),
+                    ),
+                    descriptors,
+                }))
+            })
+            .collect::<Result<_>>()?;
+
+        let mut services = config
+            .gatt_services
+            .iter()
+            .map(|service| Ok((parse_uuid(&service.uuid)?, !service.secondary)))
+            .collect::<Result<Vec<_>>>()?;
+        for char in &characteristics {
+            let service_uuid = char.characteristic.service_uuid;
+            if !services.iter().any(|(uuid, _)| *uuid == service_uuid) {
+                services.push((service_uuid, true));
+            }
+        }
+
+        let handles = assign_handles(&services, &characteristics);
+
+        Ok(Self {
+            address: BDAddr::from_str(&config.address).map_err(|e| {
+                Error::client(format!("Invalid device address {}: {e}", config.address))
+            })?,
+            adapter_index,
+            name: config.name.clone(),
+            rssi: config
+                .rssi
+                .map(|rssi| rssi - ADAPTER_RSSI_STEP * adapter_index as i16),
+            advertised_services: config
+                .services
+                .iter()
+                .map(|uuid| parse_uuid(uuid))
+                .collect::<Result<_>>()?,
+            tx_power: config.tx_power,
+            manufacturer_data: config
+                .manufacturer_data
+                .iter()
+                .map(|(company, data)| Ok((parse_company_id(company)?, parse_hex(data)?)))
+                .collect::<Result<_>>()?,
+            service_data: config
+                .service_data
+                .iter()
+                .map(|(uuid, data)| Ok((parse_uuid(uuid)?, parse_hex(data)?)))
+                .collect::<Result<_>>()?,
+            advertise_until: config
+                .advertise_for_ms
+                .map(|ms| Instant::now() + Duration::from_millis(ms)),
+            failed_connections: config.failed_connections,
+            disconnect_after: config.disconnect_after_ms.map(Duration::from_millis),
+            services,
+            characteristics,
+            handles,
+            state: Mutex::new(PeripheralState {
+                failed_connections_left: config.failed_connections,
+                ..Default::default()
+            }),
+            notifications: broadcast::channel(64).0,
+            adapter_events,
+        })
+    }
+
+    fn peripheral_id(&self) -> PeripheralId {
+        PeripheralId::Simulated {
+            adapter_index: self.adapter_index,
+            address: self.address,
+        }
+    }
+
+    fn is_silent(&self) -> bool {
+        self.advertise_until
+            .is_some_and(|until| Instant::now() >= until)
+    }
+
+    fn advertise(&self) {
+        if self.is_silent() {
+            return;
+        }
+
+        let id = self.peripheral_id();
+        // nobody listening is fine, the scan might be stopped already
+        let _ = self
+            .adapter_events
+            .send(AdapterEvent::DeviceUpdated(id.clone()));
+
+        if !self.manufacturer_data.is_empty() {
+            let _ = self
+                .adapter_events
+                .send(AdapterEvent::ManufacturerDataAdvertisement {
+                    id: id.clone(),
+                    manufacturer_data: self.manufacturer_data.clone(),
+                });
+        }
+
+        if !self.service_data.is_empty() {
+            let _ = self
+                .adapter_events
+                .send(AdapterEvent::ServiceDataAdvertisement {
+                    id,
+                    service_data: self.service_data.clone(),
+                });
+        }
+    }
+
+    fn find_characteristic(
+        &self,
+        characteristic: &Characteristic,
+    ) -> btleplug::Result<Arc<SimulatedCharacteristic>> {
+        self.characteristics
+            .iter()
+            .find(|char| {
+                char.characteristic.uuid == characteristic.uuid
+                    && char.characteristic.service_uuid == characteristic.service_uuid
+            })
+            .cloned()
+            .ok_or(btleplug::Error::NoSuchCharacteristic)
+    }
+
+    fn find_descriptor(
+        &self,
+        descriptor: &Descriptor,
+    ) -> btleplug::Result<Arc<SimulatedCharacteristic>> {
+        self.characteristics
+            .iter()
+            .find(|char| {
+                char.characteristic.uuid == descriptor.characteristic_uuid
+                    && char.characteristic.service_uuid == descriptor.service_uuid
+                    && char.descriptors.contains_key(&descriptor.uuid)
+            })
+            .cloned()
+            .ok_or(btleplug::Error::NotSupported(format!(
+                "No such descriptor {}",
+                descriptor.uuid
+            )))
+    }
+
+    fn ensure_connected(&self) -> btleplug::Result<()> {
+        if self.state.lock().unwrap().connected {
+            Ok(())
+        } else {
+            Err(btleplug::Error::NotConnected)
+        }
+    }
+
+    /// Returns the last written value or the next configured value or the counter if nothing configured
+    fn next_value(&self, char: &SimulatedCharacteristic) -> Vec<u8> {
+        let mut state = self.state.lock().unwrap();
+        let uuid = char.characteristic.uuid;
+
+        if let Some(written) = state.written_values.get(&uuid) {
+            return written.clone();
+        }
+
+        let cursor = state.value_cursors.entry(uuid).or_default();
+        let value = if char.values.is_empty() {
+            (*cursor as u16).to_le_bytes().to_vec()
+        } else {
+            char.values[*cursor % char.values.len()].clone()
+        };
+
+        *cursor = cursor.wrapping_add(1);
+        value
+    }
+
+    fn stop_subscriptions(&self) {
+        let mut state = self.state.lock().unwrap();
+        for (_, handle) in state.subscriptions.drain() {
+            handle.abort();
+        }
+    }
+
+    /// Connection lost on the device side, e.g. it was rebooted
+    fn drop_connection(&self, generation: u64) {
+        let mut state = self.state.lock().unwrap();
+        if !state.connected || state.connection_generation != generation {
+            return;
+        }
+
+        for (_, handle) in state.subscriptions.drain() {
+            handle.abort();
+        }
+
+        state.connected = false;
+        state.services_discovered = false;
+        state.failed_connections_left = self.failed_connections;
+
+        let _ = self
+            .adapter_events
+            .send(AdapterEvent::DeviceDisconnected(self.peripheral_id()));
+    }
+}
+
+#[async_trait]
+impl BlePeripheral for Arc<SimulatedPeripheral> {
+    fn id(&self) -> PeripheralId {
+        self.peripheral_id()
+    }
+
+    fn services(&self) -> BTreeSet<Service> {
+        if !self.state.lock().unwrap().services_discovered {
+            return BTreeSet::new();
+        }
+
+        self.services
+            .iter()
+            .map(|(uuid, primary)| Service {
+                uuid: *uuid,
+                primary: *primary,
+                characteristics: self
+                    .characteristics
+                    .iter()
+                    .filter(|char| char.characteristic.service_uuid == *uuid)
+                    .map(|char| char.characteristic.clone())
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        self.services()
+            .into_iter()
+            .flat_map(|service| service.characteristics)
+            .collect()
+    }
+
+    async fn properties(&self) -> btleplug::Result<Option<PeripheralProperties>> {
+        let silent = self.is_silent();
+        // make the signal strength look alive while the device advertises
+        let jitter = if silent {
+            0
+        } else {
+            (chrono::Local::now().timestamp_subsec_millis() % 7) as i16 - 3
+        };
+
+        Ok(Some(PeripheralProperties {
+            address: self.address,
+            local_name: self.name.clone(),
+            rssi: self.rssi.map(|rssi| rssi + jitter),
+            services: self.advertised_services.clone(),
+            tx_power_level: self.tx_power,
+            manufacturer_data: self.manufacturer_data.clone(),
+            service_data: self.service_data.clone(),
+            address_type: Some(AddressType::Public),
+            ..Default::default()
+        }))
+    }
+
+    async fn is_connected(&self) -> btleplug::Result<bool> {
+        Ok(self.state.lock().unwrap().connected)
+    }
+
+    async fn connect(&self) -> btleplug::Result<()> {
+        tokio::time::sleep(CONNECT_DELAY).await;
+
+        let mut state = self.state.lock().unwrap();
+        if state.failed_connections_left > 0 {
+            state.failed_connections_left -= 1;
+            return Err(btleplug::Error::TimedOut(CONNECT_DELAY));
+        }
+
+        state.connected = true;
+        state.connection_generation += 1;
+
+        if let Some(disconnect_after) = self.disconnect_after {
+            let peripheral = Arc::clone(self);
+            let generation = state.connection_generation;
+
+            tokio::spawn(async move {
+                tokio::time::sleep(disconnect_after).await;
+                peripheral.drop_connection(generation);
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> btleplug::Result<()> {
+        self.stop_subscriptions();
+
+        let mut state = self.state.lock().unwrap();
+        if state.connected {
+            let _ = self
+                .adapter_events
+                .send(AdapterEvent::DeviceDisconnected(self.peripheral_id()));
+        }
+
+        state.connected = false;
+        state.services_discovered = false;
+
+        Ok(())
+    }
+
+    async fn discover_services(&self) -> btleplug::Result<()> {
+        self.ensure_connected()?;
+        self.state.lock().unwrap().services_discovered = true;
+
+        Ok(())
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> btleplug::Result<Vec<u8>> {
+        self.ensure_connected()?;
+        let char = self.find_characteristic(characteristic)?;
+
+        if !char.characteristic.properties.contains(CharPropFlags::READ) {
+            return Err(btleplug::Error::NotSupported(
+                "Characteristic is not readable".to_owned(),
+            ));
+        }
+
+        Ok(self.next_value(&char))
+    }
+
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> btleplug::Result<()> {
+        self.ensure_connected()?;
+        let char = self.find_characteristic(characteristic)?;
+
+        let required_flag = match write_type {
+            WriteType::WithResponse => CharPropFlags::WRITE,
+            WriteType::WithoutResponse => CharPropFlags::WRITE_WITHOUT_RESPONSE,
+        };
+
+        if !char.characteristic.properties.contains(required_flag) {
+            return Err(btleplug::Error::NotSupported(format!(
+                "Characteristic does not support {write_type:?} writes"
+            )));
+        }
+
+        self.state
+            .lock()
+            .unwrap()
+            .written_values
+            .insert(char.characteristic.uuid, data.to_vec());
+
+        Ok(())
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> btleplug::Result<()> {
+        self.ensure_connected()?;
+        let char = self.find_characteristic(characteristic)?;
+
+        if !char
+            .characteristic
+            .properties
+            .intersects(CharPropFlags::NOTIFY | CharPropFlags::INDICATE)
+        {
+            return Err(btleplug::Error::NotSupported(
+                "Characteristic does not support notifications".to_owned(),
+            ));
+        }
+
+        let peripheral = Arc::clone(self);
+        let uuid = char.characteristic.uuid;
+        let handle = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(char.notify_interval).await;
+
+                let value = peripheral.next_value(&char);
+                // no receivers is not an error, the app may not listen yet
+                let _ = peripheral
+                    .notifications
+                    .send(ValueNotification { uuid, value });
+            }
+        });
+
+        if let Some(previous) = self
+            .state
+            .lock()
+            .unwrap()
+            .subscriptions
+            .insert(uuid, handle)
+        {
+            previous.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> btleplug::Result<()> {
+        if let Some(handle) = self
+            .state
+            .lock()
+            .unwrap()
+            .subscriptions
+            .remove(&characteristic.uuid)
+        {
+            handle.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn notifications(&self) -> btleplug::Result<BoxStream<ValueNotification>> {
+        Ok(broadcast_stream(self.notifications.subscribe()))
+    }
+
+    fn attribute_handles(&self) -> BTreeMap<GattAttribute, u16> {
+        if !self.state.lock().unwrap().services_discovered {
+            return BTreeMap::new();
+        }
+
+        self.handles.clone()
+    }
+
+    async fn read_descriptor(&self, descriptor: &Descriptor) -> btleplug::Result<Vec<u8>> {
+        self.ensure_connected()?;
+        let char = self.find_descriptor(descriptor)?;
+        let state = self.state.lock().unwrap();
+
+        // reflects the subscriptions made with both subscribe and the descriptor writes
+        if descriptor.uuid == CLIENT_CONFIGURATION {
+            let subscribed = state
+                .subscriptions
+                .get(&char.characteristic.uuid)
+                .is_some_and(|handle| !handle.is_finished());
+            let bit = if char
+                .characteristic
+                .properties
+                .contains(CharPropFlags::NOTIFY)
+            {
+                0b01
+            } else {
+                0b10
+            };
+
+            return Ok(vec![if subscribed { bit } else { 0 }, 0]);
+        }
+
+        Ok(state
+            .written_descriptors
+            .get(&(char.characteristic.uuid, descriptor.uuid))
+            .unwrap_or(&char.descriptors[&descriptor.uuid])
+            .clone())
+    }
+
+    async fn write_descriptor(&self, descriptor: &Descriptor, data: &[u8]) -> btleplug::Result<()> {
+        self.ensure_connected()?;
+        let char = self.find_descriptor(descriptor)?;
+
+        if descriptor.uuid == CLIENT_CONFIGURATION {
+            return if data.first().is_some_and(|bits| bits & 0b11 != 0) {
+                self.subscribe(&char.characteristic).await
+            } else {
+                self.unsubscribe(&char.characteristic).await
+            };
+        }
+
+        self.state
+            .lock()
+            .unwrap()
+            .written_descriptors
+            .insert((char.characteristic.uuid, descriptor.uuid), data.to_vec());
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BleAdapter for SimulatedAdapter {
+    async fn adapter_info(&self) -> btleplug::Result<String> {
+        Ok(self.name.clone())
+    }
+
+    async fn start_scan(&self, _filter: ScanFilter) -> btleplug::Result<()> {
+        self.scanning.store(true, Ordering::SeqCst);
+
+        let peripherals = self.peripherals.clone();
+        let events = self.events.clone();
+        let advertiser = tokio::spawn(async move {
+            for peripheral in &peripherals {
+                let _ = events.send(AdapterEvent::DeviceDiscovered(peripheral.peripheral_id()));
+            }
+
+            loop {
+                tokio::time::sleep(ADVERTISING_INTERVAL).await;
+                for peripheral in &peripherals {
+                    peripheral.advertise();
+                }
+            }
+        });
+
+        if let Some(previous) = self.advertiser.lock().unwrap().replace(advertiser) {
+            previous.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn stop_scan(&self) -> btleplug::Result<()> {
+        self.scanning.store(false, Ordering::SeqCst);
+
+        if let Some(advertiser) = self.advertiser.lock().unwrap().take() {
+            advertiser.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn peripherals(&self) -> btleplug::Result<Vec<Arc<dyn BlePeripheral>>> {
+        if !self.scanning.load(Ordering::SeqCst) {
+            return Ok(vec![]);
+        }
+
+        Ok(self
+            .peripherals
+            .iter()
+            .map(|peripheral| Arc::new(Arc::clone(peripheral)) as Arc<dyn BlePeripheral>)
+            .collect())
+    }
+
+    async fn peripheral(&self, id: &PeripheralId) -> btleplug::Result<Arc<dyn BlePeripheral>> {
+        self.peripherals
+            .iter()
+            .find(|peripheral| &peripheral.peripheral_id() == id)
+            .map(|peripheral| Arc::new(Arc::clone(peripheral)) as Arc<dyn BlePeripheral>)
+            .ok_or(btleplug::Error::DeviceNotFound)
+    }
+
+    async fn events(&self) -> btleplug::Result<BoxStream<AdapterEvent>> {
+        Ok(broadcast_stream(self.events.subscribe()))
+    }
+}
+
+#[async_trait]
+impl BleManager for SimulatedManager {
+    async fn adapters(&self) -> btleplug::Result<Vec<Arc<dyn BleAdapter>>> {
+        Ok(self
+            .adapters
+            .iter()
+            .map(|adapter| Arc::clone(adapter) as Arc<dyn BleAdapter>)
+            .collect())
+    }
+}
+
+impl FromStr for SimulatedManager {
+    type Err = Error;
+
+    fn from_str(content: &str) -> Result<Self> {
+        let file: SimulationFile = toml::from_str(content)
+            .map_err(|e| Error::client(format!("Failed to parse simulation file: {e}")))?;
+
+        let mut adapter_names = file.adapters;
+        if adapter_names.is_empty() {
+            adapter_names.push(
+                file.adapter
+                    .unwrap_or_else(|| "Simulated adapter".to_owned()),
+            );
+        }
+
+        let adapters = adapter_names
+            .into_iter()
+            .enumerate()
+            .map(|(adapter_index, name)| {
+                let events = broadcast::channel(256).0;
+
+                Ok(Arc::new(SimulatedAdapter {
+                    name,
+                    scanning: AtomicBool::new(false),
+                    peripherals: file
+                        .devices
+                        .iter()
+                        .map(|device| {
+                            SimulatedPeripheral::from_config(device, adapter_index, events.clone())
+                                .map(Arc::new)
+                        })
+                        .collect::<Result<_>>()?,
+                    events,
+                    advertiser: Mutex::new(None),
+                }))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { adapters })
+    }
+}
+
+impl SimulatedManager {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            Error::client(format!(
+                "Failed to read simulation file {}: {e}",
+                path.display()
+            ))
+        })?;
+
+        content.parse()
+    }
+}
+
+#[cfg(test)]
+pub(crate) const TEST_SIMULATION: &str = r#"
+[[device]]
+name = "Thermometer"
+address = "AA:BB:CC:DD:EE:01"
+rssi = -60
+services = ["181a"]
+failed_connections = 1
+tx_power = -4
+manufacturer_data = { "0x0059" = "0102" }
+service_data = { "181a" = "2c09" }
+
+[[device.characteristic]]
+service = "181a"
+uuid = "2a6e"
+properties = ["read", "notify"]
+values = ["0a00", "0b00"]
+notify_interval_ms = 10
+
+[[device.characteristic]]
+service = "181a"
+uuid = "ffe1"
+properties = ["read", "write"]
+values = ["00"]
+
+[[device.characteristic.descriptor]]
+uuid = "2901"
+value = "4d6f6465"
+"#;
+
+/// Context of a test running the simulation, the args go after the binary name
+#[cfg(test)]
+pub(crate) fn simulated_ctx(simulation: &str, args: &[&str]) -> crate::Ctx {
+    use clap::Parser;
+
+    let manager: SimulatedManager = simulation.parse().unwrap();
+    let args =
+        crate::cli_args::Args::parse_from(["blendr", "--scan-interval", "10"].iter().chain(args));
+
+    crate::Ctx::new(args, Arc::new(manager))
+}
+
+/// Scans until the given amount of devices is in the latest scan
+#[cfg(test)]
+pub(crate) async fn scan_once(
+    ctx: &Arc<crate::Ctx>,
+    devices: usize,
+) -> Vec<crate::bluetooth::HandledPeripheral> {
+    let scanner = tokio::spawn(crate::bluetooth::start_scan(Arc::clone(ctx)));
+    let peripherals = wait_for(|| {
+        ctx.latest_scan
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|scan| scan.peripherals.clone())
+            .filter(|peripherals| peripherals.len() == devices)
+    })
+    .await;
+    scanner.abort();
+
+    peripherals
+}
+
+/// Polls the condition until it holds, the test fails if it does not within a few seconds
+#[cfg(test)]
+pub(crate) async fn wait_for<T>(mut condition: impl FnMut() -> Option<T>) -> T {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(value) = condition() {
+                return value;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("Condition did not hold in time")
+}
+
+#[tokio::test]
+async fn simulates_peripheral_lifecycle() {
+    use futures::StreamExt;
+
+    let manager: SimulatedManager = TEST_SIMULATION.parse().unwrap();
+    let adapter = manager.adapters().await.unwrap().remove(0);
+
+    assert!(adapter.peripherals().await.unwrap().is_empty());
+    adapter.start_scan(ScanFilter::default()).await.unwrap();
+
+    let peripheral = adapter.peripherals().await.unwrap().remove(0);
+    let properties = peripheral.properties().await.unwrap().unwrap();
+    assert_eq!(properties.local_name.as_deref(), Some("Thermometer"));
+    assert_eq!(properties.manufacturer_data[&0x0059], vec![0x01, 0x02]);
+    assert_eq!(properties.tx_power_level, Some(-4));
+
+    assert!(peripheral.connect().await.is_err());
+    peripheral.connect().await.unwrap();
+    peripheral.discover_services().await.unwrap();
+
+    let characteristics = peripheral.characteristics();
+    let temperature = characteristics
+        .iter()
+        .find(|char| char.uuid == parse_ble_uuid("2a6e").unwrap())
+        .unwrap();
+    let writable = characteristics
+        .iter()
+        .find(|char| char.uuid == parse_ble_uuid("ffe1").unwrap())
+        .unwrap();
+
+    assert_eq!(peripheral.read(temperature).await.unwrap(), vec![0x0a, 0]);
+    assert_eq!(peripheral.read(temperature).await.unwrap(), vec![0x0b, 0]);
+
+    peripheral
+        .write(writable, &[1, 2], WriteType::WithResponse)
+        .await
+        .unwrap();
+    assert_eq!(peripheral.read(writable).await.unwrap(), vec![1, 2]);
+    assert!(peripheral
+        .write(writable, &[1], WriteType::WithoutResponse)
+        .await
+        .is_err());
+
+    let mut notifications = peripheral.notifications().await.unwrap();
+    peripheral.subscribe(temperature).await.unwrap();
+    let notification = notifications.next().await.unwrap();
+    assert_eq!(notification.uuid, temperature.uuid);
+
+    peripheral.disconnect().await.unwrap();
+    assert!(peripheral.read(temperature).await.is_err());
+}
+
+#[test]
+fn parses_example_simulation_file() {
+    include_str!("../../simulate_example.toml")
+        .parse::<SimulatedManager>()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn simulates_multiple_adapters() {
+    let manager: SimulatedManager = format!("adapters = [\"hci0\", \"hci1\"]\n{TEST_SIMULATION}")
+        .parse()
+        .unwrap();
+    let adapters = manager.adapters().await.unwrap();
+    assert_eq!(adapters.len(), 2);
+
+    let picked = crate::bluetooth::pick_adapters(&adapters, &[1])
+        .await
+        .unwrap();
+    let (_, adapter) = &picked[0];
+    assert_eq!(adapter.adapter_info().await.unwrap(), "hci1");
+    adapter.start_scan(ScanFilter::default()).await.unwrap();
+
+    let peripheral = adapter.peripherals().await.unwrap().remove(0);
+    assert_eq!(peripheral.id().to_string(), "AA:BB:CC:DD:EE:01@1");
+    // -60 dBm of the device weakened by 10 dBm on the second adapter with up to 3 dBm of jitter
+    let rssi = peripheral
+        .properties()
+        .await
+        .unwrap()
+        .unwrap()
+        .rssi
+        .unwrap();
+    assert!((-73..=-67).contains(&rssi));
+
+    let error = crate::bluetooth::pick_adapters(&adapters, &[0, 2])
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("1: hci1"));
+}
+
+#[tokio::test]
+async fn emits_adapter_events() {
+    use futures::StreamExt;
+
+    let manager: SimulatedManager = TEST_SIMULATION.parse().unwrap();
+    let adapter = manager.adapters().await.unwrap().remove(0);
+    let mut events = adapter.events().await.unwrap();
+    adapter.start_scan(ScanFilter::default()).await.unwrap();
+
+    let id = match events.next().await.unwrap() {
+        AdapterEvent::DeviceDiscovered(id) => id,
+        event => panic!("Unexpected first event {event:?}"),
+    };
+    assert_eq!(id.to_string(), "AA:BB:CC:DD:EE:01");
+    assert!(matches!(
+        events.next().await.unwrap(),
+        AdapterEvent::DeviceUpdated(_)
+    ));
+    assert!(matches!(
+        events.next().await.unwrap(),
+        AdapterEvent::ManufacturerDataAdvertisement { manufacturer_data, .. }
+            if manufacturer_data[&0x0059] == [0x01, 0x02]
+    ));
+
+    let peripheral = adapter.peripheral(&id).await.unwrap();
+    while peripheral.connect().await.is_err() {}
+    peripheral.disconnect().await.unwrap();
+
+    loop {
+        if let AdapterEvent::DeviceDisconnected(disconnected) = events.next().await.unwrap() {
+            assert_eq!(disconnected, id);
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn simulates_descriptors() {
+    use crate::bluetooth::gatt_decoders::{CLIENT_CONFIGURATION, USER_DESCRIPTION};
+
+    let manager: SimulatedManager = TEST_SIMULATION.parse().unwrap();
+    let adapter = manager.adapters().await.unwrap().remove(0);
+    adapter.start_scan(ScanFilter::default()).await.unwrap();
+    let peripheral = adapter.peripherals().await.unwrap().remove(0);
+    while peripheral.connect().await.is_err() {}
+    peripheral.discover_services().await.unwrap();
+
+    let descriptors = peripheral
+        .characteristics()
+        .into_iter()
+        .flat_map(|char| char.descriptors)
+        .collect::<Vec<_>>();
+    let find = |uuid| {
+        descriptors
+            .iter()
+            .find(|descriptor| descriptor.uuid == uuid)
+            .unwrap()
+    };
+
+    let description = find(USER_DESCRIPTION);
+    assert_eq!(
+        peripheral.read_descriptor(description).await.unwrap(),
+        b"Mode"
+    );
+
+    // notifying characteristic gets the configuration descriptor on its own
+    let configuration = find(CLIENT_CONFIGURATION);
+    assert_eq!(
+        peripheral.read_descriptor(configuration).await.unwrap(),
+        [0, 0]
+    );
+    peripheral
+        .write_descriptor(configuration, &[1, 0])
+        .await
+        .unwrap();
+    assert_eq!(
+        peripheral.read_descriptor(configuration).await.unwrap(),
+        [1, 0]
+    );
+    peripheral
+        .write_descriptor(configuration, &[0, 0])
+        .await
+        .unwrap();
+    assert_eq!(
+        peripheral.read_descriptor(configuration).await.unwrap(),
+        [0, 0]
+    );
+}
+
+#[tokio::test]
+async fn simulates_gatt_table() {
+    let manager: SimulatedManager = format!(
+        "{TEST_SIMULATION}
+[[device.service]]
+uuid = \"1801\"
+
+[[device.service]]
+uuid = \"181a\"
+secondary = true
+"
+    )
+    .parse()
+    .unwrap();
+    let adapter = manager.adapters().await.unwrap().remove(0);
+    adapter.start_scan(ScanFilter::default()).await.unwrap();
+    let peripheral = adapter.peripherals().await.unwrap().remove(0);
+    while peripheral.connect().await.is_err() {}
+    assert!(peripheral.attribute_handles().is_empty());
+    peripheral.discover_services().await.unwrap();
+
+    let services = peripheral.services();
+    let environment = parse_ble_uuid("181a").unwrap();
+    let generic_attribute = parse_ble_uuid("1801").unwrap();
+    assert!(services
+        .iter()
+        .any(|service| service.uuid == environment && !service.primary));
+    assert!(services
+        .iter()
+        .any(|service| service.uuid == generic_attribute
+            && service.primary
+            && service.characteristics.is_empty()));
+
+    // declared services go first, then the declaration, value and descriptors of every characteristic
+    let handles = peripheral.attribute_handles();
+    assert_eq!(handles[&GattAttribute::Service(generic_attribute)], 1);
+    assert_eq!(handles[&GattAttribute::Service(environment)], 2);
+    assert_eq!(
+        handles[&GattAttribute::Characteristic {
+            service_uuid: environment,
+            uuid: parse_ble_uuid("2a6e").unwrap(),
+        }],
+        4
+    );
+    assert_eq!(
+        handles[&GattAttribute::Characteristic {
+            service_uuid: environment,
+            uuid: parse_ble_uuid("ffe1").unwrap(),
+        }],
+        7
+    );
+}
@@ -14,22 +14,18 @@ fn parse_name_map(path: &str) -> Result<HashMap<uuid::Uuid, String>, clap::Error
     let content = std::fs::read_to_string(path)?;
 
     content
-        .trim()
-        .split('\n')
+        .lines()
         .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
         .map(|(i, line)| -> clap::error::Result<_> {
-            let [uuid, name]: [&str; 2] =
-                line.split('=')
-                    .collect::<Vec<_>>()
-                    .try_into()
-                    .map_err(|_| {
-                        clap::Error::raw(
-                            clap::error::ErrorKind::InvalidValue,
-                            format!("Failed to parse line {i} from file {}: Missing desired structure UUID=NAME.\nNames map file supports very simple key-value pairs format where first value is uuid of service or characteristic and the second is the name.\n\ne.g. 0000FFE0-0000-1000-8000-00805F9B34FB=Cpu Tempreture", path.display()),
-                        )
-                    })?;
-
-            let uuid = uuid::Uuid::parse_str(uuid).map_err(|e| {
+            let (uuid, name) = line.split_once('=').ok_or_else(|| {
+                clap::Error::raw(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!("Failed to parse line {i} from file {}: Missing desired structure UUID=NAME.\nNames map file supports very simple key-value pairs format where first value is uuid of service or characteristic and the second is the name.\n\ne.g. 0000FFE0-0000-1000-8000-00805F9B34FB=Cpu Tempreture", path.display()),
+                )
+            })?;
+
+            let uuid = uuid::Uuid::parse_str(uuid.trim()).map_err(|e| {
                 clap::Error::raw(
                     clap::error::ErrorKind::InvalidValue,
                     format!(
@@ -46,8 +42,11 @@ fn parse_name_map(path: &str) -> Result<HashMap<uuid::Uuid, String>, clap::Error
 #[test]
 fn test_parse_name_map() {
     let test_path = std::path::Path::new("test.ini");
-    std::fs::write(test_path, "0000FFE0-0000-1000-8000-00805F9B34FB=test data")
-        .expect("Unable to write file");
+    std::fs::write(
+        test_path,
+        "0000FFE0-0000-1000-8000-00805F9B34FB=test data\r\n\r\n  \n4f25b5f6-01d9-4d95-86a4-81e3d2f13b8f = spaced name \r\n",
+    )
+    .expect("Unable to write file");
 
     assert_eq!(
         parse_name_map(
@@ -56,10 +55,16 @@ fn test_parse_name_map() {
                 .expect("Unable to locate path of test .ini file")
         )
         .unwrap(),
-        HashMap::from([(
-            uuid::Uuid::from_u128(0x0000FFE0_0000_1000_8000_00805F9B34FB),
-            "test data".to_string()
-        )])
+        HashMap::from([
+            (
+                uuid::Uuid::from_u128(0x0000FFE0_0000_1000_8000_00805F9B34FB),
+                "test data".to_string()
+            ),
+            (
+                uuid::Uuid::from_u128(0x4f25b5f6_01d9_4d95_86a4_81e3d2f13b8f),
+                "spaced name".to_string()
+            )
+        ])
     );
 
     std::fs::remove_file(test_path).expect("Unable to delete file");
@@ -68,6 +73,8 @@ fn test_parse_name_map() {
 #[derive(Default, PartialEq, Eq, Debug, Clone, Copy, clap::ValueEnum)]
 pub enum GeneralSort {
     Name,
+    /// Pinned devices and characteristics first, then by name.
+    Pinned,
     #[default]
     /// The default sort based on the trait implementer.
     DefaultSort,
@@ -75,7 +82,7 @@ pub enum GeneralSort {
 
 impl GeneralSort {
     pub fn sort<T: GeneralSortable>(&self, data: &mut [T]) {
-        let should_sort = T::AVAILABLE_SORTS.contains(&self);
+        let should_sort = T::AVAILABLE_SORTS.contains(self);
 
         if should_sort {
             data.sort_by(|a, b| a.cmp(self, a, b));
@@ -88,6 +95,45 @@ pub trait GeneralSortable {
     fn cmp(&self, sort: &GeneralSort, a: &Self, b: &Self) -> std::cmp::Ordering;
 }
 
+/// Company identifier either decimal or hex with 0x prefix e.g. 0x004C
+pub(crate) fn parse_company_id(value: &str) -> Result<u16, String> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|e| format!("Invalid company identifier \"{value}\": {e}"))
+}
+
+/// Restricts the scanned peripherals, can be toggled in the peripheral list with "f" key
+#[derive(Debug, Clone, Default, clap::Args)]
+#[command(next_help_heading = "Scan filter")]
+pub struct ScanFilterArgs {
+    /// Scan only for peripherals advertising this service, either full 128bit or 16bit form of the standard uuid e.g. 180d.
+    /// Can be repeated to scan for any of the services.
+    #[clap(long = "scan-service", value_name = "UUID")]
+    pub services: Vec<String>,
+
+    /// Hide peripherals with weaker signal than this RSSI in dBm, e.g. --min-rssi=-70
+    #[clap(long, allow_negative_numbers = true)]
+    pub min_rssi: Option<i16>,
+
+    /// Show only peripherals whose address starts with this prefix, e.g. AA:BB
+    #[clap(long)]
+    pub address_prefix: Option<String>,
+
+    /// Show only peripherals advertising manufacturer data of this company identifier, e.g. 0x004C
+    #[clap(long, value_parser = parse_company_id)]
+    pub manufacturer_id: Option<u16>,
+
+    /// Treat advertisements that differ only in RSSI as duplicates: they are not counted and do not update the signal history.
+    /// The bluetooth backend does not expose the controller duplicate filter so it is applied by blendr.
+    #[clap(long)]
+    pub filter_duplicates: bool,
+}
+
 #[derive(Default, PartialEq, Eq, Debug, Clone, Copy, clap::ValueEnum)]
 pub enum LogLevel {
     Debug,
@@ -95,6 +141,73 @@ pub enum LogLevel {
     Error,
 }
 
+/// Device and characteristic to interact with from the non-interactive commands
+#[derive(Debug, Clone, clap::Args)]
+pub struct CharacteristicTarget {
+    /// Regex matched against device name or address. Must match exactly one device.
+    #[clap(long)]
+    pub device: String,
+
+    /// Characteristic uuid, either full 128bit or 16bit form of the standard uuid e.g. 2a19.
+    #[clap(long = "char")]
+    pub characteristic: String,
+
+    /// Seconds to wait for the device to show up in the scan.
+    #[clap(long, default_value_t = 10)]
+    pub timeout: u64,
+
+    /// Print values as JSON lines instead of plain hex.
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Command {
+    /// Scan for peripherals and print them to stdout.
+    Scan {
+        /// Seconds to scan before printing the results.
+        #[clap(long, default_value_t = 5)]
+        duration: u64,
+
+        /// Print the scan as JSON array instead of tab separated lines.
+        #[clap(long)]
+        json: bool,
+    },
+    /// Read the characteristic value once and print it.
+    Read {
+        #[command(flatten)]
+        target: CharacteristicTarget,
+    },
+    /// Write the value to the characteristic.
+    #[clap(group(clap::ArgGroup::new("value").required(true).args(["hex", "text"])))]
+    Write {
+        #[command(flatten)]
+        target: CharacteristicTarget,
+
+        /// Value to write as hex bytes e.g. 0102ff.
+        #[clap(long)]
+        hex: Option<String>,
+
+        /// Value to write as UTF-8 text.
+        #[clap(long)]
+        text: Option<String>,
+    },
+    /// Subscribe to characteristic notifications and print every received value.
+    Subscribe {
+        #[command(flatten)]
+        target: CharacteristicTarget,
+
+        /// Exit after receiving this amount of values. Runs until interrupted if not specified.
+        #[clap(long)]
+        count: Option<usize>,
+    },
+    /// Open the terminal UI driven by the session recorded with --record-session instead of the bluetooth adapter.
+    Replay {
+        /// Session file written by --record-session.
+        file: std::path::PathBuf,
+    },
+}
+
 #[derive(Debug, Parser)]
 #[command(
     version=env!("CARGO_PKG_VERSION"),
@@ -103,17 +216,25 @@ pub enum LogLevel {
     long_about="Blendr is a BLE browser terminal library. It allows to search for BLE peripherals, establish connections, interact with their services and characteristics, and read and write data right from your terminal."
 )]
 pub struct Args {
+    /// Run a non-interactive command instead of the terminal UI.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[clap(long, short)]
     /// Bluetooth adapter hardware index to use, if many available.
     /// If not specified, the first discovered adapter will be used.
-    #[clap(default_value_t = 0)]
-    pub adapter_index: usize,
+    /// Repeat to scan on several adapters at once, e.g. -a 0 -a 1. Adapters can be switched in the UI with "a" key.
+    #[clap(default_values_t = [0])]
+    pub adapter_index: Vec<usize>,
 
     #[clap(long, short = 'i')]
-    /// Scan interval in milliseconds.
+    /// Interval in milliseconds between the device list refreshes and the characteristic polls.
     #[clap(default_value_t = 1000)]
     pub scan_interval: u64,
 
+    #[command(flatten)]
+    pub scan_filter: ScanFilterArgs,
+
     #[clap(long, short)]
     #[clap(default_value_t = String::from("(?i)"))]
     /// Regex flags that by default applier to the filter queries.
@@ -137,14 +258,58 @@ pub struct Args {
     /// 0000FFE0-0000-1000-8000-00805F9B34FB=Cpu Tempreture
     /// 4f25b5f6-01d9-4d95-86a4-81e3d2f13b8f=My Custom Service data
     /// ```
+    ///
+    /// Takes precedence over the names from ~/.config/blendr/config.toml, which can tell services from characteristics.
     #[clap(long, value_parser = clap::builder::ValueParser::new(parse_name_map))]
     pub names_map_file: Option<HashMap<uuid::Uuid, String>>,
 
+    /// TOML file describing payload layouts of custom characteristics: field names, types, endianness, scale, offset and units.
+    /// Decoded fields are displayed as a table in the characteristic view, see decoders_example.toml.
+    #[clap(long, value_name = "FILE")]
+    pub decoders: Option<std::path::PathBuf>,
+
+    /// Amount of the latest numeric values plotted on the characteristic chart. Can be changed with +/- keys.
+    #[clap(long, default_value_t = 100)]
+    pub chart_window: usize,
+
+    /// Amount of the latest RSSI readings kept for every peripheral across scans for the signal graphs.
+    #[clap(long, default_value_t = 300)]
+    pub rssi_history: usize,
+
+    /// Amount of the latest values kept for every opened characteristic, older values are dropped.
+    /// Opened characteristics keep being polled or notified in the background for as long as their peripheral stays connected.
+    #[clap(long, default_value_t = 10000)]
+    pub history_limit: usize,
+
+    /// Seconds without a new advertisement after which the peripheral is displayed as stale. Stale peripherals can be dropped with "d" key.
+    #[clap(long, default_value_t = 30)]
+    pub stale_after: u64,
+
+    /// Connection attempts before giving up on connecting or reconnecting to a device, 0 retries forever.
+    /// Attempts are spaced with exponential backoff from half a second up to 30 seconds.
+    #[clap(long, default_value_t = 10)]
+    pub connect_attempts: u32,
+
     /// Default sort type for all the views and lists.
     #[clap(long)]
     #[arg(value_enum)]
     pub sort: Option<GeneralSort>,
 
+    /// Run against simulated devices described in the TOML file instead of the real bluetooth adapter.
+    /// Useful for demos and environments without bluetooth hardware, see simulate_example.toml.
+    #[clap(long, value_name = "FILE")]
+    pub simulate: Option<std::path::PathBuf>,
+
+    /// Record every characteristic value received or written during the session into the file.
+    /// Format is picked by the file extension: .csv, .jsonl (JSON Lines) or .bin (compact binary log).
+    #[clap(long, value_name = "FILE")]
+    pub record: Option<std::path::PathBuf>,
+
+    /// Record the whole session into the file: every scan, connection attempt, discovered services,
+    /// reads, writes and notifications. The file can be opened later with `blendr replay <FILE>`.
+    #[clap(long, value_name = "FILE")]
+    pub record_session: Option<std::path::PathBuf>,
+
     /// Log level for the CLI.
     /// Logs are located at the $TMPDIR/blendr/*-cli.log and rotated daily.
     #[clap(long)]
@@ -0,0 +1,396 @@
+use crate::{
+    bluetooth::{
+        self,
+        ble_default_services::parse_ble_uuid,
+        connections,
+        payload::{parse_hex, to_hex},
+        ConnectedCharacteristic, ConnectedPeripheral, HandledPeripheral,
+    },
+    cli_args::{CharacteristicTarget, Command},
+    error::{Error, Result},
+    export,
+    route::{CharacteristicValue, ValueDirection},
+    session::{self, ValueSource},
+    tui::ui::search_input::build_search_regex,
+    Ctx,
+};
+use futures::StreamExt;
+use std::{
+    io::Write,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time::{sleep, timeout};
+
+const SCAN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, serde::Serialize)]
+struct ScannedPeripheral<'a> {
+    name: Option<&'a str>,
+    address: String,
+    rssi: Option<i16>,
+    services: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ValueRecord<'a> {
+    time: String,
+    device: &'a str,
+    address: String,
+    service: String,
+    characteristic: String,
+    hex: String,
+}
+
+/// Prints the received value and appends it to the session recording
+fn print_value(
+    out: &mut impl Write,
+    ctx: &Ctx,
+    target: &CharacteristicTarget,
+    char: &ConnectedCharacteristic,
+    peripheral: &HandledPeripheral,
+    source: ValueSource,
+    data: &[u8],
+) -> Result<()> {
+    session::record_value(ctx, peripheral, char, source, data);
+    export::record_value(
+        ctx,
+        peripheral.address,
+        char,
+        &CharacteristicValue {
+            time: chrono::Local::now(),
+            data: data.to_vec(),
+            direction: ValueDirection::Incoming,
+        },
+    );
+
+    if !target.json {
+        writeln!(out, "{}", to_hex(data))?;
+        return Ok(());
+    }
+
+    let record = ValueRecord {
+        time: chrono::Local::now().to_rfc3339(),
+        device: &peripheral.name,
+        address: peripheral.address.to_string(),
+        service: char.service_uuid.to_string(),
+        characteristic: char.uuid.to_string(),
+        hex: to_hex(data),
+    };
+
+    writeln!(
+        out,
+        "{}",
+        serde_json::to_string(&record).map_err(|e| Error::client(e.to_string()))?
+    )?;
+
+    Ok(())
+}
+
+async fn print_scan(out: &mut impl Write, ctx: &Ctx, duration: u64, json: bool) -> Result<()> {
+    sleep(Duration::from_secs(duration)).await;
+
+    let scan = ctx.latest_scan.read()?;
+    let peripherals = scan
+        .as_ref()
+        .map(|scan| scan.peripherals.as_slice())
+        .unwrap_or_default();
+
+    if json {
+        let output = peripherals
+            .iter()
+            .map(|peripheral| ScannedPeripheral {
+                name: (!peripheral.name_unset).then_some(peripheral.name.as_str()),
+                address: peripheral.address.to_string(),
+                rssi: peripheral.rssi,
+                services: peripheral
+                    .services
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+
+        writeln!(
+            out,
+            "{}",
+            serde_json::to_string_pretty(&output).map_err(|e| Error::client(e.to_string()))?
+        )?;
+    } else {
+        for peripheral in peripherals {
+            writeln!(
+                out,
+                "{}\t{}\t{}",
+                peripheral.address,
+                peripheral
+                    .rssi
+                    .map(|rssi| rssi.to_string())
+                    .unwrap_or_default(),
+                peripheral.name
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Waits until exactly one peripheral from the scan matches the device regex
+async fn find_peripheral(ctx: &Ctx, target: &CharacteristicTarget) -> Result<HandledPeripheral> {
+    let regex = build_search_regex(&target.device, ctx)
+        .map_err(|e| Error::client(format!("Invalid device regex: {e}")))?;
+    let deadline = Instant::now() + Duration::from_secs(target.timeout);
+
+    loop {
+        {
+            let scan = ctx.latest_scan.read()?;
+            let matches = scan
+                .as_ref()
+                .map(|scan| scan.peripherals.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .filter(|peripheral| {
+                    regex.is_match(&peripheral.name)
+                        || regex.is_match(&peripheral.address.to_string())
+                })
+                .collect::<Vec<_>>();
+
+            match matches.as_slice() {
+                [peripheral] => return Ok((*peripheral).clone()),
+                [] => (),
+                matches => {
+                    return Err(Error::client(format!(
+                        "Device filter \"{}\" is ambiguous, it matches: {}",
+                        target.device,
+                        matches
+                            .iter()
+                            .map(|peripheral| format!(
+                                "{} ({})",
+                                peripheral.name, peripheral.address
+                            ))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )))
+                }
+            }
+        }
+
+        if Instant::now() >= deadline {
+            return Err(Error::client(format!(
+                "No device matching \"{}\" found in {} seconds",
+                target.device, target.timeout
+            )));
+        }
+
+        sleep(SCAN_POLL_INTERVAL).await;
+    }
+}
+
+async fn connect(
+    ctx: &Ctx,
+    target: &CharacteristicTarget,
+) -> Result<(ConnectedPeripheral, ConnectedCharacteristic)> {
+    let char_uuid = parse_ble_uuid(&target.characteristic)
+        .map_err(|e| Error::client(format!("Invalid characteristic uuid: {e}")))?;
+    let peripheral = find_peripheral(ctx, target).await?;
+
+    connections::connect_with_backoff(ctx, &peripheral, |_| ()).await?;
+
+    let connection = ConnectedPeripheral::new(ctx, peripheral);
+    let characteristic = connection
+        .characteristics
+        .iter()
+        .find(|char| char.uuid == char_uuid)
+        .cloned()
+        .ok_or_else(|| {
+            Error::client(format!(
+                "Characteristic {char_uuid} not found on {}",
+                connection.peripheral.name
+            ))
+        })?;
+
+    Ok((connection, characteristic))
+}
+
+async fn interact(
+    out: &mut impl Write,
+    ctx: &Ctx,
+    command: &Command,
+    target: &CharacteristicTarget,
+    connection: &ConnectedPeripheral,
+    char: &ConnectedCharacteristic,
+) -> Result<()> {
+    let ble_peripheral = connection.peripheral.ble_peripheral.as_ref();
+
+    match command {
+        Command::Read { .. } => {
+            let data = timeout(
+                bluetooth::TIMEOUT,
+                ble_peripheral.read(&char.ble_characteristic),
+            )
+            .await??;
+
+            print_value(
+                out,
+                ctx,
+                target,
+                char,
+                &connection.peripheral,
+                ValueSource::Read,
+                &data,
+            )
+        }
+        Command::Write { hex, text, .. } => {
+            let data = match (hex, text) {
+                (Some(hex), _) => parse_hex(hex)?,
+                (None, Some(text)) => text.as_bytes().to_vec(),
+                (None, None) => return Err(Error::client("Nothing to write")),
+            };
+
+            bluetooth::write_with_timeout(ble_peripheral, &char.ble_characteristic, &data).await?;
+            session::record_value(ctx, &connection.peripheral, char, ValueSource::Write, &data);
+            export::record_value(
+                ctx,
+                connection.peripheral.address,
+                char,
+                &CharacteristicValue {
+                    time: chrono::Local::now(),
+                    data,
+                    direction: ValueDirection::Outgoing,
+                },
+            );
+
+            Ok(())
+        }
+        Command::Subscribe { count, .. } => {
+            let mut notifications = ble_peripheral.notifications().await?;
+            ble_peripheral.subscribe(&char.ble_characteristic).await?;
+
+            let mut received = 0;
+            while count.is_none_or(|count| received < count) {
+                let Some(notification) = notifications.next().await else {
+                    break;
+                };
+
+                if notification.uuid == char.uuid {
+                    print_value(
+                        out,
+                        ctx,
+                        target,
+                        char,
+                        &connection.peripheral,
+                        ValueSource::Notification,
+                        &notification.value,
+                    )?;
+                    received += 1;
+                }
+            }
+
+            ble_peripheral.unsubscribe(&char.ble_characteristic).await?;
+            Ok(())
+        }
+        Command::Scan { .. } | Command::Replay { .. } => Ok(()),
+    }
+}
+
+async fn run_command(out: &mut impl Write, ctx: &Ctx, command: Command) -> Result<()> {
+    let target = match &command {
+        Command::Scan { duration, json } => return print_scan(out, ctx, *duration, *json).await,
+        Command::Read { target }
+        | Command::Write { target, .. }
+        | Command::Subscribe { target, .. } => target,
+        Command::Replay { .. } => return Err(Error::client("Replay runs only in the terminal UI")),
+    };
+
+    let (connection, char) = connect(ctx, target).await?;
+    let result = interact(out, ctx, &command, target, &connection, &char).await;
+
+    bluetooth::disconnect_with_timeout(connection.peripheral.ble_peripheral.as_ref()).await;
+    result
+}
+
+/// Runs the non-interactive command reusing the same scanner as the terminal UI, the output goes
+/// to `out`
+pub async fn run(ctx: Arc<Ctx>, command: Command, out: &mut impl Write) -> Result<()> {
+    let mut scanner = tokio::spawn(bluetooth::start_scan(Arc::clone(&ctx)));
+
+    let result = tokio::select! {
+        scan_result = &mut scanner => match scan_result {
+            Ok(Err(e)) => Err(e),
+            _ => Err(Error::client("Scanner stopped unexpectedly")),
+        },
+        result = run_command(out, &ctx, command) => result,
+    };
+
+    scanner.abort();
+    result
+}
+
+#[tokio::test]
+async fn runs_commands_against_simulated_device() {
+    use crate::bluetooth::simulator::{simulated_ctx, TEST_SIMULATION};
+
+    let run_simulation = |simulation: &str, args: &[&str]| {
+        let ctx = simulated_ctx(simulation, args);
+        let command = ctx.args.command.clone().unwrap();
+
+        async move {
+            let mut out = vec![];
+            run(Arc::new(ctx), command, &mut out)
+                .await
+                .map(|_| String::from_utf8(out).unwrap())
+        }
+    };
+    let run_args = |args: &[&str]| run_simulation(TEST_SIMULATION, args);
+
+    // the simulated RSSI jitters, a device without it prints the same line on every run
+    let without_rssi = "[[device]]\nname = \"Thermometer\"\naddress = \"AA:BB:CC:DD:EE:01\"";
+    assert_eq!(
+        run_simulation(without_rssi, &["scan", "--duration", "1"])
+            .await
+            .unwrap(),
+        "AA:BB:CC:DD:EE:01\t\tThermometer\n"
+    );
+    assert_eq!(
+        run_args(&["read", "--device", "thermo", "--char", "2a6e"])
+            .await
+            .unwrap(),
+        "0a00\n"
+    );
+    assert_eq!(
+        run_args(&["write", "--device", "thermo", "--char", "ffe1", "--hex", "0102",])
+            .await
+            .unwrap(),
+        ""
+    );
+    assert_eq!(
+        run_args(&[
+            "subscribe",
+            "--device",
+            "thermo",
+            "--char",
+            "2a6e",
+            "--count",
+            "2",
+        ])
+        .await
+        .unwrap(),
+        "0a00\n0b00\n"
+    );
+
+    assert!(
+        run_args(&["write", "--device", "thermo", "--char", "2a6e", "--hex", "01"])
+            .await
+            .is_err()
+    );
+    assert!(run_args(&[
+        "read",
+        "--device",
+        "missing",
+        "--char",
+        "2a6e",
+        "--timeout",
+        "0"
+    ])
+    .await
+    .is_err());
+}
@@ -0,0 +1,326 @@
+use crate::{
+    bluetooth::ble_default_services::parse_ble_uuid,
+    cli_args::{Args, GeneralSort},
+    error::{Error, Result},
+    tui::keymap::Action,
+};
+use btleplug::api::BDAddr;
+use clap::{parser::ValueSource, ArgMatches, ValueEnum};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use uuid::Uuid;
+
+/// Per-project overrides, the closest one to the working directory wins
+pub const PROJECT_CONFIG_FILE: &str = ".blendr.toml";
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    scan_interval: Option<u64>,
+    regex_flags: Option<String>,
+    sort: Option<String>,
+    #[serde(default)]
+    favorites: Vec<String>,
+    #[serde(default)]
+    names: NamesFile,
+    /// Names used only for the device with this name or address
+    #[serde(default)]
+    devices: HashMap<String, NamesFile>,
+    /// Replace the default keys of the actions, e.g. `down = ["n", "Down"]`
+    #[serde(default)]
+    keys: HashMap<Action, Vec<String>>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NamesFile {
+    #[serde(default)]
+    services: HashMap<String, String>,
+    #[serde(default)]
+    characteristics: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NameMaps {
+    pub services: HashMap<Uuid, String>,
+    pub characteristics: HashMap<Uuid, String>,
+}
+
+impl NameMaps {
+    fn extend(&mut self, other: NameMaps) {
+        self.services.extend(other.services);
+        self.characteristics.extend(other.characteristics);
+    }
+}
+
+impl TryFrom<NamesFile> for NameMaps {
+    type Error = Error;
+
+    fn try_from(file: NamesFile) -> Result<Self> {
+        let parse = |names: HashMap<String, String>| {
+            names
+                .into_iter()
+                .map(|(uuid, name)| {
+                    parse_ble_uuid(&uuid)
+                        .map(|uuid| (uuid, name))
+                        .map_err(|e| Error::client(format!("Invalid uuid \"{uuid}\": {e}")))
+                })
+                .collect::<Result<HashMap<_, _>>>()
+        };
+
+        Ok(Self {
+            services: parse(file.services)?,
+            characteristics: parse(file.characteristics)?,
+        })
+    }
+}
+
+/// Settings of the user config merged with the project overrides
+#[derive(Debug, Default)]
+pub struct Config {
+    pub scan_interval: Option<u64>,
+    pub regex_flags: Option<String>,
+    pub sort: Option<GeneralSort>,
+    /// Names or addresses of the favorite devices
+    pub favorites: Vec<String>,
+    pub names: NameMaps,
+    pub devices: HashMap<String, NameMaps>,
+    pub keys: HashMap<Action, Vec<String>>,
+    /// Names from --names-map-file, take precedence over everything in the config files
+    cli_names: NameMaps,
+}
+
+impl FromStr for Config {
+    type Err = Error;
+
+    fn from_str(content: &str) -> Result<Self> {
+        let file: ConfigFile = toml::from_str(content)
+            .map_err(|e| Error::client(format!("Failed to parse config file: {e}")))?;
+
+        Ok(Self {
+            scan_interval: file.scan_interval,
+            regex_flags: file.regex_flags,
+            sort: file
+                .sort
+                .map(|sort| {
+                    GeneralSort::from_str(&sort, true)
+                        .map_err(|e| Error::client(format!("Invalid sort \"{sort}\": {e}")))
+                })
+                .transpose()?,
+            favorites: file.favorites,
+            names: file.names.try_into()?,
+            devices: file
+                .devices
+                .into_iter()
+                .map(|(device, names)| Ok((device, names.try_into()?)))
+                .collect::<Result<_>>()?,
+            keys: file.keys,
+            cli_names: NameMaps::default(),
+        })
+    }
+}
+
+/// Directory of the user config, e.g. ~/.config/blendr
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .map(|dir| dir.join("blendr"))
+}
+
+/// Devices are referred by their exact name or address
+pub fn matches_device(key: &str, name: &str, address: &BDAddr) -> bool {
+    key == name || key.eq_ignore_ascii_case(&address.to_string())
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        std::fs::read_to_string(path)
+            .map_err(|e| Error::client(format!("Failed to read {}: {e}", path.display())))?
+            .parse()
+            .map_err(|e| Error::client(format!("{} ({})", e, path.display())))
+    }
+
+    /// Reads ~/.config/blendr/config.toml and the closest .blendr.toml up from the working directory,
+    /// both are optional
+    pub fn load(working_dir: &Path) -> Result<Self> {
+        let mut config = Config::default();
+
+        if let Some(path) = config_dir()
+            .map(|dir| dir.join("config.toml"))
+            .filter(|path| path.is_file())
+        {
+            config.merge(Config::from_file(&path)?);
+        }
+
+        if let Some(path) = working_dir
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+            .find(|path| path.is_file())
+        {
+            config.merge(Config::from_file(&path)?);
+        }
+
+        Ok(config)
+    }
+
+    /// Values of the other config override this one, lists and name tables are combined
+    pub fn merge(&mut self, other: Config) {
+        self.scan_interval = other.scan_interval.or(self.scan_interval);
+        self.regex_flags = other.regex_flags.or(self.regex_flags.take());
+        self.sort = other.sort.or(self.sort);
+
+        for favorite in other.favorites {
+            if !self.favorites.contains(&favorite) {
+                self.favorites.push(favorite);
+            }
+        }
+
+        self.names.extend(other.names);
+        for (device, names) in other.devices {
+            self.devices.entry(device).or_default().extend(names);
+        }
+        self.keys.extend(other.keys);
+    }
+
+    /// Fills the arguments not passed on the command line with the config values
+    pub fn apply(&mut self, args: &mut Args, matches: &ArgMatches) {
+        let from_cli = |id| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if let Some(scan_interval) = self.scan_interval.filter(|_| !from_cli("scan_interval")) {
+            args.scan_interval = scan_interval;
+        }
+
+        if let Some(regex_flags) = self
+            .regex_flags
+            .clone()
+            .filter(|_| !from_cli("regex_flags"))
+        {
+            args.regex_flags = regex_flags;
+        }
+
+        args.sort = args.sort.or(self.sort);
+
+        // the names map file does not tell services from characteristics
+        if let Some(names) = args.names_map_file.as_ref() {
+            self.cli_names = NameMaps {
+                services: names.clone(),
+                characteristics: names.clone(),
+            };
+        }
+    }
+
+    /// Name tables applicable to the device from the most to the least specific
+    fn name_maps(&self, name: &str, address: &BDAddr) -> Vec<&NameMaps> {
+        std::iter::once(&self.cli_names)
+            .chain(
+                self.devices
+                    .iter()
+                    .filter(|(key, _)| matches_device(key, name, address))
+                    .map(|(_, names)| names),
+            )
+            .chain(std::iter::once(&self.names))
+            .collect()
+    }
+
+    pub fn service_name(&self, name: &str, address: &BDAddr, uuid: &Uuid) -> Option<&str> {
+        self.name_maps(name, address)
+            .into_iter()
+            .find_map(|names| names.services.get(uuid))
+            .map(String::as_str)
+    }
+
+    pub fn characteristic_name(&self, name: &str, address: &BDAddr, uuid: &Uuid) -> Option<&str> {
+        self.name_maps(name, address)
+            .into_iter()
+            .find_map(|names| names.characteristics.get(uuid))
+            .map(String::as_str)
+    }
+
+    pub fn is_favorite(&self, name: &str, address: &BDAddr) -> bool {
+        self.favorites
+            .iter()
+            .any(|favorite| matches_device(favorite, name, address))
+    }
+}
+
+#[test]
+fn merges_config_with_args() {
+    use crate::bluetooth::ble_default_services::create_ble_uuid;
+    use clap::{CommandFactory, FromArgMatches};
+
+    let mut config: Config = r#"
+        scan_interval = 500
+        regex_flags = ""
+        favorites = ["Thermometer"]
+
+        [names.services]
+        181a = "Environment"
+
+        [names.characteristics]
+        181a = "Not a service"
+        2a6e = "Temperature"
+    "#
+    .parse()
+    .unwrap();
+
+    config.merge(
+        r#"
+        sort = "name"
+        favorites = ["aa:bb:cc:dd:ee:02"]
+
+        [devices."AA:BB:CC:DD:EE:02".characteristics]
+        2a6e = "Water temperature"
+
+        [keys]
+        down = ["n"]
+        "#
+        .parse()
+        .unwrap(),
+    );
+
+    let matches = Args::command()
+        .try_get_matches_from(["blendr", "--scan-interval", "2000"])
+        .unwrap();
+    let mut args = Args::from_arg_matches(&matches).unwrap();
+    config.apply(&mut args, &matches);
+
+    // command line wins over the config
+    assert_eq!(args.scan_interval, 2000);
+    assert_eq!(args.regex_flags, "");
+    assert_eq!(args.sort, Some(GeneralSort::Name));
+
+    let address = BDAddr::from_str("AA:BB:CC:DD:EE:02").unwrap();
+    assert!(config.is_favorite("Thermometer", &BDAddr::default()));
+    assert!(config.is_favorite("Toothbrush", &address));
+
+    let temperature = create_ble_uuid(0x2A6E);
+    let environment = create_ble_uuid(0x181A);
+    assert_eq!(
+        config.service_name("Thermometer", &BDAddr::default(), &environment),
+        Some("Environment")
+    );
+    assert_eq!(
+        config.characteristic_name("Thermometer", &BDAddr::default(), &temperature),
+        Some("Temperature")
+    );
+    assert_eq!(
+        config.characteristic_name("Toothbrush", &address, &temperature),
+        Some("Water temperature")
+    );
+
+    assert_eq!(config.keys[&Action::Down], ["n"]);
+
+    let example = include_str!("../config_example.toml")
+        .parse::<Config>()
+        .unwrap();
+    assert!(crate::tui::keymap::Keymap::new(&example.keys).is_ok());
+    assert!("colors = true".parse::<Config>().is_err());
+    assert!("[names.services]\nnope = \"x\"".parse::<Config>().is_err());
+    assert!("[keys]\nfly = [\"f\"]".parse::<Config>().is_err());
+}
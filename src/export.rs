@@ -0,0 +1,330 @@
+use crate::{
+    bluetooth::{
+        payload::{to_hex, try_parse_numeric_value},
+        ConnectedCharacteristic,
+    },
+    error::{Error, Result},
+    route::{CharacteristicValue, ValueDirection},
+    Ctx,
+};
+use btleplug::api::BDAddr;
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Magic header of the binary log, the last byte is the format version
+pub const BINARY_LOG_MAGIC: &[u8; 8] = b"BLENDR\x00\x01";
+
+const CSV_HEADER: &str =
+    "time,direction,address,service,characteristic,hex,numeric_type,numeric_value";
+
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    JsonLines,
+    /// Compact little endian log. After the magic header every record is:
+    /// i64 unix time in microseconds, u8 direction (0 incoming, 1 outgoing), 6 bytes of device address,
+    /// 16 bytes of service uuid, 16 bytes of characteristic uuid, u32 data length and the data itself.
+    Binary,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Binary => "bin",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "jsonl" | "ndjson" => Some(ExportFormat::JsonLines),
+            "bin" | "blendr" => Some(ExportFormat::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            ExportFormat::Csv => ExportFormat::JsonLines,
+            ExportFormat::JsonLines => ExportFormat::Binary,
+            ExportFormat::Binary => ExportFormat::Csv,
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct JsonRecord {
+    time: String,
+    direction: &'static str,
+    address: String,
+    service: String,
+    characteristic: String,
+    hex: String,
+    numeric_type: Option<&'static str>,
+    numeric_value: Option<String>,
+}
+
+fn direction_label(direction: ValueDirection) -> &'static str {
+    match direction {
+        ValueDirection::Incoming => "in",
+        ValueDirection::Outgoing => "out",
+    }
+}
+
+pub struct ExportWriter {
+    format: ExportFormat,
+    writer: BufWriter<File>,
+}
+
+impl std::fmt::Debug for ExportWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExportWriter")
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+impl ExportWriter {
+    pub fn create(path: &Path, format: ExportFormat) -> Result<Self> {
+        let file = File::create(path)
+            .map_err(|e| Error::client(format!("Failed to create file {}: {e}", path.display())))?;
+
+        let mut writer = BufWriter::new(file);
+        match format {
+            ExportFormat::Csv => writeln!(writer, "{CSV_HEADER}")?,
+            ExportFormat::Binary => writer.write_all(BINARY_LOG_MAGIC)?,
+            ExportFormat::JsonLines => (),
+        }
+
+        Ok(Self { format, writer })
+    }
+
+    pub fn write(
+        &mut self,
+        address: BDAddr,
+        characteristic: &ConnectedCharacteristic,
+        value: &CharacteristicValue,
+    ) -> Result<()> {
+        let hex = to_hex(&value.data);
+
+        let numeric = try_parse_numeric_value::<LittleEndian>(
+            &mut std::io::Cursor::new(&value.data[..]),
+            false,
+            false,
+        )
+        .ok();
+
+        match self.format {
+            ExportFormat::Csv => writeln!(
+                self.writer,
+                "{},{},{address},{},{},{hex},{},{}",
+                value.time.to_rfc3339(),
+                direction_label(value.direction),
+                characteristic.service_uuid,
+                characteristic.uuid,
+                numeric
+                    .as_ref()
+                    .map(|(label, _)| *label)
+                    .unwrap_or_default(),
+                numeric
+                    .as_ref()
+                    .map(|(_, value)| value.as_str())
+                    .unwrap_or_default(),
+            )?,
+            ExportFormat::JsonLines => {
+                let record = JsonRecord {
+                    time: value.time.to_rfc3339(),
+                    direction: direction_label(value.direction),
+                    address: address.to_string(),
+                    service: characteristic.service_uuid.to_string(),
+                    characteristic: characteristic.uuid.to_string(),
+                    hex,
+                    numeric_type: numeric.as_ref().map(|(label, _)| *label),
+                    numeric_value: numeric.map(|(_, value)| value),
+                };
+
+                serde_json::to_writer(&mut self.writer, &record)
+                    .map_err(|e| Error::client(e.to_string()))?;
+                writeln!(self.writer)?;
+            }
+            ExportFormat::Binary => {
+                self.writer
+                    .write_i64::<LittleEndian>(value.time.timestamp_micros())?;
+                self.writer.write_u8(match value.direction {
+                    ValueDirection::Incoming => 0,
+                    ValueDirection::Outgoing => 1,
+                })?;
+                self.writer.write_all(&address.into_inner())?;
+                self.writer
+                    .write_all(characteristic.service_uuid.as_bytes())?;
+                self.writer.write_all(characteristic.uuid.as_bytes())?;
+                self.writer
+                    .write_u32::<LittleEndian>(value.data.len() as u32)?;
+                self.writer.write_all(&value.data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes the whole characteristic history into the file, returns the amount of exported values
+pub fn export_history<'a>(
+    path: &Path,
+    format: ExportFormat,
+    address: BDAddr,
+    characteristic: &ConnectedCharacteristic,
+    history: impl IntoIterator<Item = &'a CharacteristicValue>,
+) -> Result<usize> {
+    let mut writer = ExportWriter::create(path, format)?;
+    let mut count = 0;
+    for value in history {
+        writer.write(address, characteristic, value)?;
+        count += 1;
+    }
+
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Default file name for the export of characteristic history e.g. blendr-AABBCCDDEEFF-2a19-20230101-120000.csv
+pub fn default_export_file_name(
+    address: BDAddr,
+    characteristic: &ConnectedCharacteristic,
+    format: ExportFormat,
+) -> String {
+    format!(
+        "blendr-{}-{}-{}.{}",
+        address.to_string_no_delim(),
+        characteristic.uuid,
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    )
+}
+
+/// Appends the value to the session recording if --record is enabled
+pub fn record_value(
+    ctx: &Ctx,
+    address: BDAddr,
+    characteristic: &ConnectedCharacteristic,
+    value: &CharacteristicValue,
+) {
+    let Some(recorder) = ctx.recorder.as_ref() else {
+        return;
+    };
+
+    let result = recorder
+        .lock()
+        .map_err(Error::from)
+        .and_then(|mut recorder| {
+            recorder.write(address, characteristic, value)?;
+            recorder.flush()
+        });
+
+    if let Err(e) = result {
+        tracing::error!(?e, "Failed to record characteristic value.");
+    }
+}
+
+#[test]
+fn exports_history_in_all_formats() {
+    use crate::bluetooth::ble_default_services::create_ble_uuid;
+
+    let characteristic = ConnectedCharacteristic {
+        ble_characteristic: btleplug::api::Characteristic {
+            uuid: create_ble_uuid(0x2A19),
+            service_uuid: create_ble_uuid(0x180F),
+            properties: btleplug::api::CharPropFlags::READ,
+            descriptors: Default::default(),
+        },
+        standard_gatt_char_name: None,
+        standard_gatt_service_name: None,
+        custom_char_name: None,
+        custom_service_name: None,
+        user_description: None,
+        pinned: false,
+        uuid: create_ble_uuid(0x2A19),
+        service_uuid: create_ble_uuid(0x180F),
+    };
+
+    let history = [
+        CharacteristicValue {
+            time: chrono::Local::now(),
+            data: vec![0x64],
+            direction: ValueDirection::Incoming,
+        },
+        CharacteristicValue {
+            time: chrono::Local::now(),
+            data: vec![0x01, 0x02],
+            direction: ValueDirection::Outgoing,
+        },
+    ];
+
+    let address = BDAddr::from([0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]);
+    let dir = std::env::temp_dir();
+
+    let csv_path = dir.join("blendr-export-test.csv");
+    export_history(
+        &csv_path,
+        ExportFormat::Csv,
+        address,
+        &characteristic,
+        &history,
+    )
+    .unwrap();
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    let lines = csv.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], CSV_HEADER);
+    assert!(lines[1].ends_with(
+        ",in,AA:BB:CC:DD:EE:FF,0000180f-0000-1000-8000-00805f9b34fb,00002a19-0000-1000-8000-00805f9b34fb,64,i8,100"
+    ));
+    assert!(lines[2].contains(",out,") && lines[2].ends_with(",0102,i16,513"));
+
+    let jsonl_path = dir.join("blendr-export-test.jsonl");
+    export_history(
+        &jsonl_path,
+        ExportFormat::JsonLines,
+        address,
+        &characteristic,
+        &history,
+    )
+    .unwrap();
+    let jsonl = std::fs::read_to_string(&jsonl_path).unwrap();
+    let first: serde_json::Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+    assert_eq!(first["hex"], "64");
+    assert_eq!(first["numeric_value"], "100");
+
+    let bin_path = dir.join("blendr-export-test.bin");
+    export_history(
+        &bin_path,
+        ExportFormat::Binary,
+        address,
+        &characteristic,
+        &history,
+    )
+    .unwrap();
+    let bin = std::fs::read(&bin_path).unwrap();
+    assert!(bin.starts_with(BINARY_LOG_MAGIC));
+    // magic + 2 records with 51 bytes of header each + 3 bytes of data
+    assert_eq!(bin.len(), 8 + 51 * 2 + 3);
+
+    for path in [csv_path, jsonl_path, bin_path] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
 use cli_args::GeneralSort;
-use crossterm::event::KeyCode;
 
 use crate::{
     cli_args::{self, Args},
+    tui::{command_palette::Command, keymap::Action},
     Ctx,
 };
 
@@ -20,17 +20,23 @@ impl GeneralOptions {
         }
     }
 
-    pub fn handle_keystroke(keycode: &KeyCode, ctx: &Arc<Ctx>) -> bool {
-        match keycode {
-            KeyCode::Char('n') => {
+    pub fn handle_command(command: &Command, ctx: &Arc<Ctx>) -> bool {
+        match command {
+            Command::Action(Action::Sort) => {
                 let mut general_options = ctx.general_options.write().unwrap();
                 general_options.sort = match general_options.sort {
-                    GeneralSort::Name => GeneralSort::DefaultSort,
+                    GeneralSort::Name => GeneralSort::Pinned,
+                    GeneralSort::Pinned => GeneralSort::DefaultSort,
                     GeneralSort::DefaultSort => GeneralSort::Name,
                 };
 
                 true
             }
+            Command::Sort(sort) => {
+                ctx.general_options.write().unwrap().sort = *sort;
+
+                true
+            }
             _ => false,
         }
     }
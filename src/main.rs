@@ -1,16 +1,32 @@
 #![allow(clippy::single_match)]
 mod bluetooth;
 mod cli_args;
+mod commands;
+mod config;
 mod error;
+mod export;
 mod general_options;
+mod pins;
 mod route;
+mod session;
 mod tui;
 
-use crate::{bluetooth::BleScan, tui::run_tui_app};
-use btleplug::platform::Manager;
-use clap::Parser;
+use crate::{
+    bluetooth::{
+        backend::{BleManager, PeripheralId},
+        decoder::DecoderSchema,
+        replay::ReplayManager,
+        scan_filter::ScanOptions,
+        simulator::SimulatedManager,
+        BleScan,
+    },
+    cli_args::Command,
+    tui::run_tui_app,
+};
+use clap::{CommandFactory, FromArgMatches};
 use cli_args::Args;
 use general_options::GeneralOptions;
+use std::collections::HashMap;
 use std::env;
 use std::sync::RwLock;
 use std::sync::{Arc, Mutex, RwLockReadGuard};
@@ -18,16 +34,71 @@ use std::sync::{Arc, Mutex, RwLockReadGuard};
 #[derive(Debug)]
 pub struct Ctx {
     args: Args,
-    ble_manager: Manager,
+    ble_manager: Arc<dyn BleManager>,
     latest_scan: RwLock<Option<BleScan>>,
+    /// Signal history of every peripheral seen since the start, survives scan restarts
+    peripheral_history: RwLock<HashMap<PeripheralId, bluetooth::history::PeripheralHistory>>,
     active_route: RwLock<route::Route>,
     active_side_effect_handle: Mutex<Option<tokio::task::JoinHandle<()>>>,
     request_scan_restart: Mutex<bool>,
+    /// Info of every available adapter, filled by the scanner
+    adapters: RwLock<Vec<String>>,
+    /// Indexes of the adapters to scan on, changes are applied by the scan restart
+    selected_adapters: RwLock<Vec<usize>>,
+    /// Peripherals kept connected in the background, in the order of connecting
+    connections: RwLock<Vec<bluetooth::connections::Connection>>,
+    /// Applied to the adapter on the scan start and on every scan restart
+    scan_options: RwLock<bluetooth::scan_filter::ScanOptions>,
     global_error: Mutex<Option<crate::error::Error>>,
     general_options: RwLock<general_options::GeneralOptions>,
+    /// Session recording of every characteristic value enabled by --record
+    recorder: Option<Mutex<export::ExportWriter>>,
+    /// Recording of every BLE event enabled by --record-session
+    session_recorder: Option<Mutex<session::SessionRecorder>>,
+    /// Payload layouts of custom characteristics loaded from --decoders
+    decoders: bluetooth::decoder::DecoderSchema,
+    /// Names and favorites from the config files, the rest of the config is already applied to the args
+    config: config::Config,
+    /// Peripherals and characteristics pinned from the UI
+    pins: RwLock<pins::Pins>,
+    /// Characteristic to open as soon as the peripheral with the id is connected, set by the favorites
+    /// panel and cleared once the waiting view is left or the connection failed
+    open_on_connect: Mutex<
+        Option<(
+            bluetooth::backend::PeripheralId,
+            bluetooth::backend::GattAttribute,
+        )>,
+    >,
+    /// Keys of the actions, defaults overridden by the [keys] table of the config
+    keymap: tui::keymap::Keymap,
 }
 
 impl Ctx {
+    pub fn new(args: Args, ble_manager: Arc<dyn BleManager>) -> Self {
+        Self {
+            latest_scan: RwLock::new(None),
+            peripheral_history: RwLock::new(HashMap::new()),
+            active_route: RwLock::new(route::Route::PeripheralList),
+            active_side_effect_handle: Mutex::new(None),
+            ble_manager,
+            request_scan_restart: Mutex::new(false),
+            scan_options: RwLock::new(Default::default()),
+            connections: RwLock::new(vec![]),
+            adapters: RwLock::new(vec![]),
+            selected_adapters: RwLock::new(args.adapter_index.clone()),
+            global_error: Mutex::new(None),
+            general_options: RwLock::new(GeneralOptions::new(&args)),
+            recorder: None,
+            session_recorder: None,
+            decoders: Default::default(),
+            config: Default::default(),
+            pins: Default::default(),
+            open_on_connect: Mutex::new(None),
+            keymap: Default::default(),
+            args,
+        }
+    }
+
     pub fn get_active_route(&self) -> RwLockReadGuard<'_, route::Route> {
         self.active_route
             .read()
@@ -37,9 +108,65 @@ impl Ctx {
     }
 }
 
+/// Loads the files and the BLE backend the args point to
+async fn build_ctx(mut args: Args, matches: &clap::ArgMatches) -> error::Result<Ctx> {
+    let mut config = config::Config::load(&env::current_dir()?)?;
+    config.apply(&mut args, matches);
+
+    let keymap = tui::keymap::Keymap::new(&config.keys)?;
+    let pins = pins::Pins::load()?;
+    let scan_options = ScanOptions::try_from(&args.scan_filter)?;
+
+    let ble_manager: Arc<dyn BleManager> = match (&args.command, args.simulate.as_deref()) {
+        (Some(Command::Replay { file }), _) => Arc::new(ReplayManager::from_file(file)?),
+        (_, Some(path)) => Arc::new(SimulatedManager::from_file(path)?),
+        _ => bluetooth::backend::platform_manager()
+            .await
+            .map_err(|e| error::Error::client(format!("Can not establish BLE connection: {e}")))?,
+    };
+
+    let adapters = ble_manager.adapters().await?;
+    bluetooth::pick_adapters(&adapters, &args.adapter_index).await?;
+
+    let recorder = match args.record.as_deref() {
+        Some(path) => {
+            let format = export::ExportFormat::from_path(path).ok_or_else(|| {
+                error::Error::client(
+                    "Unknown record file format, use one of .csv, .jsonl or .bin extensions",
+                )
+            })?;
+
+            Some(Mutex::new(export::ExportWriter::create(path, format)?))
+        }
+        None => None,
+    };
+
+    let session_recorder = match args.record_session.as_deref() {
+        Some(path) => Some(Mutex::new(session::SessionRecorder::create(path)?)),
+        None => None,
+    };
+
+    let decoders = match args.decoders.as_deref() {
+        Some(path) => DecoderSchema::from_file(path)?,
+        None => Default::default(),
+    };
+
+    let mut ctx = Ctx::new(args, ble_manager);
+    ctx.recorder = recorder;
+    ctx.session_recorder = session_recorder;
+    ctx.decoders = decoders;
+    ctx.config = config;
+    ctx.pins = RwLock::new(pins);
+    ctx.keymap = keymap;
+    ctx.scan_options = RwLock::new(scan_options);
+
+    Ok(ctx)
+}
+
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
     let file_appender = tracing_appender::rolling::daily(env::temp_dir().join("blendr"), "cli.log");
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
 
@@ -52,18 +179,27 @@ async fn main() {
         .pretty()
         .init();
 
-    let ctx = Arc::new(Ctx {
-        latest_scan: RwLock::new(None),
-        active_route: RwLock::new(route::Route::PeripheralList),
-        active_side_effect_handle: Mutex::new(None),
-        ble_manager: Manager::new()
-            .await
-            .expect("Can not establish BLE connection."),
-        request_scan_restart: Mutex::new(false),
-        global_error: Mutex::new(None),
-        general_options: RwLock::new(GeneralOptions::new(&args)),
-        args,
-    });
+    let ctx = match build_ctx(args, &matches).await {
+        Ok(ctx) => Arc::new(ctx),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(command) = ctx
+        .args
+        .command
+        .clone()
+        .filter(|command| !matches!(command, Command::Replay { .. }))
+    {
+        if let Err(e) = commands::run(ctx, command, &mut std::io::stdout()).await {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+
+        return;
+    }
 
     let ctx_clone = Arc::clone(&ctx);
     let _scanner = tokio::spawn(async move {
@@ -0,0 +1,226 @@
+use crate::{
+    bluetooth::{ConnectedCharacteristic, HandledPeripheral},
+    config::{self, matches_device},
+    error::{Error, Result},
+    Ctx,
+};
+use btleplug::api::BDAddr;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+pub const PINS_FILE: &str = "pins.toml";
+
+/// Device pinned by its address or by a regex matching its whole name
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct PeripheralPin {
+    pub pattern: String,
+    name_regex: Option<Regex>,
+}
+
+impl From<String> for PeripheralPin {
+    fn from(pattern: String) -> Self {
+        Self {
+            name_regex: Regex::new(&format!("^(?:{pattern})$")).ok(),
+            pattern,
+        }
+    }
+}
+
+impl From<PeripheralPin> for String {
+    fn from(pin: PeripheralPin) -> Self {
+        pin.pattern
+    }
+}
+
+impl PeripheralPin {
+    fn matches(&self, name: &str, address: &BDAddr) -> bool {
+        matches_device(&self.pattern, name, address)
+            || self
+                .name_regex
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PinnedCharacteristic {
+    /// Address or name of the device the characteristic was pinned on, the favorites connect to it
+    pub device: String,
+    pub service: Uuid,
+    pub characteristic: Uuid,
+    /// Name of the characteristic at the time of pinning
+    pub name: String,
+}
+
+/// Pinned peripherals and characteristics, persisted next to the user config
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Pins {
+    #[serde(default)]
+    peripherals: Vec<PeripheralPin>,
+    #[serde(default, rename = "characteristic")]
+    characteristics: Vec<PinnedCharacteristic>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// Bumped on every change so the sorted lists know to refresh
+    #[serde(skip)]
+    revision: u64,
+}
+
+/// Address is the stable way to refer to the device unless the platform hides it
+fn device_key(peripheral: &HandledPeripheral) -> String {
+    if peripheral.address == BDAddr::default() {
+        peripheral.name.clone()
+    } else {
+        peripheral.address.to_string()
+    }
+}
+
+impl Pins {
+    /// Missing file means nothing is pinned yet, it is created on the first pin
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let mut pins: Pins =
+            if path.is_file() {
+                toml::from_str(&std::fs::read_to_string(path).map_err(|e| {
+                    Error::client(format!("Failed to read {}: {e}", path.display()))
+                })?)
+                .map_err(|e| Error::client(format!("Failed to parse {}: {e}", path.display())))?
+            } else {
+                Pins::default()
+            };
+
+        pins.path = Some(path.to_owned());
+        Ok(pins)
+    }
+
+    /// Reads ~/.config/blendr/pins.toml
+    pub fn load() -> Result<Self> {
+        match config::config_dir() {
+            Some(dir) => Self::from_file(&dir.join(PINS_FILE)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn save(&mut self) -> Result<()> {
+        self.revision += 1;
+
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let content = toml::to_string(self)
+            .map_err(|e| Error::client(format!("Failed to serialize pins: {e}")))?;
+        std::fs::write(path, content)
+            .map_err(|e| Error::client(format!("Failed to write {}: {e}", path.display())))
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn characteristics(&self) -> &[PinnedCharacteristic] {
+        &self.characteristics
+    }
+
+    pub fn is_peripheral_pinned(&self, name: &str, address: &BDAddr) -> bool {
+        self.peripherals
+            .iter()
+            .any(|pin| pin.matches(name, address))
+    }
+
+    pub fn is_characteristic_pinned(&self, service: &Uuid, characteristic: &Uuid) -> bool {
+        self.characteristics
+            .iter()
+            .any(|pin| pin.service == *service && pin.characteristic == *characteristic)
+    }
+
+    /// Unpins every pattern matching the peripheral or pins it by the address
+    pub fn toggle_peripheral(&mut self, peripheral: &HandledPeripheral) -> Result<()> {
+        let pins_count = self.peripherals.len();
+        self.peripherals
+            .retain(|pin| !pin.matches(&peripheral.name, &peripheral.address));
+
+        if self.peripherals.len() == pins_count {
+            self.peripherals.push(device_key(peripheral).into());
+        }
+
+        self.save()
+    }
+
+    pub fn toggle_characteristic(
+        &mut self,
+        peripheral: &HandledPeripheral,
+        characteristic: &ConnectedCharacteristic,
+    ) -> Result<()> {
+        if self.is_characteristic_pinned(&characteristic.service_uuid, &characteristic.uuid) {
+            self.characteristics.retain(|pin| {
+                pin.service != characteristic.service_uuid
+                    || pin.characteristic != characteristic.uuid
+            });
+        } else {
+            self.characteristics.push(PinnedCharacteristic {
+                device: device_key(peripheral),
+                service: characteristic.service_uuid,
+                characteristic: characteristic.uuid,
+                name: characteristic.char_name().into_owned(),
+            });
+        }
+
+        self.save()
+    }
+
+    pub fn unpin_characteristic(&mut self, index: usize) -> Result<()> {
+        if index < self.characteristics.len() {
+            self.characteristics.remove(index);
+        }
+
+        self.save()
+    }
+}
+
+/// Pinned from the UI or listed as favorite in the config
+pub fn is_peripheral_pinned(ctx: &Ctx, name: &str, address: &BDAddr) -> bool {
+    ctx.config.is_favorite(name, address)
+        || ctx
+            .pins
+            .read()
+            .is_ok_and(|pins| pins.is_peripheral_pinned(name, address))
+}
+
+pub fn is_characteristic_pinned(ctx: &Ctx, characteristic: &ConnectedCharacteristic) -> bool {
+    ctx.pins.read().is_ok_and(|pins| {
+        pins.is_characteristic_pinned(&characteristic.service_uuid, &characteristic.uuid)
+    })
+}
+
+#[test]
+fn persists_pins() {
+    let path = std::env::temp_dir().join(format!("blendr-pins-{}.toml", std::process::id()));
+    let mut pins = Pins::from_file(&path).unwrap();
+    assert!(!pins.is_peripheral_pinned("Sensor 1", &BDAddr::default()));
+
+    pins.peripherals.push(String::from("Sensor \\d").into());
+    pins.characteristics.push(PinnedCharacteristic {
+        device: String::from("AA:BB:CC:DD:EE:01"),
+        service: Uuid::from_u128(0x0000180f_0000_1000_8000_00805f9b34fb),
+        characteristic: Uuid::from_u128(0x00002a19_0000_1000_8000_00805f9b34fb),
+        name: String::from("Battery Level"),
+    });
+    pins.save().unwrap();
+
+    let pins = Pins::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(pins.is_peripheral_pinned("Sensor 1", &BDAddr::default()));
+    assert!(!pins.is_peripheral_pinned("Sensor 10", &BDAddr::default()));
+    assert!(pins.is_characteristic_pinned(
+        &Uuid::from_u128(0x0000180f_0000_1000_8000_00805f9b34fb),
+        &Uuid::from_u128(0x00002a19_0000_1000_8000_00805f9b34fb),
+    ));
+    assert_eq!(pins.characteristics()[0].device, "AA:BB:CC:DD:EE:01");
+}
@@ -1,25 +1,72 @@
 use super::Ctx;
 use crate::{
-    bluetooth::{self, ConnectedCharacteristic, ConnectedPeripheral},
+    bluetooth::{
+        connections::{self, ConnectionState},
+        ConnectedCharacteristic, ConnectedPeripheral,
+    },
     error,
 };
-use btleplug::api::Peripheral;
+use btleplug::api::CharPropFlags;
 use std::{
     ops::{Deref, DerefMut},
     sync::{
         atomic::{AtomicIsize, AtomicU16},
-        Arc, RwLock,
+        Arc,
     },
-    time::Duration,
 };
-use tokio::time::{self, timeout};
 
 use crate::bluetooth::HandledPeripheral;
 
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueDirection {
+    /// Value received from the device
+    #[default]
+    Incoming,
+    /// Value written to the device by user
+    Outgoing,
+}
+
 #[derive(Debug, Clone)]
 pub struct CharacteristicValue {
     pub time: chrono::DateTime<chrono::Local>,
     pub data: Vec<u8>,
+    pub direction: ValueDirection,
+}
+
+/// How the characteristic view receives new values from the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueUpdateMode {
+    /// Read the value on every scan interval tick
+    Poll,
+    /// Subscribe to notifications or indications pushed by the device
+    Subscribe,
+}
+
+impl ValueUpdateMode {
+    pub fn can_subscribe(props: CharPropFlags) -> bool {
+        props.intersects(CharPropFlags::NOTIFY | CharPropFlags::INDICATE)
+    }
+
+    /// Prefer notifications over polling whenever characteristic supports them
+    pub fn default_for(props: CharPropFlags) -> Self {
+        if Self::can_subscribe(props) {
+            ValueUpdateMode::Subscribe
+        } else {
+            ValueUpdateMode::Poll
+        }
+    }
+
+    /// Switching makes sense only if characteristic supports both reads and notifications
+    pub fn can_toggle(props: CharPropFlags) -> bool {
+        props.contains(CharPropFlags::READ) && Self::can_subscribe(props)
+    }
+
+    pub fn toggle(self) -> Self {
+        match self {
+            ValueUpdateMode::Poll => ValueUpdateMode::Subscribe,
+            ValueUpdateMode::Subscribe => ValueUpdateMode::Poll,
+        }
+    }
 }
 
 /// Atomic implementation for optional index
@@ -60,11 +107,34 @@ impl AtomicOptionalIndex {
     pub fn annulate(&self) {
         self.0.store(-1, std::sync::atomic::Ordering::SeqCst)
     }
+
+    /// Keeps pointing at the same value once `count` values were dropped from the front of the
+    /// history, cleared if the value itself was dropped
+    pub fn shift_back(&self, count: usize) {
+        let count = isize::try_from(count).unwrap_or(isize::MAX);
+        let _ = self.0.fetch_update(
+            std::sync::atomic::Ordering::SeqCst,
+            std::sync::atomic::Ordering::SeqCst,
+            |index| (index >= 0).then(|| if index >= count { index - count } else { -1 }),
+        );
+    }
 }
 
+// only a single route is alive at a time, boxing the connected views would not save anything
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 pub enum Route {
     PeripheralList,
+    /// Peripheral list with the advertisement data of the selected peripheral, no connection is made
+    AdvertisementView {
+        peripheral: HandledPeripheral,
+    },
+    /// Picker of the adapters to scan on, shown next to the peripheral list
+    AdapterList,
+    /// Peripherals connected in the background, shown next to the peripheral list
+    ConnectionsPanel,
+    /// Pinned characteristics to jump into, shown next to the peripheral list
+    FavoritesPanel,
     PeripheralConnectedView(ConnectedPeripheral),
     PeripheralWaitingView {
         peripheral: HandledPeripheral,
@@ -75,7 +145,8 @@ pub enum Route {
         peripheral: ConnectedPeripheral,
         characteristic: ConnectedCharacteristic,
         historical_view_index: Arc<AtomicOptionalIndex>,
-        history: Arc<RwLock<Vec<CharacteristicValue>>>,
+        history: connections::ValueHistory,
+        update_mode: ValueUpdateMode,
     },
 }
 
@@ -84,36 +155,41 @@ impl Route {
     pub(crate) async fn spawn_navigation_side_effect(
         self,
         previous: &Route,
-        ctx: &Ctx,
+        ctx: &Arc<Ctx>,
     ) -> error::Result<()> {
         match (previous, self) {
-            (Route::PeripheralList, Route::PeripheralWaitingView { peripheral, retry }) => {
-                while !peripheral
-                    .ble_peripheral
-                    .is_connected()
-                    .await
-                    .unwrap_or(false)
+            // any view can start connecting, e.g. with the :connect command from the connected view
+            (_, Route::PeripheralWaitingView { peripheral, retry }) => {
+                // switching to the peripheral connected in the background, the supervisor takes care of the reconnection
+                if let Some(connected) = connections::find(ctx, &peripheral.ble_peripheral.id())
+                    .filter(|_| {
+                        connections::state(ctx, &peripheral.ble_peripheral.id())
+                            != Some(ConnectionState::Failed)
+                    })
                 {
-                    tracing::debug!("Connecting to peripheral.");
-                    if let Err(e) =
-                        timeout(Duration::from_secs(10), peripheral.ble_peripheral.connect()).await
-                    {
-                        tracing::error!(?e, "Failed to connect to peripheral.");
-                    }
-
-                    retry.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    *ctx.active_route.write()? = Route::PeripheralConnectedView(connected);
+                    return Ok(());
                 }
 
-                peripheral.ble_peripheral.discover_services().await?;
+                if let Err(e) = connections::connect_with_backoff(ctx, &peripheral, |attempt| {
+                    retry.store(
+                        attempt.saturating_sub(1).try_into().unwrap_or(u16::MAX),
+                        std::sync::atomic::Ordering::SeqCst,
+                    )
+                })
+                .await
+                {
+                    // the pinned characteristic must not be opened by the next unrelated connection
+                    ctx.open_on_connect.lock()?.take();
+                    return Err(e);
+                }
+
+                let mut connected = ConnectedPeripheral::new(ctx, peripheral);
+                connected.read_descriptors(ctx).await;
+                connections::register(ctx, connected.clone())?;
+
                 let mut active_route = ctx.active_route.write().unwrap();
-                (*active_route) =
-                    Route::PeripheralConnectedView(ConnectedPeripheral::new(ctx, peripheral))
-            }
-            (
-                Route::PeripheralConnectedView(ConnectedPeripheral { peripheral, .. }),
-                Route::PeripheralList,
-            ) => {
-                bluetooth::disconnect_with_timeout(&peripheral.ble_peripheral).await;
+                (*active_route) = Route::PeripheralConnectedView(connected)
             }
             (
                 _,
@@ -121,26 +197,12 @@ impl Route {
                     peripheral,
                     characteristic,
                     history,
+                    update_mode,
                     ..
                 },
-            ) => loop {
-                let ble_peripheral = &peripheral.peripheral.ble_peripheral;
-                if let Ok(data) = ble_peripheral
-                    .read(&characteristic.ble_characteristic)
-                    .await
-                {
-                    if !ble_peripheral.is_connected().await.unwrap_or(false) {
-                        break;
-                    }
-
-                    history.write().unwrap().push(CharacteristicValue {
-                        time: chrono::Local::now(),
-                        data,
-                    });
-
-                    time::sleep(Duration::from_millis(ctx.args.scan_interval)).await;
-                }
-            },
+            ) => {
+                connections::watch(ctx, &peripheral, &characteristic, update_mode, history).await?;
+            }
 
             _ => (),
         }
@@ -161,6 +223,13 @@ impl Route {
         let old_route = std::mem::replace(active_route.deref_mut(), self.clone());
         drop(active_route);
 
+        // leaving the waiting view cancels the jump to the pinned characteristic
+        if !matches!(self, Route::PeripheralWaitingView { .. }) {
+            if let Ok(mut open_on_connect) = ctx.open_on_connect.lock() {
+                open_on_connect.take();
+            }
+        }
+
         let ctx_clone = Arc::clone(ctx);
         let active_handle = tokio::spawn(async move {
             if let Err(e) = self
@@ -183,3 +252,18 @@ impl Route {
         effect_handle.replace(active_handle);
     }
 }
+
+#[test]
+fn shifts_historical_index_with_dropped_values() {
+    let index = AtomicOptionalIndex::default();
+    index.shift_back(1);
+    assert_eq!(index.read(), None);
+
+    index.write(3);
+    index.shift_back(2);
+    assert_eq!(index.read(), Some(1));
+    index.shift_back(1);
+    assert_eq!(index.read(), Some(0));
+    index.shift_back(1);
+    assert_eq!(index.read(), None);
+}
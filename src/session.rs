@@ -0,0 +1,289 @@
+use crate::{
+    bluetooth::{ConnectedCharacteristic, HandledPeripheral},
+    error::{Error, Result},
+    Ctx,
+};
+use btleplug::api::{CharPropFlags, Characteristic, Descriptor, PeripheralProperties, Service};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecordedCharacteristic {
+    pub uuid: Uuid,
+    pub service_uuid: Uuid,
+    pub properties: u8,
+    #[serde(default)]
+    pub descriptors: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecordedService {
+    pub uuid: Uuid,
+    pub primary: bool,
+    pub characteristics: Vec<RecordedCharacteristic>,
+}
+
+impl From<&Characteristic> for RecordedCharacteristic {
+    fn from(char: &Characteristic) -> Self {
+        Self {
+            uuid: char.uuid,
+            service_uuid: char.service_uuid,
+            properties: char.properties.bits(),
+            descriptors: char
+                .descriptors
+                .iter()
+                .map(|descriptor| descriptor.uuid)
+                .collect(),
+        }
+    }
+}
+
+impl From<&RecordedCharacteristic> for Characteristic {
+    fn from(char: &RecordedCharacteristic) -> Self {
+        Self {
+            uuid: char.uuid,
+            service_uuid: char.service_uuid,
+            properties: CharPropFlags::from_bits_truncate(char.properties),
+            descriptors: char
+                .descriptors
+                .iter()
+                .map(|uuid| Descriptor {
+                    uuid: *uuid,
+                    service_uuid: char.service_uuid,
+                    characteristic_uuid: char.uuid,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&Service> for RecordedService {
+    fn from(service: &Service) -> Self {
+        Self {
+            uuid: service.uuid,
+            primary: service.primary,
+            characteristics: service
+                .characteristics
+                .iter()
+                .map(RecordedCharacteristic::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<&RecordedService> for Service {
+    fn from(service: &RecordedService) -> Self {
+        Self {
+            uuid: service.uuid,
+            primary: service.primary,
+            characteristics: service
+                .characteristics
+                .iter()
+                .map(Characteristic::from)
+                .collect::<BTreeSet<_>>(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScannedPeripheral {
+    /// Backend specific peripheral id, stable only within one session
+    pub id: String,
+    #[serde(deserialize_with = "deserialize_properties")]
+    pub properties: PeripheralProperties,
+}
+
+/// Flattened session entries are buffered by serde which can not parse the numeric keys of
+/// the manufacturer data back, so the properties are parsed again from their json text.
+/// Text is required because btleplug parses addresses only from borrowed strings.
+fn deserialize_properties<'de, D>(
+    deserializer: D,
+) -> std::result::Result<PeripheralProperties, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = <serde_json::Value as serde::Deserialize>::deserialize(deserializer)?;
+    serde_json::from_str(&value.to_string()).map_err(serde::de::Error::custom)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueSource {
+    Read,
+    Notification,
+    Write,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// Peripherals updated since the previous scan snapshot
+    Scan {
+        peripherals: Vec<ScannedPeripheral>,
+    },
+    ConnectAttempt {
+        id: String,
+        error: Option<String>,
+    },
+    ServicesDiscovered {
+        id: String,
+        services: Vec<RecordedService>,
+    },
+    Value {
+        id: String,
+        service: Uuid,
+        characteristic: Uuid,
+        source: ValueSource,
+        #[serde(with = "hex_bytes")]
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SessionEntry {
+    /// Milliseconds since the session start
+    pub elapsed_ms: u64,
+    pub time: chrono::DateTime<chrono::Local>,
+    #[serde(flatten)]
+    pub event: SessionEvent,
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&crate::bluetooth::payload::to_hex(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        crate::bluetooth::payload::parse_hex(&hex).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Writes every observed BLE event as JSON line so the whole session can be replayed later
+pub struct SessionRecorder {
+    started: Instant,
+    writer: BufWriter<File>,
+}
+
+impl std::fmt::Debug for SessionRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionRecorder")
+            .field("started", &self.started)
+            .finish()
+    }
+}
+
+impl SessionRecorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).map_err(|e| {
+            Error::client(format!(
+                "Failed to create session file {}: {e}",
+                path.display()
+            ))
+        })?;
+
+        Ok(Self {
+            started: Instant::now(),
+            writer: BufWriter::new(file),
+        })
+    }
+
+    pub fn write(&mut self, event: SessionEvent) -> Result<()> {
+        let entry = SessionEntry {
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            time: chrono::Local::now(),
+            event,
+        };
+
+        serde_json::to_writer(&mut self.writer, &entry)
+            .map_err(|e| Error::client(e.to_string()))?;
+        writeln!(self.writer)?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+pub fn read_session(path: &Path) -> Result<Vec<SessionEntry>> {
+    let file = File::open(path).map_err(|e| {
+        Error::client(format!(
+            "Failed to open session file {}: {e}",
+            path.display()
+        ))
+    })?;
+
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(i, line)| {
+            serde_json::from_str(&line?).map_err(|e| {
+                Error::client(format!(
+                    "Failed to parse line {} of session file {}: {e}",
+                    i + 1,
+                    path.display()
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Appends the event to the session recording if --record-session is enabled.
+/// Event is built lazily so nothing is cloned when recording is off.
+pub fn record_event(ctx: &Ctx, event: impl FnOnce() -> SessionEvent) {
+    let Some(recorder) = ctx.session_recorder.as_ref() else {
+        return;
+    };
+
+    let result = recorder
+        .lock()
+        .map_err(Error::from)
+        .and_then(|mut recorder| recorder.write(event()));
+
+    if let Err(e) = result {
+        tracing::error!(?e, "Failed to record session event.");
+    }
+}
+
+pub fn record_connect_attempt(ctx: &Ctx, peripheral: &HandledPeripheral, result: &Result<()>) {
+    record_event(ctx, || SessionEvent::ConnectAttempt {
+        id: peripheral.ble_peripheral.id().to_string(),
+        error: result.as_ref().err().map(ToString::to_string),
+    })
+}
+
+pub fn record_services(ctx: &Ctx, peripheral: &HandledPeripheral) {
+    record_event(ctx, || SessionEvent::ServicesDiscovered {
+        id: peripheral.ble_peripheral.id().to_string(),
+        services: peripheral
+            .ble_peripheral
+            .services()
+            .iter()
+            .map(RecordedService::from)
+            .collect(),
+    })
+}
+
+pub fn record_value(
+    ctx: &Ctx,
+    peripheral: &HandledPeripheral,
+    characteristic: &ConnectedCharacteristic,
+    source: ValueSource,
+    data: &[u8],
+) {
+    record_event(ctx, || SessionEvent::Value {
+        id: peripheral.ble_peripheral.id().to_string(),
+        service: characteristic.service_uuid,
+        characteristic: characteristic.uuid,
+        source,
+        data: data.to_vec(),
+    })
+}
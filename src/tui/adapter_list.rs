@@ -0,0 +1,217 @@
+use crate::{
+    route::Route,
+    tui::{
+        command_palette::Command,
+        keymap::Action,
+        ui::{block, BlendrBlock},
+        AppRoute, HandleKeydownResult,
+    },
+    Ctx,
+};
+use crossterm::event::KeyEvent;
+use std::sync::Arc;
+use tui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{List, ListItem, ListState},
+};
+
+pub(super) const KEY_ACTIONS: &[Action] = &[
+    Action::Down,
+    Action::Up,
+    Action::ToggleAdapter,
+    Action::Confirm,
+    Action::Cancel,
+    Action::Adapters,
+];
+
+/// Lets the user pick the adapters to scan on, the selection is applied with a scan restart
+pub struct AdapterList {
+    ctx: Arc<Ctx>,
+    list_state: ListState,
+    /// Pending selection, loaded from the context every time the picker is opened
+    checked: Option<Vec<usize>>,
+}
+
+impl AdapterList {
+    fn checked(&mut self) -> &mut Vec<usize> {
+        let ctx = &self.ctx;
+
+        self.checked.get_or_insert_with(|| {
+            ctx.selected_adapters
+                .read()
+                .map(|selected| selected.clone())
+                .unwrap_or_default()
+        })
+    }
+
+    fn apply(&mut self) {
+        let mut checked = self.checked.take().unwrap_or_default();
+        // scanning on nothing makes no sense, take the highlighted adapter instead
+        if checked.is_empty() {
+            checked.extend(self.list_state.selected());
+        }
+
+        if checked.is_empty() {
+            return;
+        }
+
+        checked.sort_unstable();
+        if let Ok(mut selected_adapters) = self.ctx.selected_adapters.write() {
+            *selected_adapters = checked;
+        }
+
+        if let Ok(request_restart) = self.ctx.request_scan_restart.lock().as_deref_mut() {
+            *request_restart = true;
+        }
+
+        Route::PeripheralList.navigate(&self.ctx);
+    }
+}
+
+impl AppRoute for AdapterList {
+    fn new(ctx: Arc<Ctx>) -> Self
+    where
+        Self: Sized,
+    {
+        let mut list_state = ListState::default();
+        list_state.select(Some(0));
+
+        AdapterList {
+            ctx,
+            list_state,
+            checked: None,
+        }
+    }
+
+    fn handle_input(&mut self, key: &KeyEvent) -> HandleKeydownResult {
+        match self.ctx.keymap.action(key, KEY_ACTIONS) {
+            Some(action) => self.handle_command(&Command::Action(action)),
+            None => HandleKeydownResult::Continue,
+        }
+    }
+
+    fn handle_command(&mut self, command: &Command) -> HandleKeydownResult {
+        let Command::Action(action) = command else {
+            return HandleKeydownResult::Continue;
+        };
+
+        let adapters_count = self
+            .ctx
+            .adapters
+            .read()
+            .map_or(0, |adapters| adapters.len());
+        let highlighted = self
+            .list_state
+            .selected()
+            .unwrap_or(0)
+            .min(adapters_count.saturating_sub(1));
+
+        match action {
+            Action::Down if adapters_count > 0 => {
+                self.list_state
+                    .select(Some((highlighted + 1) % adapters_count));
+            }
+            Action::Up if adapters_count > 0 => {
+                self.list_state
+                    .select(Some((highlighted + adapters_count - 1) % adapters_count));
+            }
+            Action::ToggleAdapter if adapters_count > 0 => {
+                let checked = self.checked();
+                match checked.iter().position(|index| *index == highlighted) {
+                    Some(position) => {
+                        checked.remove(position);
+                    }
+                    None => checked.push(highlighted),
+                }
+            }
+            Action::Confirm => self.apply(),
+            Action::Cancel | Action::Adapters => {
+                self.checked = None;
+                Route::PeripheralList.navigate(&self.ctx);
+            }
+            _ => return HandleKeydownResult::Continue,
+        }
+
+        HandleKeydownResult::Handled
+    }
+
+    fn render(
+        &mut self,
+        area: tui::layout::Rect,
+        route_active: bool,
+        f: &mut tui::Frame<super::TerminalBackend>,
+    ) -> crate::error::Result<()> {
+        let adapters = self.ctx.adapters.read()?.clone();
+        let checked = self.checked().clone();
+
+        let items = adapters
+            .iter()
+            .enumerate()
+            .map(|(index, info)| {
+                let is_checked = checked.contains(&index);
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        if is_checked { "[x] " } else { "[ ] " },
+                        Style::default().fg(if is_checked {
+                            Color::Green
+                        } else {
+                            Color::DarkGray
+                        }),
+                    ),
+                    Span::styled(
+                        format!("{index}: "),
+                        Style::default().add_modifier(Modifier::DIM),
+                    ),
+                    Span::raw(info.clone()),
+                ]))
+            })
+            .collect::<Vec<_>>();
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(5),
+                    Constraint::Length(if route_active { 3 } else { 0 }),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        let list = List::new(items)
+            .block(tui::widgets::Block::from(BlendrBlock {
+                route_active,
+                focused: true,
+                title: format!("Adapters ({} selected)", checked.len()),
+                ..Default::default()
+            }))
+            .highlight_style(
+                Style::default()
+                    .bg(Color::Blue)
+                    .fg(Color::Black)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        f.render_stateful_widget(list, chunks[0], &mut self.list_state);
+
+        if chunks[1].height > 0 {
+            f.render_widget(
+                block::render_help(
+                    Arc::clone(&self.ctx),
+                    [
+                        Some((&[Action::ToggleAdapter], "Toggle adapter", false)),
+                        Some((&[Action::Confirm], "Scan on selected", false)),
+                        Some((&[Action::Adapters, Action::Cancel], "Cancel", false)),
+                        Some((&[Action::Down, Action::Up], "Navigate", false)),
+                    ],
+                ),
+                chunks[1],
+            );
+        }
+
+        Ok(())
+    }
+}
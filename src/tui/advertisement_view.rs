@@ -0,0 +1,271 @@
+use crate::{
+    bluetooth::{
+        ble_default_services::{format_company_id, SPECIAL_SERVICES_NAMES},
+        history, BleScan, HandledPeripheral,
+    },
+    route::Route,
+    tui::{
+        keymap::Action,
+        ui::{
+            block, format_axis_value, hex_dump_lines, render_time_series, BlendrBlock,
+            StableListItem,
+        },
+        AppRoute, HandleKeydownResult,
+    },
+    Ctx,
+};
+use crossterm::event::KeyEvent;
+use std::{ops::Deref, sync::Arc};
+use tui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Paragraph, Wrap},
+};
+
+/// Shows raw advertisement data of the peripheral selected in the list without connecting to it
+pub struct AdvertisementView {
+    ctx: Arc<Ctx>,
+}
+
+fn render_property<'a>(name: &'a str, value: String) -> Line<'a> {
+    Line::from(vec![
+        Span::styled(name, Style::default().add_modifier(Modifier::DIM)),
+        Span::raw(": "),
+        Span::raw(value),
+    ])
+}
+
+fn render_section_title(title: &str) -> Line<'_> {
+    Line::from(Span::styled(
+        title,
+        Style::default().add_modifier(Modifier::BOLD),
+    ))
+}
+
+impl AppRoute for AdvertisementView {
+    fn new(ctx: Arc<Ctx>) -> Self
+    where
+        Self: Sized,
+    {
+        AdvertisementView { ctx }
+    }
+
+    // all the controls are handled by the peripheral list which is always displayed next to this block
+    fn handle_input(&mut self, _key: &KeyEvent) -> HandleKeydownResult {
+        HandleKeydownResult::Continue
+    }
+
+    fn render(
+        &mut self,
+        area: tui::layout::Rect,
+        route_active: bool,
+        f: &mut tui::Frame<super::TerminalBackend>,
+    ) -> crate::error::Result<()> {
+        let active_route = self.ctx.get_active_route();
+        let Route::AdvertisementView { peripheral } = active_route.deref() else {
+            return Err(crate::error::Error::client("Invalid route"));
+        };
+
+        // prefer the data from the latest scan so the view keeps updating while it is open
+        let scan = self.ctx.latest_scan.read();
+        let peripheral = scan
+            .as_deref()
+            .ok()
+            .and_then(Option::as_ref)
+            .and_then(|BleScan { peripherals, .. }| {
+                peripherals.iter().find(|p| p.id() == peripheral.id())
+            })
+            .unwrap_or(peripheral);
+
+        let HandledPeripheral {
+            name,
+            address,
+            address_type,
+            rssi,
+            tx_power_level,
+            services_names,
+            manufacturer_data,
+            service_data,
+            beacons,
+            ..
+        } = peripheral;
+
+        let mut text = vec![
+            render_property("Name", name.clone()),
+            render_property(
+                "Address",
+                match address_type {
+                    Some(address_type) => format!("{address} ({address_type:?})"),
+                    None => address.to_string(),
+                },
+            ),
+            render_property(
+                "RSSI",
+                rssi.map_or("unknown".to_owned(), |rssi| format!("{rssi} dBm")),
+            ),
+            render_property(
+                "TX power",
+                tx_power_level.map_or("not advertised".to_owned(), |tx| format!("{tx} dBm")),
+            ),
+        ];
+
+        if let Some(history) = self.ctx.peripheral_history.read()?.get(&peripheral.id()) {
+            text.push(render_property(
+                "First seen",
+                history.first_seen.format("%H:%M:%S").to_string(),
+            ));
+            text.push(render_property(
+                "Last seen",
+                format!(
+                    "{} ({} ago)",
+                    history.last_seen.format("%H:%M:%S"),
+                    history::format_age(history.age())
+                ),
+            ));
+            text.push(render_property(
+                "Advertisements",
+                history.advertisement_count.to_string(),
+            ));
+        }
+
+        if let (Some(reference_rssi), Some(rssi)) =
+            (history::reference_rssi(beacons, *tx_power_level), rssi)
+        {
+            text.push(render_property(
+                "Distance",
+                format!(
+                    "~{:.1} m (expecting {reference_rssi} dBm at 1 m)",
+                    history::estimate_distance(reference_rssi, *rssi)
+                ),
+            ));
+        }
+
+        if !services_names.is_empty() {
+            text.push(render_property("Services", services_names.join(", ")));
+        }
+
+        if !beacons.is_empty() {
+            text.push(Line::from(""));
+            text.push(render_section_title("Beacon"));
+            text.extend(beacons.iter().map(|beacon| {
+                Line::from(Span::styled(
+                    beacon.to_string(),
+                    Style::default().fg(Color::Cyan),
+                ))
+            }));
+        }
+
+        text.push(Line::from(""));
+        text.push(render_section_title("Manufacturer data"));
+        if manufacturer_data.is_empty() {
+            text.push(Line::from("Not advertised"));
+        }
+
+        let mut manufacturer_data = manufacturer_data.iter().collect::<Vec<_>>();
+        manufacturer_data.sort_by_key(|(company_id, _)| **company_id);
+        for (company_id, data) in manufacturer_data {
+            text.push(Line::from(Span::styled(
+                format_company_id(*company_id),
+                Style::default().fg(Color::Green),
+            )));
+            text.extend(hex_dump_lines(data, area.width));
+        }
+
+        text.push(Line::from(""));
+        text.push(render_section_title("Service data"));
+        if service_data.is_empty() {
+            text.push(Line::from("Not advertised"));
+        }
+
+        let mut service_data = service_data.iter().collect::<Vec<_>>();
+        service_data.sort_by_key(|(uuid, _)| **uuid);
+        for (uuid, data) in service_data {
+            text.push(Line::from(Span::styled(
+                SPECIAL_SERVICES_NAMES
+                    .get(uuid)
+                    .map_or_else(|| uuid.to_string(), |name| name.to_string()),
+                Style::default().fg(Color::Green),
+            )));
+            text.extend(hex_dump_lines(data, area.width));
+        }
+
+        let rssi_values = self
+            .ctx
+            .peripheral_history
+            .read()?
+            .get(&peripheral.id())
+            .map(|history| {
+                history
+                    .rssi
+                    .iter()
+                    .map(|sample| (sample.time, sample.rssi as f64))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(10),
+                    // the chart would be an empty hole until the first RSSI sample arrives
+                    if rssi_values.is_empty() {
+                        Constraint::Length(0)
+                    } else {
+                        Constraint::Percentage(35)
+                    },
+                    Constraint::Length(if route_active { 3 } else { 0 }),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        let paragraph = Paragraph::new(text)
+            .block(tui::widgets::Block::from(BlendrBlock {
+                route_active,
+                focused: false,
+                title: format!("Advertisement of {name}"),
+                ..Default::default()
+            }))
+            .wrap(Wrap { trim: false });
+
+        f.render_widget(paragraph, chunks[0]);
+
+        render_time_series(
+            f,
+            chunks[1],
+            route_active,
+            &rssi_values,
+            Color::Yellow,
+            |stats| {
+                format!(
+                    "RSSI of last {} scans  min {}  max {}  avg {}",
+                    rssi_values.len(),
+                    format_axis_value(stats.min),
+                    format_axis_value(stats.max),
+                    format_axis_value(stats.avg)
+                )
+            },
+        );
+
+        if chunks[2].height > 0 {
+            f.render_widget(
+                block::render_help(
+                    Arc::clone(&self.ctx),
+                    [
+                        Some((
+                            &[Action::Inspect, Action::Cancel],
+                            "Close advertisement",
+                            false,
+                        )),
+                        Some((&[Action::Right], "Connect to device", false)),
+                    ],
+                ),
+                chunks[2],
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,405 @@
+use crate::{
+    cli_args::GeneralSort,
+    error::{Error, Result},
+    export::ExportFormat,
+    tui::{
+        keymap::Action,
+        ui::{search_input, BlendrBlock},
+        TerminalBackend,
+    },
+    Ctx,
+};
+use clap::ValueEnum;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::sync::Arc;
+use tui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Clear, List, ListItem, ListState},
+    Frame,
+};
+
+/// Amount of the matching commands listed under the input
+const MAX_VISIBLE_COMMANDS: usize = 10;
+/// Characters the best match may skip to run without picking it from the list, e.g. "disc" runs
+/// disconnect while "hu" has to be picked or completed to run hide_unknown
+const MAX_IMPLICIT_MATCH_SCORE: usize = 2;
+
+pub(super) const INPUT_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Complete,
+    Action::Down,
+    Action::Up,
+];
+
+/// What the keys and the command palette ask the views to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Action(Action),
+    /// Connects to the device with the name or address
+    Connect(String),
+    /// Writes hex value to the opened characteristic
+    Write(String),
+    /// Exports the history of the opened characteristic to the default file
+    Export(ExportFormat),
+    Sort(GeneralSort),
+    /// Sets the search of the view
+    Filter(String),
+}
+
+/// Commands taking an argument, the rest are the actions by their names
+const ARGUMENT_COMMANDS: &[(&str, &str)] = &[
+    ("connect", "<name or address>"),
+    ("write", "[hex value]"),
+    ("export", "[csv|jsonl|bin]"),
+    ("sort", "[name|pinned|default]"),
+    ("filter", "<query>"),
+];
+
+impl Command {
+    /// Parses the command line without the leading ":", e.g. "sort name"
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (line, None),
+        };
+
+        match (name, argument) {
+            ("connect", Some(device)) => Ok(Command::Connect(device.to_owned())),
+            ("connect", None) => Err(Error::client("connect needs a device name or address")),
+            ("write", Some(value)) => Ok(Command::Write(value.to_owned())),
+            ("export", Some(format)) => ExportFormat::from_extension(format)
+                .map(Command::Export)
+                .ok_or_else(|| Error::client(format!("Unknown export format \"{format}\""))),
+            ("sort", Some("default")) => Ok(Command::Sort(GeneralSort::DefaultSort)),
+            ("sort", Some(sort)) => GeneralSort::from_str(sort, true)
+                .map(Command::Sort)
+                .map_err(|_| Error::client(format!("Unknown sort \"{sort}\""))),
+            ("filter", argument) => Ok(Command::Filter(argument.unwrap_or("").to_owned())),
+            (name, None) => Action::from_name(name)
+                .map(Command::Action)
+                .ok_or_else(|| Error::client(format!("Unknown command \"{name}\""))),
+            (name, Some(_)) => Err(Error::client(format!("{name} takes no arguments"))),
+        }
+    }
+}
+
+/// Characters of the query have to appear in the name in the same order, the less gaps the better
+fn fuzzy_score(query: &str, name: &str) -> Option<usize> {
+    let mut chars = name.char_indices();
+    let mut score = 0;
+    let mut last_match = None;
+
+    for query_char in query.chars() {
+        let (index, _) = chars.find(|(_, char)| char.eq_ignore_ascii_case(&query_char))?;
+        score += match last_match {
+            Some(last_match) => index - last_match - 1,
+            None => index,
+        };
+        last_match = Some(index);
+    }
+
+    Some(score)
+}
+
+/// Names of every command with the hint of its argument, the best matching first
+fn matching_commands(query: &str) -> Vec<(String, &'static str)> {
+    let mut commands = ARGUMENT_COMMANDS
+        .iter()
+        .map(|(name, hint)| (name.to_string(), *hint))
+        .chain(
+            Action::ALL
+                .iter()
+                .map(|action| (action.name(), ""))
+                .filter(|(name, _)| !ARGUMENT_COMMANDS.iter().any(|(other, _)| other == name)),
+        )
+        .filter_map(|(name, hint)| Some((fuzzy_score(query, &name)?, name, hint)))
+        .collect::<Vec<_>>();
+
+    commands.sort_by(|(a_score, a_name, _), (b_score, b_name, _)| {
+        a_score.cmp(b_score).then(a_name.len().cmp(&b_name.len()))
+    });
+
+    commands
+        .into_iter()
+        .map(|(_, name, hint)| (name, hint))
+        .collect()
+}
+
+/// Vim like ":" prompt running any action or command by its fuzzy matched name
+pub struct CommandPalette {
+    ctx: Arc<Ctx>,
+    input: Option<String>,
+    list_state: ListState,
+    /// The highlighted command was picked by the user rather than being the best match
+    highlight_moved: bool,
+}
+
+impl CommandPalette {
+    pub fn new(ctx: Arc<Ctx>) -> Self {
+        Self {
+            ctx,
+            input: None,
+            list_state: ListState::default(),
+            highlight_moved: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.input.is_some()
+    }
+
+    pub fn open(&mut self) {
+        self.input = Some(String::new());
+        self.reset_highlight();
+    }
+
+    fn reset_highlight(&mut self) {
+        self.list_state.select(Some(0));
+        self.highlight_moved = false;
+    }
+
+    /// Name typed so far and the rest of the line with the arguments
+    fn split_input(&self) -> (&str, &str) {
+        let input = self.input.as_deref().unwrap_or("").trim_start();
+        input.split_once(' ').unwrap_or((input, ""))
+    }
+
+    fn highlighted_command(&self) -> Option<String> {
+        let (name, _) = self.split_input();
+        let commands = matching_commands(name);
+        let index = self.list_state.selected().unwrap_or(0);
+
+        commands.into_iter().nth(index).map(|(command, _)| command)
+    }
+
+    /// The exact name wins over the highlighted match so the full commands work without looking,
+    /// the highlighted match runs only if it was picked or matches closely. Empty line runs nothing.
+    fn submit(&mut self) -> Option<Result<Command>> {
+        let (name, argument) = self.split_input();
+        if name.is_empty() {
+            self.input = None;
+            return None;
+        }
+
+        let name = if ARGUMENT_COMMANDS
+            .iter()
+            .any(|(command, _)| *command == name)
+            || Action::from_name(name).is_some()
+        {
+            Ok(name.to_owned())
+        } else {
+            self.highlighted_command()
+                .filter(|command| {
+                    self.highlight_moved
+                        || fuzzy_score(name, command)
+                            .is_some_and(|score| score <= MAX_IMPLICIT_MATCH_SCORE)
+                })
+                .ok_or_else(|| Error::client(format!("Unknown command \"{name}\"")))
+        };
+
+        let line = name.map(|name| format!("{name} {argument}"));
+        self.input = None;
+
+        Some(line.and_then(|line| Command::parse(&line)))
+    }
+
+    /// Returns the command to run once the line is submitted
+    pub fn handle_input(&mut self, key: &KeyEvent) -> Option<Command> {
+        let commands_count = matching_commands(self.split_input().0)
+            .len()
+            .min(MAX_VISIBLE_COMMANDS);
+        let highlighted = self.list_state.selected().unwrap_or(0);
+
+        match self.ctx.keymap.input_action(key, INPUT_ACTIONS) {
+            Some(Action::Cancel) => self.input = None,
+            Some(Action::Confirm) => match self.submit() {
+                Some(Ok(command)) => return Some(command),
+                Some(Err(e)) => {
+                    if let Ok(global_error) = self.ctx.global_error.lock().as_deref_mut() {
+                        *global_error = Some(e);
+                    }
+                }
+                None => (),
+            },
+            Some(Action::Complete) => {
+                if let Some(command) = self.highlighted_command() {
+                    self.input = Some(format!("{command} "));
+                    self.reset_highlight();
+                }
+            }
+            Some(Action::Down) if commands_count > 0 => {
+                self.list_state
+                    .select(Some((highlighted + 1) % commands_count));
+                self.highlight_moved = true;
+            }
+            Some(Action::Up) if commands_count > 0 => {
+                self.list_state
+                    .select(Some((highlighted + commands_count - 1) % commands_count));
+                self.highlight_moved = true;
+            }
+            _ if key.code == KeyCode::Backspace && self.input.as_deref() == Some("") => {
+                self.input = None
+            }
+            _ => {
+                let last_input = self.input.clone();
+                search_input::handle_search_input(&mut self.input, key);
+
+                if self.input != last_input {
+                    self.reset_highlight();
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn render(&mut self, f: &mut Frame<TerminalBackend>) {
+        let Some(input) = self.input.clone() else {
+            return;
+        };
+
+        let (name, argument) = self.split_input();
+        let commands = if argument.is_empty() {
+            matching_commands(name)
+        } else {
+            vec![]
+        };
+
+        let size = f.size();
+        let width = (size.width * 3 / 5).max(40).min(size.width);
+        let height = (commands.len().min(MAX_VISIBLE_COMMANDS) as u16 + 3).min(size.height);
+        let area =
+            Rect::new((size.width - width) / 2, size.height / 5, width, height).intersection(size);
+
+        let items = std::iter::once(ListItem::new(Line::from(vec![
+            Span::styled(":", Style::default().fg(Color::Cyan)),
+            Span::from(input),
+        ])))
+        .chain(
+            commands
+                .iter()
+                .take(MAX_VISIBLE_COMMANDS)
+                .map(|(command, hint)| {
+                    let key = Action::from_name(command)
+                        .map(|action| self.ctx.keymap.label(&[action]))
+                        .unwrap_or_default();
+
+                    ListItem::new(Line::from(vec![
+                        Span::from(format!("  {command} ")),
+                        Span::styled(*hint, Style::default().add_modifier(Modifier::DIM)),
+                        Span::styled(
+                            if key.is_empty() {
+                                key
+                            } else {
+                                format!("  [{key}]")
+                            },
+                            Style::default().fg(Color::Gray),
+                        ),
+                    ]))
+                }),
+        )
+        .collect::<Vec<_>>();
+
+        // the input takes the first row of the list
+        let mut list_state = ListState::default();
+        if !commands.is_empty() {
+            list_state.select(self.list_state.selected().map(|index| index + 1));
+        }
+
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(
+            List::new(items)
+                .block(tui::widgets::Block::from(BlendrBlock {
+                    focused: true,
+                    route_active: true,
+                    title: "Command",
+                    ..Default::default()
+                }))
+                .highlight_style(
+                    Style::default()
+                        .bg(Color::Blue)
+                        .fg(Color::Black)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            area,
+            &mut list_state,
+        );
+    }
+}
+
+#[test]
+fn parses_commands() {
+    assert_eq!(
+        Command::parse("connect Smart Toothbrush").unwrap(),
+        Command::Connect(String::from("Smart Toothbrush"))
+    );
+    assert_eq!(
+        Command::parse("write 01ff").unwrap(),
+        Command::Write(String::from("01ff"))
+    );
+    assert_eq!(
+        Command::parse("export csv").unwrap(),
+        Command::Export(ExportFormat::Csv)
+    );
+    assert_eq!(
+        Command::parse("sort default").unwrap(),
+        Command::Sort(GeneralSort::DefaultSort)
+    );
+    assert_eq!(
+        Command::parse(" filter rssi>-70").unwrap(),
+        Command::Filter(String::from("rssi>-70"))
+    );
+    assert_eq!(
+        Command::parse("disconnect").unwrap(),
+        Command::Action(Action::Disconnect)
+    );
+    assert_eq!(
+        Command::parse("export").unwrap(),
+        Command::Action(Action::Export)
+    );
+    assert!(Command::parse("export pdf").is_err());
+    assert!(Command::parse("connect").is_err());
+    assert!(Command::parse("disconnect now").is_err());
+
+    assert_eq!(matching_commands("disc")[0].0, "disconnect");
+    assert_eq!(matching_commands("hu")[0].0, "hide_unknown");
+    assert!(matching_commands("zzz").is_empty());
+}
+
+#[test]
+fn submits_only_picked_or_close_matches() {
+    use crate::bluetooth::simulator::{simulated_ctx, TEST_SIMULATION};
+
+    let mut palette = CommandPalette::new(Arc::new(simulated_ctx(TEST_SIMULATION, &[])));
+    let mut submit = |input: &str, moves: usize| {
+        palette.open();
+        palette.input = Some(input.to_owned());
+        palette.list_state.select(Some(moves));
+        palette.highlight_moved = moves > 0;
+        palette.submit()
+    };
+
+    assert!(submit("", 0).is_none());
+    assert!(submit("  ", 0).is_none());
+    assert_eq!(
+        submit("disc", 0).unwrap().unwrap(),
+        Command::Action(Action::Disconnect)
+    );
+    assert_eq!(
+        submit("sort name", 0).unwrap().unwrap(),
+        Command::Sort(GeneralSort::Name)
+    );
+    assert!(submit("zzz", 0).unwrap().is_err());
+    // too loose to run without picking it
+    assert!(submit("hu", 0).unwrap().is_err());
+
+    let picked = matching_commands("hu")[1].0.clone();
+    assert_eq!(
+        submit("hu", 1).unwrap().unwrap(),
+        Command::parse(&picked).unwrap()
+    );
+}
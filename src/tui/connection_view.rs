@@ -1,20 +1,30 @@
 use crate::{
-    bluetooth::ConnectedCharacteristic,
-    route::{CharacteristicValue, Route},
+    bluetooth::{
+        self, connections,
+        decoder::DecodedField,
+        gatt_decoders::decode_standard,
+        payload::{parse_write_payload, to_hex, try_parse_numeric_value, WriteFormat},
+        ConnectedCharacteristic,
+    },
+    error::Error,
+    export::{self, ExportFormat},
+    route::{CharacteristicValue, Route, ValueDirection, ValueUpdateMode},
+    session::{self, ValueSource},
     tui::{
+        command_palette::Command,
+        keymap::Action,
         ui::{
             block::{self, Title},
-            BlendrBlock,
+            format_axis_value, hex_dump_lines, render_time_series, search_input, BlendrBlock,
         },
         AppRoute, HandleKeydownResult,
     },
     Ctx,
 };
-use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+use byteorder::LittleEndian;
 use copypasta::{ClipboardContext, ClipboardProvider};
-use crossterm::event::KeyCode;
 use lazy_static::__Deref;
-use std::{io::Cursor, sync::Arc};
+use std::{collections::VecDeque, sync::Arc};
 use tui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
@@ -22,6 +32,32 @@ use tui::{
     widgets::{Paragraph, Wrap},
 };
 
+pub(super) const KEY_ACTIONS: &[Action] = &[
+    Action::FloatNumbers,
+    Action::UnsignedNumbers,
+    Action::Graph,
+    Action::ChartZoomIn,
+    Action::ChartZoomOut,
+    Action::PreviousValue,
+    Action::NextValue,
+    Action::LatestValue,
+    Action::CopyUuid,
+    Action::CopyServiceUuid,
+    Action::Write,
+    Action::Export,
+    Action::UpdateMode,
+];
+
+pub(super) const WRITE_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::SwitchFormat,
+    Action::SwitchEndianness,
+    Action::Confirm,
+];
+
+pub(super) const EXPORT_ACTIONS: &[Action] =
+    &[Action::Cancel, Action::SwitchFormat, Action::Confirm];
+
 pub struct ConnectionView {
     ctx: Arc<Ctx>,
     float_numbers: bool,
@@ -29,37 +65,24 @@ pub struct ConnectionView {
     highlight_copy_char_renders_delay_stack: u8,
     highlight_copy_service_renders_delay_stack: u8,
     clipboard: Option<ClipboardContext>,
-}
-
-fn try_parse_numeric_value<T: ByteOrder>(
-    buf: &mut Cursor<&[u8]>,
-    float: bool,
-    unsigned: bool,
-) -> std::io::Result<(&'static str, String)> {
-    Ok(match buf.get_ref().len() {
-        // 1 byte
-        1 if unsigned => ("u8", buf.read_u8()?.to_string()),
-        1 => ("i8", buf.read_i8()?.to_string()),
-        // 4 bytes
-        4 if unsigned => ("u32", buf.read_u32::<T>()?.to_string()),
-        4 if float => ("f32", buf.read_f32::<T>()?.to_string()),
-        4 => ("i32", buf.read_i32::<T>()?.to_string()),
-        // 8 bytes
-        8 if unsigned => ("u64", buf.read_u64::<T>()?.to_string()),
-        8 if float => ("f64", buf.read_f64::<T>()?.to_string()),
-        8 => ("i64", buf.read_i64::<T>()?.to_string()),
-        // 16 bytes
-        16 if unsigned => ("u128", buf.read_u128::<T>()?.to_string()),
-        16 => ("i128", buf.read_i128::<T>()?.to_string()),
-        _ => return Err(std::io::ErrorKind::InvalidData.into()),
-    })
+    write_mode: bool,
+    write_input: Option<String>,
+    write_format: WriteFormat,
+    write_big_endian: bool,
+    /// Path of the export file, prompt is open when set
+    export_input: Option<String>,
+    export_format: ExportFormat,
+    export_status: Option<String>,
+    chart_visible: bool,
+    /// Amount of the latest values plotted on the chart
+    chart_window: usize,
 }
 
 fn render_title_with_navigation_controls(
     area: &tui::layout::Rect,
     char: &ConnectedCharacteristic,
     historical_view_index: Option<usize>,
-    history: &[CharacteristicValue],
+    history: &VecDeque<CharacteristicValue>,
 ) -> Title<'static> {
     const PREVIOUS_BUTTON: &str = " [<- Previous]  ";
     const PREVIOUS_BUTTON_DENSE: &str = " [<-]  ";
@@ -103,31 +126,448 @@ fn render_title_with_navigation_controls(
     Title::new(spans)
 }
 
+const MIN_CHART_WINDOW: usize = 10;
+const MAX_CHART_WINDOW: usize = 10_000;
+
+/// Plotted number of the value: the first numeric decoded field or the inferred numeric value
+fn chart_value(
+    ctx: &Ctx,
+    char: &ConnectedCharacteristic,
+    data: &[u8],
+    float: bool,
+    unsigned: bool,
+) -> Option<f64> {
+    let decoded = match ctx.decoders.get(&char.uuid) {
+        Some(schema) => Some(schema.decode(data)),
+        None => decode_standard(&char.uuid, data).and_then(Result::ok),
+    };
+
+    if let Some(fields) = decoded {
+        return fields
+            .into_iter()
+            .find_map(|field| field.value.ok()?.parse::<f64>().ok());
+    }
+
+    try_parse_numeric_value::<LittleEndian>(&mut std::io::Cursor::new(data), float, unsigned)
+        .ok()
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// Renders decoded fields as table rows with aligned name, value and unit columns
+fn render_decoded_fields(fields: Vec<DecodedField>) -> Vec<Line<'static>> {
+    let name_width = fields
+        .iter()
+        .map(|field| field.name.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    fields
+        .into_iter()
+        .map(|field| {
+            let mut spans = vec![Span::styled(
+                format!("{:name_width$}  ", field.name),
+                Style::default().add_modifier(Modifier::BOLD),
+            )];
+
+            match field.value {
+                Ok(value) => spans.push(Span::styled(value, Style::default().fg(Color::Green))),
+                Err(e) => spans.push(Span::styled(e, Style::default().fg(Color::Red))),
+            }
+
+            if let Some(unit) = field.unit {
+                spans.push(Span::styled(
+                    format!(" {unit}"),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+
+            Line::from(spans)
+        })
+        .collect()
+}
+
+impl ConnectionView {
+    fn handle_write_input(&mut self, key: &crossterm::event::KeyEvent) -> HandleKeydownResult {
+        match self.ctx.keymap.input_action(key, WRITE_ACTIONS) {
+            Some(Action::Cancel) => self.write_mode = false,
+            Some(Action::SwitchFormat) => self.write_format = self.write_format.next(),
+            Some(Action::SwitchEndianness) => self.write_big_endian = !self.write_big_endian,
+            Some(Action::Confirm) => self.submit_write(),
+            _ => search_input::handle_search_input(&mut self.write_input, key),
+        }
+
+        // while typing the value all the keys are belonging to the input
+        HandleKeydownResult::Handled
+    }
+
+    fn submit_write(&mut self) {
+        let active_route = self.ctx.get_active_route();
+        let Route::CharacteristicView {
+            peripheral,
+            characteristic,
+            history,
+            historical_view_index,
+            ..
+        } = active_route.deref()
+        else {
+            return;
+        };
+
+        let data = match parse_write_payload(
+            self.write_input.as_deref().unwrap_or(""),
+            self.write_format,
+            self.write_big_endian,
+        ) {
+            Ok(data) => data,
+            Err(e) => {
+                // keep the input so the value can be fixed instead of retyped
+                if let Ok(global_error) = self.ctx.global_error.lock().as_deref_mut() {
+                    *global_error = Some(e);
+                }
+                return;
+            }
+        };
+
+        let ctx = Arc::clone(&self.ctx);
+        let history = Arc::clone(history);
+        let peripheral = peripheral.peripheral.clone();
+        let characteristic = characteristic.clone();
+
+        tokio::spawn(async move {
+            match bluetooth::write_with_timeout(
+                peripheral.ble_peripheral.as_ref(),
+                &characteristic.ble_characteristic,
+                &data,
+            )
+            .await
+            {
+                Ok(()) => {
+                    session::record_value(
+                        &ctx,
+                        &peripheral,
+                        &characteristic,
+                        ValueSource::Write,
+                        &data,
+                    );
+
+                    let value = CharacteristicValue {
+                        time: chrono::Local::now(),
+                        data,
+                        direction: ValueDirection::Outgoing,
+                    };
+
+                    export::record_value(&ctx, peripheral.address, &characteristic, &value);
+                    connections::push_history(&ctx, &history, value);
+                }
+                Err(e) => {
+                    tracing::error!(?e, "Failed to write characteristic value.");
+
+                    if let Ok(global_error) = ctx.global_error.lock().as_deref_mut() {
+                        *global_error = Some(e);
+                    }
+                }
+            }
+        });
+
+        historical_view_index.annulate();
+        self.write_input = None;
+    }
+
+    /// Writes the hex value right away keeping the format picked for the write input
+    fn write_command(&mut self, value: &str) -> HandleKeydownResult {
+        let writable = match self.ctx.get_active_route().deref() {
+            Route::CharacteristicView { characteristic, .. } => {
+                bluetooth::is_writable(characteristic.ble_characteristic.properties)
+            }
+            _ => return HandleKeydownResult::Continue,
+        };
+
+        let result = if writable {
+            parse_write_payload(value, WriteFormat::Hex, false).map(|_| ())
+        } else {
+            Err(Error::client("Characteristic is not writable"))
+        };
+
+        match result {
+            Ok(()) => {
+                let write_format = std::mem::replace(&mut self.write_format, WriteFormat::Hex);
+                self.write_input = Some(value.to_owned());
+                self.submit_write();
+                self.write_format = write_format;
+            }
+            Err(e) => {
+                if let Ok(global_error) = self.ctx.global_error.lock().as_deref_mut() {
+                    *global_error = Some(e);
+                }
+            }
+        }
+
+        HandleKeydownResult::Handled
+    }
+
+    /// Exports the history to the default file in the format
+    fn export_command(&mut self, format: ExportFormat) -> HandleKeydownResult {
+        let path = match self.ctx.get_active_route().deref() {
+            Route::CharacteristicView {
+                peripheral,
+                characteristic,
+                ..
+            } => export::default_export_file_name(
+                peripheral.peripheral.address,
+                characteristic,
+                format,
+            ),
+            _ => return HandleKeydownResult::Continue,
+        };
+
+        self.export_format = format;
+        self.export_input = Some(path);
+        self.submit_export();
+
+        HandleKeydownResult::Handled
+    }
+
+    fn handle_export_input(&mut self, key: &crossterm::event::KeyEvent) -> HandleKeydownResult {
+        match self.ctx.keymap.input_action(key, EXPORT_ACTIONS) {
+            Some(Action::Cancel) => self.export_input = None,
+            Some(Action::SwitchFormat) => {
+                let previous_extension = format!(".{}", self.export_format.extension());
+                self.export_format = self.export_format.next();
+
+                if let Some(path) = self.export_input.as_mut() {
+                    if let Some(stem) = path.strip_suffix(&previous_extension) {
+                        *path = format!("{stem}.{}", self.export_format.extension());
+                    }
+                }
+            }
+            Some(Action::Confirm) => self.submit_export(),
+            _ => search_input::handle_search_input(&mut self.export_input, key),
+        }
+
+        HandleKeydownResult::Handled
+    }
+
+    fn submit_export(&mut self) {
+        let Some(path) = self.export_input.take() else {
+            return;
+        };
+
+        let active_route = self.ctx.get_active_route();
+        let Route::CharacteristicView {
+            peripheral,
+            characteristic,
+            history,
+            ..
+        } = active_route.deref()
+        else {
+            return;
+        };
+
+        let result = history.read().map_err(Error::from).and_then(|history| {
+            export::export_history(
+                std::path::Path::new(&path),
+                self.export_format,
+                peripheral.peripheral.address,
+                characteristic,
+                history.iter(),
+            )
+        });
+
+        match result {
+            Ok(count) => self.export_status = Some(format!("Exported {count} values to {path}")),
+            Err(e) => {
+                tracing::error!(?e, "Failed to export characteristic history.");
+
+                if let Ok(global_error) = self.ctx.global_error.lock().as_deref_mut() {
+                    *global_error = Some(e);
+                }
+            }
+        }
+    }
+
+    fn render_chart(
+        &self,
+        area: tui::layout::Rect,
+        route_active: bool,
+        characteristic: &ConnectedCharacteristic,
+        history: &VecDeque<CharacteristicValue>,
+        f: &mut tui::Frame<super::TerminalBackend>,
+    ) {
+        // decode from the newest value so only the plotted window is decoded every frame
+        let mut window = history
+            .iter()
+            .rev()
+            .filter(|value| value.direction == ValueDirection::Incoming)
+            .filter_map(|value| {
+                chart_value(
+                    &self.ctx,
+                    characteristic,
+                    &value.data,
+                    self.float_numbers,
+                    self.unsigned_numbers,
+                )
+                .map(|number| (value.time, number))
+            })
+            .take(self.chart_window)
+            .collect::<Vec<_>>();
+        window.reverse();
+
+        if window.is_empty() {
+            f.render_widget(
+                Paragraph::new("No numeric values to plot yet").block(tui::widgets::Block::from(
+                    BlendrBlock {
+                        route_active,
+                        title: format!("Chart (last {} values)", self.chart_window),
+                        ..Default::default()
+                    },
+                )),
+                area,
+            );
+            return;
+        }
+
+        render_time_series(f, area, route_active, &window, Color::Green, |stats| {
+            format!(
+                "Last {} of {} values  min {}  max {}  avg {}",
+                window.len(),
+                self.chart_window,
+                format_axis_value(stats.min),
+                format_axis_value(stats.max),
+                format_axis_value(stats.avg)
+            )
+        });
+    }
+
+    fn render_export_input(&self, route_active: bool, history_len: usize) -> Paragraph<'static> {
+        let mut title = format!("Export history as {}", self.export_format.extension());
+        if history_len >= self.ctx.args.history_limit {
+            title.push_str(&format!(
+                ", only the latest {history_len} values are kept by --history-limit"
+            ));
+        }
+
+        Paragraph::new(Line::from(vec![
+            Span::styled(" >", Style::default().fg(Color::DarkGray)),
+            Span::from(self.export_input.clone().unwrap_or_default()),
+        ]))
+        .block(tui::widgets::Block::from(BlendrBlock {
+            route_active,
+            focused: true,
+            title,
+            ..Default::default()
+        }))
+    }
+
+    fn render_write_input(&self, route_active: bool) -> Paragraph<'static> {
+        let input = self.write_input.as_deref().unwrap_or("");
+        let payload = parse_write_payload(input, self.write_format, self.write_big_endian);
+
+        let mut title = vec![Span::from(format!(" Write {}", self.write_format.label()))];
+        if self.write_format.is_endian_sensitive() {
+            title.push(Span::from(if self.write_big_endian {
+                " (big endian)"
+            } else {
+                " (little endian)"
+            }));
+        }
+
+        match &payload {
+            Ok(data) => title.push(Span::styled(
+                format!(" -> {} ", to_hex(data)),
+                Style::default().fg(Color::Green),
+            )),
+            Err(e) if !input.is_empty() => title.push(Span::styled(
+                format!(" {e} "),
+                Style::default().fg(Color::Red),
+            )),
+            Err(_) => title.push(Span::from(" ")),
+        }
+
+        Paragraph::new(Line::from(vec![
+            Span::styled(" >", Style::default().fg(Color::DarkGray)),
+            Span::from(input.to_owned()),
+        ]))
+        .block(tui::widgets::Block::from(BlendrBlock {
+            route_active,
+            focused: true,
+            title: Title::new(title),
+            color: (payload.is_err() && !input.is_empty()).then_some(Color::Red),
+            ..Default::default()
+        }))
+    }
+}
+
 impl AppRoute for ConnectionView {
     fn new(ctx: std::sync::Arc<crate::Ctx>) -> Self
     where
         Self: Sized,
     {
         ConnectionView {
+            chart_visible: false,
+            chart_window: ctx
+                .args
+                .chart_window
+                .clamp(MIN_CHART_WINDOW, MAX_CHART_WINDOW),
             ctx,
             float_numbers: false,
             unsigned_numbers: false,
             highlight_copy_char_renders_delay_stack: 0,
             highlight_copy_service_renders_delay_stack: 0,
             clipboard: ClipboardContext::new().ok(),
+            write_mode: false,
+            write_input: None,
+            write_format: WriteFormat::default(),
+            write_big_endian: false,
+            export_input: None,
+            export_format: ExportFormat::default(),
+            export_status: None,
         }
     }
 
     fn handle_input(&mut self, key: &crossterm::event::KeyEvent) -> HandleKeydownResult {
-        match key.code {
-            KeyCode::Char('f') => {
+        if self.write_mode {
+            return self.handle_write_input(key);
+        }
+
+        if self.export_input.is_some() {
+            return self.handle_export_input(key);
+        }
+
+        match self.ctx.keymap.action(key, KEY_ACTIONS) {
+            Some(action) => self.handle_command(&Command::Action(action)),
+            None => HandleKeydownResult::Continue,
+        }
+    }
+
+    fn handle_command(&mut self, command: &Command) -> HandleKeydownResult {
+        let action = match command {
+            Command::Action(action) => Some(*action),
+            Command::Write(value) => return self.write_command(value),
+            Command::Export(format) => return self.export_command(*format),
+            _ => return HandleKeydownResult::Continue,
+        };
+
+        match action {
+            Some(Action::FloatNumbers) => {
                 self.float_numbers = !self.float_numbers;
                 return HandleKeydownResult::Handled;
             }
-            KeyCode::Char('u') => {
+            Some(Action::UnsignedNumbers) => {
                 self.unsigned_numbers = !self.unsigned_numbers;
                 return HandleKeydownResult::Handled;
             }
+            Some(Action::Graph) => {
+                self.chart_visible = !self.chart_visible;
+                return HandleKeydownResult::Handled;
+            }
+            Some(Action::ChartZoomIn) if self.chart_visible => {
+                self.chart_window = (self.chart_window * 2).min(MAX_CHART_WINDOW);
+                return HandleKeydownResult::Handled;
+            }
+            Some(Action::ChartZoomOut) if self.chart_visible => {
+                self.chart_window = (self.chart_window / 2).max(MIN_CHART_WINDOW);
+                return HandleKeydownResult::Handled;
+            }
             _ => (),
         }
 
@@ -144,56 +584,106 @@ impl AppRoute for ConnectionView {
             };
 
             match (
-                key.code,
+                action,
                 history.read().ok().as_ref(),
                 historical_view_index.deref().read(),
             ) {
-                (KeyCode::Left, _, Some(current_historical_index)) => {
-                    if current_historical_index >= 1 {
-                        update_index(current_historical_index - 1);
-                    }
+                (Some(Action::PreviousValue), _, Some(current_historical_index))
+                    if current_historical_index >= 1 =>
+                {
+                    update_index(current_historical_index - 1);
                 }
-                (KeyCode::Left, Some(history), None) => {
+                (Some(Action::PreviousValue), Some(history), None) => {
                     update_index(history.len().saturating_sub(1));
                 }
-                (KeyCode::Char('l'), _, _) => historical_view_index.annulate(),
-                (KeyCode::Right, Some(history), Some(current_historical_index))
+                // only while browsing the history, otherwise the key belongs to the device view
+                (Some(Action::LatestValue), _, Some(_)) => {
+                    historical_view_index.annulate();
+                    return HandleKeydownResult::Handled;
+                }
+                (Some(Action::NextValue), Some(history), Some(current_historical_index))
                     if history.len() > 2 && current_historical_index == history.len() - 2 =>
                 {
                     historical_view_index.annulate();
                 }
-                (KeyCode::Right, Some(history), Some(current_historical_index)) => {
-                    if history.len() > current_historical_index {
-                        update_index(current_historical_index + 1);
-                    }
+                (Some(Action::NextValue), Some(history), Some(current_historical_index))
+                    if history.len() > current_historical_index =>
+                {
+                    update_index(current_historical_index + 1);
                 }
                 _ => (),
             }
 
-            if matches!(key.code, KeyCode::Left | KeyCode::Right) {
+            if matches!(action, Some(Action::PreviousValue | Action::NextValue)) {
                 // on this view we always handing arrows as history navigation and preventing other view's actions
                 return HandleKeydownResult::Handled;
             }
         }
 
         match (active_route.deref(), self.clipboard.as_mut()) {
-            (Route::CharacteristicView { characteristic, .. }, Some(clipboard)) => match key.code {
-                KeyCode::Char('c') => {
+            (Route::CharacteristicView { characteristic, .. }, Some(clipboard)) => match action {
+                Some(Action::CopyUuid) => {
                     let _ = clipboard.set_contents(characteristic.uuid.to_string());
                     self.highlight_copy_char_renders_delay_stack = 4;
+                    return HandleKeydownResult::Handled;
                 }
-                KeyCode::Char('s') => {
+                Some(Action::CopyServiceUuid) => {
                     let _ = clipboard.set_contents(characteristic.service_uuid.to_string());
                     self.highlight_copy_service_renders_delay_stack = 4;
+                    return HandleKeydownResult::Handled;
                 }
                 _ => (),
             },
             _ => (),
         }
 
+        if let Route::CharacteristicView {
+            peripheral,
+            characteristic,
+            history,
+            historical_view_index,
+            update_mode,
+        } = active_route.deref()
+        {
+            let properties = characteristic.ble_characteristic.properties;
+            match action {
+                Some(Action::Write) if bluetooth::is_writable(properties) => {
+                    self.write_mode = true;
+                    return HandleKeydownResult::Handled;
+                }
+                Some(Action::Export) => {
+                    self.export_status = None;
+                    self.export_input = Some(export::default_export_file_name(
+                        peripheral.peripheral.address,
+                        characteristic,
+                        self.export_format,
+                    ));
+                    return HandleKeydownResult::Handled;
+                }
+                Some(Action::UpdateMode) if ValueUpdateMode::can_toggle(properties) => {
+                    let route = Route::CharacteristicView {
+                        peripheral: peripheral.clone(),
+                        characteristic: characteristic.clone(),
+                        history: Arc::clone(history),
+                        historical_view_index: Arc::clone(historical_view_index),
+                        update_mode: update_mode.toggle(),
+                    };
+
+                    drop(active_route);
+                    route.navigate(&self.ctx);
+                    return HandleKeydownResult::Handled;
+                }
+                _ => (),
+            }
+        }
+
         HandleKeydownResult::Continue
     }
 
+    fn is_typing(&self) -> bool {
+        self.write_mode || self.export_input.is_some()
+    }
+
     fn render(
         &mut self,
         area: tui::layout::Rect,
@@ -201,15 +691,16 @@ impl AppRoute for ConnectionView {
         f: &mut tui::Frame<super::TerminalBackend>,
     ) -> crate::error::Result<()> {
         let active_route = self.ctx.active_route.read()?;
-        let (_, characteristic, history, historical_view_index) =
+        let (characteristic, history, historical_view_index, update_mode) =
             if let Route::CharacteristicView {
-                peripheral,
                 characteristic,
                 history,
                 historical_view_index,
+                update_mode,
+                ..
             } = active_route.deref()
             {
-                (peripheral, characteristic, history, historical_view_index)
+                (characteristic, history, historical_view_index, *update_mode)
             } else {
                 tracing::error!(
                     "ConnectionView::render called when active route is not CharacteristicView"
@@ -223,10 +714,17 @@ impl AppRoute for ConnectionView {
 
         let active_value = match historical_index {
             Some(index) => history.get(index),
-            None => history.last(),
+            None => history.back(),
         };
 
         let mut text = vec![];
+        if let Some(status) = self.export_status.as_ref() {
+            text.push(Line::from(Span::styled(
+                status.clone(),
+                Style::default().fg(Color::Green),
+            )));
+        }
+
         if let Some(value) = active_value.as_ref() {
             text.push(Line::from(""));
 
@@ -239,11 +737,20 @@ impl AppRoute for ConnectionView {
                         index + 1,
                         history.len()
                     )
+                } else if value.direction == ValueDirection::Outgoing {
+                    "Latest value written".to_owned()
                 } else {
                     "Latest value received".to_owned()
                 },
             )));
 
+            if value.direction == ValueDirection::Outgoing {
+                text.push(Line::from(Span::styled(
+                    "Outgoing value sent to the device",
+                    Style::default().fg(Color::Magenta),
+                )));
+            }
+
             text.push(Line::from(""));
 
             if let Ok(string_value) = String::from_utf8(value.data.clone()) {
@@ -257,6 +764,30 @@ impl AppRoute for ConnectionView {
                 }
             }
 
+            // user provided schema wins over the standard decoder of the same characteristic
+            if let Some(schema) = self.ctx.decoders.get(&characteristic.uuid) {
+                text.push(Line::from("Decoded fields"));
+                text.extend(render_decoded_fields(schema.decode(&value.data)));
+                text.push(Line::from(""));
+            } else if let Some(decoded) = decode_standard(&characteristic.uuid, &value.data) {
+                text.push(Line::from(format!(
+                    "Decoded as {}",
+                    characteristic
+                        .standard_gatt_char_name
+                        .unwrap_or("standard characteristic")
+                )));
+
+                match decoded {
+                    Ok(fields) => text.extend(render_decoded_fields(fields)),
+                    Err(e) => text.push(Line::from(Span::styled(
+                        format!("Malformed value: {e}"),
+                        Style::default().fg(Color::Red),
+                    ))),
+                }
+
+                text.push(Line::from(""));
+            }
+
             let mut cursor = std::io::Cursor::new(&value.data[..]);
             if let Ok((type_label, value)) = try_parse_numeric_value::<LittleEndian>(
                 &mut cursor,
@@ -276,36 +807,33 @@ impl AppRoute for ConnectionView {
                 text.push(Line::from(""));
             }
 
-            let mut hexyl_output_buf = Vec::new();
-            {
-                let mut writer = std::io::Cursor::new(&mut hexyl_output_buf);
-                let mut printer = hexyl::PrinterBuilder::new(&mut writer)
-                    .show_color(true)
-                    .num_panels(if area.width > 70 { 2 } else { 1 })
-                    .with_border_style(hexyl::BorderStyle::Unicode)
-                    .build();
-
-                printer.print_all(&value.data[..]).unwrap();
-            }
-
-            use ansi_to_tui::IntoText;
-            if let Ok(output) = hexyl_output_buf.into_text() {
-                text.extend(output);
-            } else {
-                tracing::error!(
-                    ?hexyl_output_buf,
-                    "Failed to parse and display hexyl output"
-                );
-            }
+            text.extend(hex_dump_lines(&value.data, area.width));
         } else {
             text.push(Line::from("No value received yet"));
         }
 
+        text.push(Line::from(""));
+        text.push(Line::from(Span::styled(
+            match update_mode {
+                ValueUpdateMode::Subscribe => "Subscribed to notifications".to_owned(),
+                ValueUpdateMode::Poll => {
+                    format!("Polling every {}ms", self.ctx.args.scan_interval)
+                }
+            },
+            Style::default().fg(Color::DarkGray),
+        )));
+
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
                 [
                     Constraint::Min(10),
+                    Constraint::Percentage(if self.chart_visible { 45 } else { 0 }),
+                    Constraint::Length(if self.write_mode || self.export_input.is_some() {
+                        3
+                    } else {
+                        0
+                    }),
                     Constraint::Length(if route_active { 3 } else { 0 }),
                 ]
                 .as_ref(),
@@ -330,39 +858,104 @@ impl AppRoute for ConnectionView {
 
         f.render_widget(paragraph, chunks[0]);
         if chunks[1].height > 0 {
+            self.render_chart(chunks[1], route_active, characteristic, &history, f);
+        }
+
+        if chunks[2].height > 0 && self.write_mode {
+            f.render_widget(self.render_write_input(route_active), chunks[2]);
+        } else if chunks[2].height > 0 {
+            f.render_widget(
+                self.render_export_input(route_active, history.len()),
+                chunks[2],
+            );
+        }
+
+        if chunks[3].height > 0 && self.write_mode {
+            f.render_widget(
+                block::render_help(
+                    Arc::clone(&self.ctx),
+                    [
+                        Some((&[Action::Confirm], "Send value", false)),
+                        Some((&[Action::SwitchFormat], "Change format", false)),
+                        self.write_format.is_endian_sensitive().then_some((
+                            &[Action::SwitchEndianness],
+                            "Big endian",
+                            self.write_big_endian,
+                        )),
+                        Some((&[Action::Cancel], "Cancel", false)),
+                    ],
+                ),
+                chunks[3],
+            );
+        } else if chunks[3].height > 0 && self.export_input.is_some() {
+            f.render_widget(
+                block::render_help(
+                    Arc::clone(&self.ctx),
+                    [
+                        Some((&[Action::Confirm], "Save file", false)),
+                        Some((&[Action::SwitchFormat], "Change format", false)),
+                        Some((&[Action::Cancel], "Cancel", false)),
+                    ],
+                ),
+                chunks[3],
+            );
+        } else if chunks[3].height > 0 {
             f.render_widget(
                 block::render_help(
                     Arc::clone(&self.ctx),
                     [
-                        Some(("<-", "Previous value", false)),
-                        Some(("->", "Next value", false)),
-                        Some(("d", "[D]isconnect from device", false)),
-                        Some(("u", "Parse numeric as [u]nsigned", self.unsigned_numbers)),
-                        Some(("f", "Parse numeric as [f]loats", self.float_numbers)),
+                        Some((&[Action::PreviousValue], "Previous value", false)),
+                        Some((&[Action::NextValue], "Next value", false)),
+                        Some((&[Action::Disconnect], "Disconnect from device", false)),
+                        Some((
+                            &[Action::UnsignedNumbers],
+                            "Parse numeric as unsigned",
+                            self.unsigned_numbers,
+                        )),
+                        Some((
+                            &[Action::FloatNumbers],
+                            "Parse numeric as floats",
+                            self.float_numbers,
+                        )),
+                        bluetooth::is_writable(characteristic.ble_characteristic.properties)
+                            .then_some((&[Action::Write], "Write value", false)),
+                        Some((&[Action::Export], "Export history", false)),
+                        Some((&[Action::Graph], "Show graph", self.chart_visible)),
+                        self.chart_visible.then_some((
+                            &[Action::ChartZoomIn, Action::ChartZoomOut],
+                            "Chart window",
+                            false,
+                        )),
+                        ValueUpdateMode::can_toggle(characteristic.ble_characteristic.properties)
+                            .then_some((
+                                &[Action::UpdateMode],
+                                "Notifications mode",
+                                update_mode == ValueUpdateMode::Subscribe,
+                            )),
                         historical_index.map(|_| {
                             (
-                                "l",
-                                "Go to the [l]atest values",
+                                &[Action::LatestValue][..],
+                                "Go to the latest values",
                                 self.highlight_copy_char_renders_delay_stack > 0,
                             )
                         }),
                         self.clipboard.as_ref().map(|_| {
                             (
-                                "c",
-                                "Copy [c]haracteristic UUID",
+                                &[Action::CopyUuid][..],
+                                "Copy characteristic UUID",
                                 self.highlight_copy_char_renders_delay_stack > 0,
                             )
                         }),
                         self.clipboard.as_ref().map(|_| {
                             (
-                                "s",
-                                "Copy [s]ervice UUID",
+                                &[Action::CopyServiceUuid][..],
+                                "Copy service UUID",
                                 self.highlight_copy_service_renders_delay_stack > 0,
                             )
                         }),
                     ],
                 ),
-                chunks[1],
+                chunks[3],
             );
         }
 
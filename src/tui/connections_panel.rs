@@ -0,0 +1,255 @@
+use crate::{
+    bluetooth::{
+        connections::{self, ConnectionState},
+        history::format_age,
+    },
+    error::Error,
+    route::Route,
+    tui::{
+        command_palette::Command,
+        keymap::Action,
+        ui::{block, BlendrBlock},
+        AppRoute, HandleKeydownResult,
+    },
+    Ctx,
+};
+use crossterm::event::KeyEvent;
+use std::sync::{atomic::AtomicU16, Arc};
+use tui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{List, ListItem, ListState, Paragraph},
+};
+
+pub fn connection_state_style(state: ConnectionState) -> Style {
+    Style::default().fg(match state {
+        ConnectionState::Connected => Color::Green,
+        ConnectionState::Reconnecting(_) => Color::Yellow,
+        ConnectionState::Failed => Color::Red,
+    })
+}
+
+pub(super) const KEY_ACTIONS: &[Action] = &[
+    Action::Down,
+    Action::Up,
+    Action::Confirm,
+    Action::Right,
+    Action::Disconnect,
+    Action::Cancel,
+    Action::Left,
+    Action::Connections,
+];
+
+/// Switches between the peripherals connected in the background
+pub struct ConnectionsPanel {
+    ctx: Arc<Ctx>,
+    list_state: ListState,
+}
+
+impl ConnectionsPanel {
+    fn highlighted(&self, connections_count: usize) -> Option<usize> {
+        if connections_count == 0 {
+            return None;
+        }
+
+        Some(
+            self.list_state
+                .selected()
+                .unwrap_or(0)
+                .min(connections_count - 1),
+        )
+    }
+}
+
+impl AppRoute for ConnectionsPanel {
+    fn new(ctx: Arc<Ctx>) -> Self
+    where
+        Self: Sized,
+    {
+        let mut list_state = ListState::default();
+        list_state.select(Some(0));
+
+        ConnectionsPanel { ctx, list_state }
+    }
+
+    fn handle_input(&mut self, key: &KeyEvent) -> HandleKeydownResult {
+        match self.ctx.keymap.action(key, KEY_ACTIONS) {
+            Some(action) => self.handle_command(&Command::Action(action)),
+            None => HandleKeydownResult::Continue,
+        }
+    }
+
+    fn handle_command(&mut self, command: &Command) -> HandleKeydownResult {
+        let Command::Action(action) = command else {
+            return HandleKeydownResult::Continue;
+        };
+
+        let (connections_count, highlighted_connection) = match self.ctx.connections.read() {
+            Ok(connections) => {
+                let highlighted = self.highlighted(connections.len());
+
+                (
+                    connections.len(),
+                    highlighted.map(|index| {
+                        (
+                            connections[index].id(),
+                            connections[index].peripheral.clone(),
+                            connections[index].state,
+                        )
+                    }),
+                )
+            }
+            Err(_) => (0, None),
+        };
+
+        match (action, highlighted_connection) {
+            (Action::Down, Some(_)) => {
+                let index = self.highlighted(connections_count).unwrap_or(0);
+                self.list_state
+                    .select(Some((index + 1) % connections_count));
+            }
+            (Action::Up, Some(_)) => {
+                let index = self.highlighted(connections_count).unwrap_or(0);
+                self.list_state
+                    .select(Some((index + connections_count - 1) % connections_count));
+            }
+            (Action::Confirm | Action::Right, Some((_, peripheral, state))) => match state {
+                ConnectionState::Connected => {
+                    Route::PeripheralConnectedView(peripheral).navigate(&self.ctx)
+                }
+                // the supervisor gave up, connecting again from the waiting view keeps the history
+                ConnectionState::Failed => Route::PeripheralWaitingView {
+                    peripheral: peripheral.peripheral,
+                    retry: Arc::new(AtomicU16::new(0)),
+                }
+                .navigate(&self.ctx),
+                ConnectionState::Reconnecting(attempt) => {
+                    if let Ok(global_error) = self.ctx.global_error.lock().as_deref_mut() {
+                        *global_error = Some(Error::client(format!(
+                            "{} is reconnecting (attempt #{attempt}), it can be opened once connected",
+                            peripheral.peripheral.name
+                        )));
+                    }
+                }
+            },
+            (Action::Disconnect, Some((id, ..))) => connections::spawn_disconnect(&self.ctx, id),
+            (Action::Cancel | Action::Left | Action::Connections, _) => {
+                Route::PeripheralList.navigate(&self.ctx);
+            }
+            _ => return HandleKeydownResult::Continue,
+        }
+
+        HandleKeydownResult::Handled
+    }
+
+    fn render(
+        &mut self,
+        area: tui::layout::Rect,
+        route_active: bool,
+        f: &mut tui::Frame<super::TerminalBackend>,
+    ) -> crate::error::Result<()> {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(5),
+                    Constraint::Length(if route_active { 3 } else { 0 }),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        let connections = self.ctx.connections.read()?;
+        let highlighted_state = self
+            .highlighted(connections.len())
+            .map(|index| connections[index].state);
+        let block = tui::widgets::Block::from(BlendrBlock {
+            route_active,
+            focused: true,
+            title: format!("Connections ({})", connections.len()),
+            ..Default::default()
+        });
+
+        if connections.is_empty() {
+            f.render_widget(
+                Paragraph::new(vec![
+                    Line::from(""),
+                    Line::from("No connected devices. Connect to a device with ->, press <- to get back to the list while keeping it connected."),
+                ])
+                .block(block)
+                .wrap(tui::widgets::Wrap { trim: true }),
+                chunks[0],
+            );
+        } else {
+            let highlighted = self.highlighted(connections.len());
+            self.list_state.select(highlighted);
+
+            let items = connections
+                .iter()
+                .map(|connection| {
+                    let peripheral = &connection.peripheral.peripheral;
+
+                    ListItem::new(vec![
+                        Line::from(vec![
+                            Span::styled(
+                                peripheral.name.clone(),
+                                Style::default().add_modifier(Modifier::BOLD),
+                            ),
+                            Span::styled(
+                                format!(" ({})", peripheral.address),
+                                Style::default().add_modifier(Modifier::DIM),
+                            ),
+                        ]),
+                        Line::from(vec![
+                            Span::styled(
+                                format!("  {}", connection.state),
+                                connection_state_style(connection.state),
+                            ),
+                            Span::raw(format!(
+                                ", connected {} ago, {} live characteristics, {} values",
+                                format_age(chrono::Local::now() - connection.connected_at),
+                                connection.watched_count(),
+                                connection.values_count()
+                            )),
+                        ]),
+                    ])
+                })
+                .collect::<Vec<_>>();
+
+            let list = List::new(items).block(block).highlight_style(
+                Style::default()
+                    .bg(Color::Blue)
+                    .fg(Color::Black)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+            f.render_stateful_widget(list, chunks[0], &mut self.list_state);
+        }
+
+        if chunks[1].height > 0 {
+            f.render_widget(
+                block::render_help(
+                    Arc::clone(&self.ctx),
+                    [
+                        Some((
+                            &[Action::Right],
+                            if highlighted_state == Some(ConnectionState::Failed) {
+                                "Reconnect"
+                            } else {
+                                "Open device"
+                            },
+                            false,
+                        )),
+                        Some((&[Action::Disconnect], "Disconnect", false)),
+                        Some((&[Action::Connections, Action::Cancel], "Close", false)),
+                        Some((&[Action::Down, Action::Up], "Navigate", false)),
+                    ],
+                ),
+                chunks[1],
+            );
+        }
+
+        Ok(())
+    }
+}
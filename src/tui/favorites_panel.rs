@@ -0,0 +1,262 @@
+use crate::{
+    bluetooth::backend::GattAttribute,
+    config::matches_device,
+    error::Error,
+    pins::PinnedCharacteristic,
+    route::Route,
+    tui::{
+        command_palette::Command,
+        keymap::Action,
+        ui::{block, BlendrBlock},
+        AppRoute, HandleKeydownResult,
+    },
+    Ctx,
+};
+use crossterm::event::{KeyCode, KeyEvent};
+use std::sync::{atomic::AtomicU16, Arc};
+use tui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{List, ListItem, ListState, Paragraph},
+};
+
+pub(super) const KEY_ACTIONS: &[Action] = &[
+    Action::Down,
+    Action::Up,
+    Action::Confirm,
+    Action::Right,
+    Action::Unpin,
+    Action::Cancel,
+    Action::Left,
+    Action::Favorites,
+];
+
+/// Jumps straight into the pinned characteristics, connecting to their device when needed
+pub struct FavoritesPanel {
+    ctx: Arc<Ctx>,
+    list_state: ListState,
+}
+
+impl FavoritesPanel {
+    fn highlighted(&self, pins_count: usize) -> Option<usize> {
+        if pins_count == 0 {
+            return None;
+        }
+
+        Some(self.list_state.selected().unwrap_or(0).min(pins_count - 1))
+    }
+
+    fn report(&self, result: crate::error::Result<()>) -> HandleKeydownResult {
+        if let Err(e) = result {
+            if let Ok(global_error) = self.ctx.global_error.lock().as_deref_mut() {
+                *global_error = Some(e);
+            }
+
+            return HandleKeydownResult::Errored;
+        }
+
+        HandleKeydownResult::Handled
+    }
+
+    /// Connects to the device of the pin, the peripheral view opens the characteristic once connected
+    fn jump(&self, pin: &PinnedCharacteristic) -> crate::error::Result<()> {
+        let peripheral = self
+            .ctx
+            .latest_scan
+            .read()?
+            .as_ref()
+            .and_then(|scan| {
+                scan.peripherals
+                    .iter()
+                    .find(|peripheral| {
+                        matches_device(&pin.device, &peripheral.name, &peripheral.address)
+                    })
+                    .cloned()
+            })
+            .ok_or_else(|| {
+                Error::client(format!(
+                    "Device {} of the pinned characteristic is not found in the latest scan",
+                    pin.device
+                ))
+            })?;
+
+        *self.ctx.open_on_connect.lock()? = Some((
+            peripheral.ble_peripheral.id(),
+            GattAttribute::Characteristic {
+                service_uuid: pin.service,
+                uuid: pin.characteristic,
+            },
+        ));
+
+        Route::PeripheralWaitingView {
+            retry: Arc::new(AtomicU16::new(0)),
+            peripheral,
+        }
+        .navigate(&self.ctx);
+
+        Ok(())
+    }
+}
+
+impl AppRoute for FavoritesPanel {
+    fn new(ctx: Arc<Ctx>) -> Self
+    where
+        Self: Sized,
+    {
+        let mut list_state = ListState::default();
+        list_state.select(Some(0));
+
+        FavoritesPanel { ctx, list_state }
+    }
+
+    fn handle_input(&mut self, key: &KeyEvent) -> HandleKeydownResult {
+        match (self.ctx.keymap.action(key, KEY_ACTIONS), key.code) {
+            (Some(action), _) => self.handle_command(&Command::Action(action)),
+            // the digits are the numbers shown in the list so they are not remappable
+            (None, KeyCode::Char(digit @ '1'..='9')) => {
+                let pin = self.ctx.pins.read().ok().and_then(|pins| {
+                    pins.characteristics()
+                        .get(digit as usize - '1' as usize)
+                        .cloned()
+                });
+
+                match pin {
+                    Some(pin) => self.report(self.jump(&pin)),
+                    None => HandleKeydownResult::Handled,
+                }
+            }
+            _ => HandleKeydownResult::Continue,
+        }
+    }
+
+    fn handle_command(&mut self, command: &Command) -> HandleKeydownResult {
+        let Command::Action(action) = command else {
+            return HandleKeydownResult::Continue;
+        };
+
+        let pins = match self.ctx.pins.read() {
+            Ok(pins) => pins.characteristics().to_vec(),
+            Err(_) => vec![],
+        };
+        let highlighted = self.highlighted(pins.len());
+
+        let result = match (action, highlighted) {
+            (Action::Down, Some(index)) => {
+                self.list_state.select(Some((index + 1) % pins.len()));
+                Ok(())
+            }
+            (Action::Up, Some(index)) => {
+                self.list_state
+                    .select(Some((index + pins.len() - 1) % pins.len()));
+                Ok(())
+            }
+            (Action::Confirm | Action::Right, Some(index)) => self.jump(&pins[index]),
+            (Action::Unpin, Some(index)) => self
+                .ctx
+                .pins
+                .write()
+                .map_err(Into::into)
+                .and_then(|mut pins| pins.unpin_characteristic(index)),
+            (Action::Cancel | Action::Left | Action::Favorites, _) => {
+                Route::PeripheralList.navigate(&self.ctx);
+                Ok(())
+            }
+            _ => return HandleKeydownResult::Continue,
+        };
+
+        self.report(result)
+    }
+
+    fn render(
+        &mut self,
+        area: tui::layout::Rect,
+        route_active: bool,
+        f: &mut tui::Frame<super::TerminalBackend>,
+    ) -> crate::error::Result<()> {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(5),
+                    Constraint::Length(if route_active { 3 } else { 0 }),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        let pins = self.ctx.pins.read()?;
+        let pinned = pins.characteristics();
+        let block = tui::widgets::Block::from(BlendrBlock {
+            route_active,
+            focused: true,
+            title: format!("Favorites ({})", pinned.len()),
+            ..Default::default()
+        });
+
+        if pinned.is_empty() {
+            f.render_widget(
+                Paragraph::new(vec![
+                    Line::from(""),
+                    Line::from("No pinned characteristics. Connect to a device and press p on a characteristic to pin it."),
+                ])
+                .block(block)
+                .wrap(tui::widgets::Wrap { trim: true }),
+                chunks[0],
+            );
+        } else {
+            self.list_state.select(self.highlighted(pinned.len()));
+
+            let items = pinned
+                .iter()
+                .enumerate()
+                .map(|(i, pin)| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled(
+                            if i < 9 {
+                                format!("{} ", i + 1)
+                            } else {
+                                String::from("  ")
+                            },
+                            Style::default().fg(Color::Cyan),
+                        ),
+                        Span::styled(
+                            pin.name.clone(),
+                            Style::default().add_modifier(Modifier::BOLD),
+                        ),
+                        Span::styled(
+                            format!(" on {}", pin.device),
+                            Style::default().add_modifier(Modifier::DIM),
+                        ),
+                    ]))
+                })
+                .collect::<Vec<_>>();
+
+            let list = List::new(items).block(block).highlight_style(
+                Style::default()
+                    .bg(Color::Blue)
+                    .fg(Color::Black)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+            f.render_stateful_widget(list, chunks[0], &mut self.list_state);
+        }
+
+        if chunks[1].height > 0 {
+            f.render_widget(
+                block::render_help(
+                    Arc::clone(&self.ctx),
+                    [
+                        Some((&[Action::Right], "Open characteristic (or 1-9)", false)),
+                        Some((&[Action::Unpin], "Unpin", false)),
+                        Some((&[Action::Favorites, Action::Cancel], "Close", false)),
+                        Some((&[Action::Down, Action::Up], "Navigate", false)),
+                    ],
+                ),
+                chunks[1],
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,439 @@
+use crate::error::{Error, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+
+/// Everything the keys do. Views pick the actions they understand in the order of priority,
+/// so the same key can mean different things in different views.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    Sort,
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Cancel,
+    Search,
+    CommandPalette,
+    Complete,
+    // device list
+    HideUnknown,
+    HideStale,
+    Inspect,
+    RestartScan,
+    Adapters,
+    Connections,
+    ScanFilter,
+    Pin,
+    Favorites,
+    // device view
+    Disconnect,
+    Collapse,
+    Expand,
+    ToggleCollapse,
+    CollapseAll,
+    ExpandAll,
+    ToggleNotifications,
+    // characteristic view
+    PreviousValue,
+    NextValue,
+    LatestValue,
+    FloatNumbers,
+    UnsignedNumbers,
+    Graph,
+    ChartZoomIn,
+    ChartZoomOut,
+    CopyUuid,
+    CopyServiceUuid,
+    Write,
+    Export,
+    UpdateMode,
+    SwitchFormat,
+    SwitchEndianness,
+    // panels
+    ToggleAdapter,
+    Unpin,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Quit,
+        Action::Sort,
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Confirm,
+        Action::Cancel,
+        Action::Search,
+        Action::CommandPalette,
+        Action::Complete,
+        Action::HideUnknown,
+        Action::HideStale,
+        Action::Inspect,
+        Action::RestartScan,
+        Action::Adapters,
+        Action::Connections,
+        Action::ScanFilter,
+        Action::Pin,
+        Action::Favorites,
+        Action::Disconnect,
+        Action::Collapse,
+        Action::Expand,
+        Action::ToggleCollapse,
+        Action::CollapseAll,
+        Action::ExpandAll,
+        Action::ToggleNotifications,
+        Action::PreviousValue,
+        Action::NextValue,
+        Action::LatestValue,
+        Action::FloatNumbers,
+        Action::UnsignedNumbers,
+        Action::Graph,
+        Action::ChartZoomIn,
+        Action::ChartZoomOut,
+        Action::CopyUuid,
+        Action::CopyServiceUuid,
+        Action::Write,
+        Action::Export,
+        Action::UpdateMode,
+        Action::SwitchFormat,
+        Action::SwitchEndianness,
+        Action::ToggleAdapter,
+        Action::Unpin,
+    ];
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Sort => &["n"],
+            Action::Up => &["k", "Up"],
+            Action::Down => &["j", "Down"],
+            Action::Left => &["Left", "h"],
+            Action::Right => &["Right", "l"],
+            Action::Confirm => &["Enter"],
+            Action::Cancel => &["Esc"],
+            Action::Search => &["/", "Tab"],
+            Action::CommandPalette => &[":"],
+            Action::Complete => &["Tab"],
+            Action::HideUnknown => &["u"],
+            Action::HideStale => &["x"],
+            Action::Inspect => &["i"],
+            Action::RestartScan => &["r"],
+            Action::Adapters => &["a"],
+            Action::Connections => &["o"],
+            Action::ScanFilter => &["f"],
+            Action::Pin => &["p"],
+            Action::Favorites => &["P"],
+            Action::Disconnect => &["d"],
+            Action::Collapse => &["["],
+            Action::Expand => &["]"],
+            Action::ToggleCollapse => &["Space"],
+            Action::CollapseAll => &["H"],
+            Action::ExpandAll => &["L"],
+            Action::ToggleNotifications => &["t"],
+            Action::PreviousValue => &["<", ","],
+            Action::NextValue => &[">", "."],
+            Action::LatestValue => &["End"],
+            Action::FloatNumbers => &["f"],
+            Action::UnsignedNumbers => &["u"],
+            Action::Graph => &["g"],
+            Action::ChartZoomIn => &["+", "="],
+            Action::ChartZoomOut => &["-"],
+            Action::CopyUuid => &["c"],
+            Action::CopyServiceUuid => &["s"],
+            Action::Write => &["w"],
+            Action::Export => &["e"],
+            Action::UpdateMode => &["m"],
+            Action::SwitchFormat => &["Tab"],
+            Action::SwitchEndianness => &["BackTab"],
+            Action::ToggleAdapter => &["Space"],
+            Action::Unpin => &["p", "Delete"],
+        }
+    }
+
+    /// Name of the action in the config and in the command palette, e.g. "hide_unknown"
+    pub fn name(self) -> String {
+        format!("{self:?}")
+            .chars()
+            .enumerate()
+            .fold(String::new(), |mut name, (i, char)| {
+                if char.is_uppercase() && i > 0 {
+                    name.push('_');
+                }
+                name.push(char.to_ascii_lowercase());
+                name
+            })
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Shift is a part of the char itself, only ctrl and alt have to match
+    fn matches(&self, key: &KeyEvent) -> bool {
+        let modifiers = KeyModifiers::CONTROL | KeyModifiers::ALT;
+
+        self.code == key.code && self.modifiers & modifiers == key.modifiers & modifiers
+    }
+}
+
+impl std::str::FromStr for KeyBinding {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut key = value;
+        loop {
+            let lowercase = key.to_lowercase();
+            if let Some(rest) = ["ctrl-", "ctrl+"]
+                .iter()
+                .find_map(|prefix| lowercase.strip_prefix(prefix).map(|_| &key[prefix.len()..]))
+            {
+                modifiers |= KeyModifiers::CONTROL;
+                key = rest;
+            } else if let Some(rest) = ["alt-", "alt+"]
+                .iter()
+                .find_map(|prefix| lowercase.strip_prefix(prefix).map(|_| &key[prefix.len()..]))
+            {
+                modifiers |= KeyModifiers::ALT;
+                key = rest;
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(char), None) => KeyCode::Char(char),
+            _ => match key.to_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "space" => KeyCode::Char(' '),
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                function => match function.strip_prefix('f').map(str::parse) {
+                    Some(Ok(number)) => KeyCode::F(number),
+                    _ => return Err(Error::client(format!("Unknown key \"{value}\""))),
+                },
+            },
+        };
+
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(char) => write!(f, "{char}"),
+            KeyCode::Left => write!(f, "<-"),
+            KeyCode::Right => write!(f, "->"),
+            KeyCode::F(number) => write!(f, "F{number}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// Keys of every action, the defaults are replaced per action by the [keys] table of the config
+#[derive(Debug)]
+pub struct Keymap {
+    bindings: HashMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(&HashMap::new()).expect("Default key bindings are valid")
+    }
+}
+
+impl Keymap {
+    /// Fails on unknown keys and on a key bound to two actions of the views taking the keys together
+    pub fn new(overrides: &HashMap<Action, Vec<String>>) -> Result<Self> {
+        let bindings: HashMap<Action, Vec<KeyBinding>> = Action::ALL
+            .iter()
+            .map(|action| {
+                let keys = match overrides.get(action) {
+                    Some(keys) => keys.iter().map(|key| key.parse()).collect::<Result<_>>(),
+                    None => action
+                        .default_keys()
+                        .iter()
+                        .map(|key| key.parse())
+                        .collect(),
+                };
+
+                Ok((*action, keys?))
+            })
+            .collect::<Result<_>>()?;
+
+        for scope in super::KEY_SCOPES {
+            let mut actions = scope
+                .iter()
+                .flat_map(|actions| actions.iter())
+                .collect::<Vec<_>>();
+            actions.sort();
+            actions.dedup();
+
+            for (i, first) in actions.iter().enumerate() {
+                for second in &actions[i + 1..] {
+                    if let Some(key) = bindings[*first]
+                        .iter()
+                        .find(|key| bindings[*second].contains(key))
+                    {
+                        return Err(Error::client(format!(
+                            "Key \"{key}\" is bound to both {} and {}",
+                            first.name(),
+                            second.name()
+                        )));
+                    }
+                }
+            }
+        }
+
+        Ok(Self { bindings })
+    }
+
+    /// First of the actions bound to the key
+    pub fn action(&self, key: &KeyEvent, actions: &[Action]) -> Option<Action> {
+        actions.iter().copied().find(|action| {
+            self.bindings
+                .get(action)
+                .is_some_and(|bindings| bindings.iter().any(|binding| binding.matches(key)))
+        })
+    }
+
+    /// Same as `action` for views with focused text input, printable chars are always typed
+    pub fn input_action(&self, key: &KeyEvent, actions: &[Action]) -> Option<Action> {
+        match key.code {
+            KeyCode::Char(_)
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                None
+            }
+            _ => self.action(key, actions),
+        }
+    }
+
+    /// Help label with the main key of every action, e.g. "j/k"
+    pub fn label(&self, actions: &[Action]) -> String {
+        actions
+            .iter()
+            .filter_map(|action| self.bindings.get(action)?.first())
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+#[test]
+fn overrides_key_bindings() {
+    let keymap = Keymap::new(&HashMap::from([
+        (Action::Down, vec![String::from("n"), String::from("Down")]),
+        (Action::Quit, vec![String::from("Ctrl-x")]),
+    ]))
+    .unwrap();
+
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    assert_eq!(
+        keymap.action(&key(KeyCode::Char('n')), &[Action::Down, Action::Sort]),
+        Some(Action::Down)
+    );
+    assert_eq!(
+        keymap.action(&key(KeyCode::Char('j')), &[Action::Down]),
+        None
+    );
+    assert_eq!(
+        keymap.action(&key(KeyCode::Char('H')), &[Action::CollapseAll]),
+        Some(Action::CollapseAll)
+    );
+    assert_eq!(
+        keymap.action(&key(KeyCode::Char('x')), &[Action::Quit]),
+        None
+    );
+    assert_eq!(
+        keymap.action(
+            &KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL),
+            &[Action::Quit]
+        ),
+        Some(Action::Quit)
+    );
+    assert_eq!(
+        keymap.input_action(&key(KeyCode::Char('/')), &[Action::Search]),
+        None
+    );
+    assert_eq!(keymap.label(&[Action::Down, Action::Up]), "n/k");
+    assert_eq!(
+        keymap.label(&[Action::Right, Action::ToggleCollapse]),
+        "->/Space"
+    );
+
+    assert!(Keymap::new(&HashMap::from([(Action::Up, vec![String::from("Hyper")])])).is_err());
+
+    // names of the palette are the ones of the config
+    for action in Action::ALL {
+        let name = action.name();
+        let table: HashMap<Action, u8> = toml::from_str(&format!("{name} = 1")).unwrap();
+        assert!(table.contains_key(action), "{name}");
+        assert_eq!(Action::from_name(&name), Some(*action));
+    }
+    assert_eq!(Action::HideUnknown.name(), "hide_unknown");
+}
+
+#[test]
+fn rejects_conflicting_key_bindings() {
+    // every key of the defaults does a single thing wherever it is pressed
+    assert!(Keymap::new(&HashMap::new()).is_ok());
+
+    let error = Keymap::new(&HashMap::from([(
+        Action::Collapse,
+        vec![String::from("h")],
+    )]))
+    .unwrap_err()
+    .to_string();
+    assert!(
+        error.contains("\"h\" is bound to both left and collapse"),
+        "{error}"
+    );
+
+    // views not taking the keys together may share them
+    assert!(Keymap::new(&HashMap::from([(Action::Unpin, vec![String::from("p")])])).is_ok());
+    assert!(Keymap::new(&HashMap::from([(Action::Export, vec![String::from("r")])])).is_ok());
+    assert!(Keymap::new(&HashMap::from([(
+        Action::Export,
+        vec![String::from("Left")]
+    )]))
+    .is_err());
+}
@@ -1,5 +1,11 @@
+mod adapter_list;
+mod advertisement_view;
+pub mod command_palette;
 mod connection_view;
+mod connections_panel;
 mod error_popup;
+mod favorites_panel;
+pub mod keymap;
 mod peripheral_list;
 mod peripheral_view;
 pub mod ui;
@@ -9,7 +15,15 @@ use crate::{
     error::Result,
     route::Route,
     tui::{
-        connection_view::ConnectionView, error_popup::ErrorView, peripheral_view::PeripheralView,
+        adapter_list::AdapterList,
+        advertisement_view::AdvertisementView,
+        command_palette::{Command, CommandPalette},
+        connection_view::ConnectionView,
+        connections_panel::ConnectionsPanel,
+        error_popup::ErrorView,
+        favorites_panel::FavoritesPanel,
+        keymap::Action,
+        peripheral_view::PeripheralView,
     },
 };
 use crate::{tui::peripheral_list::PeripheralList, Ctx};
@@ -30,12 +44,43 @@ use tui::{
     Frame, Terminal,
 };
 
+/// Keys working in every view unless a text input is focused
+const GLOBAL_ACTIONS: &[Action] = &[Action::Quit, Action::CommandPalette];
+
+/// Actions of the blocks taking the keys together, see `get_active_blocks`, and of the text
+/// inputs. A key may do only one thing in each of them.
+const KEY_SCOPES: &[&[&[Action]]] = &[
+    &[GLOBAL_ACTIONS, peripheral_list::KEY_ACTIONS],
+    &[GLOBAL_ACTIONS, adapter_list::KEY_ACTIONS],
+    &[GLOBAL_ACTIONS, connections_panel::KEY_ACTIONS],
+    &[GLOBAL_ACTIONS, favorites_panel::KEY_ACTIONS],
+    &[
+        GLOBAL_ACTIONS,
+        peripheral_list::KEY_ACTIONS,
+        peripheral_view::KEY_ACTIONS,
+    ],
+    &[
+        GLOBAL_ACTIONS,
+        peripheral_view::KEY_ACTIONS,
+        connection_view::KEY_ACTIONS,
+    ],
+    &[peripheral_list::SEARCH_ACTIONS],
+    &[connection_view::WRITE_ACTIONS],
+    &[connection_view::EXPORT_ACTIONS],
+    &[command_palette::INPUT_ACTIONS],
+];
+
 struct App {
     ctx: Arc<Ctx>,
     peripheral_list: peripheral_list::PeripheralList,
     peripheral_view: peripheral_view::PeripheralView,
+    advertisement_view: advertisement_view::AdvertisementView,
+    adapter_list: adapter_list::AdapterList,
     connection_view: connection_view::ConnectionView,
+    connections_panel: connections_panel::ConnectionsPanel,
+    favorites_panel: favorites_panel::FavoritesPanel,
     error_view: error_popup::ErrorView,
+    command_palette: CommandPalette,
     welcome_screen: welcome::WelcomeBlock,
 }
 
@@ -76,6 +121,30 @@ impl App {
                     BlockVariant::Secondary(&mut self.welcome_screen),
                 ]
             }
+            Route::AdvertisementView { .. } => {
+                vec![
+                    BlockVariant::Primary(&mut self.peripheral_list),
+                    BlockVariant::Primary(&mut self.advertisement_view),
+                ]
+            }
+            Route::AdapterList => {
+                vec![
+                    BlockVariant::Secondary(&mut self.peripheral_list),
+                    BlockVariant::Primary(&mut self.adapter_list),
+                ]
+            }
+            Route::ConnectionsPanel => {
+                vec![
+                    BlockVariant::Secondary(&mut self.peripheral_list),
+                    BlockVariant::Primary(&mut self.connections_panel),
+                ]
+            }
+            Route::FavoritesPanel => {
+                vec![
+                    BlockVariant::Secondary(&mut self.peripheral_list),
+                    BlockVariant::Primary(&mut self.favorites_panel),
+                ]
+            }
             // When peripheral is not yet connected we share controls for both blocks to be able at the same time navigate and disconnect
             Route::PeripheralWaitingView { .. } => {
                 vec![
@@ -120,6 +189,14 @@ trait AppRoute {
     where
         Self: Sized;
     fn handle_input(&mut self, key: &KeyEvent) -> HandleKeydownResult;
+    /// Runs the command of the key binding or of the command palette
+    fn handle_command(&mut self, _command: &Command) -> HandleKeydownResult {
+        HandleKeydownResult::Continue
+    }
+    /// Printable keys go to a text input of the view and must not trigger any action
+    fn is_typing(&self) -> bool {
+        false
+    }
     fn render(&mut self, area: Rect, is_active: bool, f: &mut Frame<TerminalBackend>)
         -> Result<()>;
 }
@@ -148,8 +225,13 @@ pub fn run_tui_app(ctx: Arc<Ctx>) -> Result<()> {
         ctx: Arc::clone(&ctx),
         peripheral_list: PeripheralList::new(Arc::clone(&ctx)),
         peripheral_view: PeripheralView::new(Arc::clone(&ctx)),
+        advertisement_view: AdvertisementView::new(Arc::clone(&ctx)),
+        adapter_list: AdapterList::new(Arc::clone(&ctx)),
         connection_view: ConnectionView::new(Arc::clone(&ctx)),
+        connections_panel: ConnectionsPanel::new(Arc::clone(&ctx)),
+        favorites_panel: FavoritesPanel::new(Arc::clone(&ctx)),
         error_view: ErrorView::new(Arc::clone(&ctx)),
+        command_palette: CommandPalette::new(Arc::clone(&ctx)),
         welcome_screen: welcome::WelcomeBlock::new(ctx),
     };
 
@@ -186,21 +268,48 @@ fn tui_loop(
 
         if crossterm::event::poll(timeout)? {
             if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Char('c') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
-                        return Ok(())
+                if key.code == KeyCode::Char('c')
+                    && key.modifiers.contains(event::KeyModifiers::CONTROL)
+                {
+                    return Ok(());
+                }
+
+                let width = terminal.size()?.width;
+                if app.command_palette.is_open() {
+                    match app.command_palette.handle_input(&key) {
+                        Some(Command::Action(Action::Quit)) => return Ok(()),
+                        Some(command) => run_command(&mut app, width, &command),
+                        None => (),
                     }
-                    _ => {}
+
+                    continue;
                 }
 
-                for block in app
-                    .get_active_blocks(terminal.size()?.width)
+                let typing = app.get_active_blocks(width).into_iter().any(
+                    |block| matches!(block, BlockVariant::Primary(block) if block.is_typing()),
+                );
+
+                match app.ctx.keymap.action(&key, GLOBAL_ACTIONS) {
+                    Some(Action::Quit) if !typing => return Ok(()),
+                    Some(Action::CommandPalette) if !typing => {
+                        app.command_palette.open();
+                        continue;
+                    }
+                    _ => (),
+                }
+
+                let mut blocks = app
+                    .get_active_blocks(width)
                     .into_iter()
                     .rev()
                     .filter(|block| matches!(block, BlockVariant::Primary(_)))
-                {
-                    let handle_result = block.into_inner().handle_input(&key);
+                    .map(BlockVariant::into_inner)
+                    .collect::<Vec<_>>();
+                // the focused text input takes the keys before the other blocks
+                blocks.sort_by_key(|block| !block.is_typing());
+
+                for block in blocks {
+                    let handle_result = block.handle_input(&key);
                     // can't use find cause it it is not giving the ownership required for handle_input
                     if handle_result == HandleKeydownResult::Handled {
                         break;
@@ -217,6 +326,19 @@ fn tui_loop(
     }
 }
 
+/// Commands go to the focused blocks first, the ones they do not know go to the rest of the blocks
+fn run_command(app: &mut App, width: u16, command: &Command) {
+    let mut blocks = app.get_active_blocks(width);
+    blocks.reverse();
+    blocks.sort_by_key(|block| matches!(block, BlockVariant::Secondary(_)));
+
+    for block in blocks {
+        if block.into_inner().handle_command(command) == HandleKeydownResult::Handled {
+            break;
+        }
+    }
+}
+
 fn ui(f: &mut Frame<TerminalBackend>, app: &mut App) {
     // Create two chunks with equal horizontal screen space
     let active_blocks = app.get_active_blocks(f.size().width);
@@ -239,5 +361,64 @@ fn ui(f: &mut Frame<TerminalBackend>, app: &mut App) {
         }
     }
 
+    app.command_palette.render(f);
     app.error_view.render(chunks[0], true, f).unwrap();
 }
+
+#[tokio::test]
+async fn connects_from_the_connected_view() {
+    use crate::bluetooth::{
+        connections,
+        simulator::{scan_once, simulated_ctx, wait_for},
+        ConnectedPeripheral,
+    };
+
+    let simulation = r#"
+[[device]]
+name = "Sensor A"
+address = "AA:BB:CC:DD:EE:01"
+
+[[device]]
+name = "Sensor B"
+address = "AA:BB:CC:DD:EE:02"
+"#;
+    let ctx = Arc::new(simulated_ctx(simulation, &[]));
+    let sensor_a = scan_once(&ctx, 2)
+        .await
+        .into_iter()
+        .find(|peripheral| peripheral.name == "Sensor A")
+        .unwrap();
+    connections::connect_with_backoff(&ctx, &sensor_a, |_| ())
+        .await
+        .unwrap();
+    let sensor_a = ConnectedPeripheral::new(&ctx, sensor_a);
+    connections::register(&ctx, sensor_a.clone()).unwrap();
+    *ctx.active_route.write().unwrap() = Route::PeripheralConnectedView(sensor_a);
+
+    let mut app = App {
+        ctx: Arc::clone(&ctx),
+        peripheral_list: PeripheralList::new(Arc::clone(&ctx)),
+        peripheral_view: PeripheralView::new(Arc::clone(&ctx)),
+        advertisement_view: AdvertisementView::new(Arc::clone(&ctx)),
+        adapter_list: AdapterList::new(Arc::clone(&ctx)),
+        connection_view: ConnectionView::new(Arc::clone(&ctx)),
+        connections_panel: ConnectionsPanel::new(Arc::clone(&ctx)),
+        favorites_panel: FavoritesPanel::new(Arc::clone(&ctx)),
+        error_view: ErrorView::new(Arc::clone(&ctx)),
+        command_palette: CommandPalette::new(Arc::clone(&ctx)),
+        welcome_screen: welcome::WelcomeBlock::new(Arc::clone(&ctx)),
+    };
+    run_command(&mut app, 120, &Command::Connect(String::from("Sensor B")));
+
+    wait_for(|| match ctx.get_active_route().deref() {
+        Route::PeripheralConnectedView(peripheral) if peripheral.peripheral.name == "Sensor B" => {
+            Some(())
+        }
+        _ => None,
+    })
+    .await;
+
+    // the previous device stays connected in the background
+    assert_eq!(ctx.connections.read().unwrap().len(), 2);
+    assert!(ctx.global_error.lock().unwrap().is_none());
+}
@@ -1,14 +1,20 @@
-use crate::bluetooth::{BleScan, HandledPeripheral};
-use crate::error::Result;
-use crate::tui::ui::{block, list::StableListState, search_input, BlendrBlock, ShouldUpdate};
-use crate::tui::ui::{HandleInputResult, StableIndexList};
-use crate::tui::{AppRoute, HandleKeydownResult};
+use crate::bluetooth::backend::PeripheralId;
+use crate::bluetooth::{connections, history::format_age, BleScan, HandledPeripheral};
+use crate::error::{Error, Result};
+use crate::tui::command_palette::Command;
+use crate::tui::ui::{
+    block, filter_query::FilterQuery, list::StableListState, search_input, BlendrBlock,
+    ShouldUpdate,
+};
+use crate::tui::ui::{HandleInputResult, StableIndexList, StableListItem};
+use crate::tui::{
+    connections_panel::connection_state_style, keymap::Action, AppRoute, HandleKeydownResult,
+};
 use crate::GeneralOptions;
-use crate::{route::Route, Ctx};
+use crate::{config::matches_device, pins, route::Route, Ctx};
 use btleplug::api::BDAddr;
-use btleplug::platform::PeripheralId;
-use crossterm::event::{KeyCode, KeyEvent};
-use regex::Regex;
+use crossterm::event::KeyEvent;
+use std::ops::Deref;
 use std::sync::atomic::AtomicU16;
 use std::sync::Arc;
 use tui::layout::Rect;
@@ -22,10 +28,55 @@ use tui::{
     Frame,
 };
 
+/// Amount of the latest RSSI readings displayed next to every peripheral
+const RSSI_SPARKLINE_WIDTH: usize = 12;
+
 lazy_static::lazy_static! {
     static ref DEFAULT_BD_ADDR: BDAddr = BDAddr::default();
 }
 
+pub(super) const KEY_ACTIONS: &[Action] = &[
+    Action::Down,
+    Action::Up,
+    Action::Left,
+    Action::Right,
+    Action::Confirm,
+    Action::Search,
+    Action::RestartScan,
+    Action::Adapters,
+    Action::Connections,
+    Action::Pin,
+    Action::Favorites,
+    Action::ScanFilter,
+    Action::HideUnknown,
+    Action::HideStale,
+    Action::Inspect,
+    Action::Cancel,
+];
+
+/// Keys of the focused search input, shared with the device view
+pub(super) const SEARCH_ACTIONS: &[Action] = &[
+    Action::Confirm,
+    Action::Down,
+    Action::Cancel,
+    Action::Search,
+];
+
+/// Exact name or address first, then the first device with the name containing the query
+fn find_peripheral(peripherals: &[HandledPeripheral], device: &str) -> Option<HandledPeripheral> {
+    let device_lowercase = device.to_lowercase();
+
+    peripherals
+        .iter()
+        .find(|peripheral| matches_device(device, &peripheral.name, &peripheral.address))
+        .or_else(|| {
+            peripherals
+                .iter()
+                .find(|peripheral| peripheral.name.to_lowercase().contains(&device_lowercase))
+        })
+        .cloned()
+}
+
 pub enum Focus {
     Search,
     List,
@@ -35,7 +86,8 @@ pub(crate) struct PeripheralList {
     pub ctx: Arc<Ctx>,
     pub list_state: StableListState<PeripheralId>,
     pub search: Option<String>,
-    pub search_regex: Option<Regex>,
+    pub filter_query: Option<FilterQuery>,
+    pub filter_error: Option<String>,
     pub focus: Focus,
     pub to_remove_unknowns: bool,
     pub first_match_done: bool,
@@ -47,23 +99,63 @@ impl PeripheralList {
             return false;
         }
 
-        if let Some(regex) = self.search_regex.as_ref() {
-            return regex.is_match(&peripheral.name)
-                || peripheral
-                    .services_names
-                    .iter()
-                    .any(|name| regex.is_match(name));
+        self.filter_query
+            .as_ref()
+            .is_none_or(|query| query.matches(peripheral))
+    }
+
+    /// The last valid query keeps filtering while the search has an error
+    fn update_filter_query(&mut self, last_search: Option<String>) {
+        if let ShouldUpdate::Update(query) =
+            search_input::maybe_update_filter_query(self.search.as_deref(), last_search, &self.ctx)
+        {
+            match query {
+                Ok(query) => {
+                    self.filter_query = query;
+                    self.filter_error = None;
+                }
+                Err(e) => self.filter_error = Some(e.to_string()),
+            }
+        }
+    }
+
+    fn request_scan_restart(&self) {
+        if let Ok(request_restart) = self.ctx.request_scan_restart.lock().as_deref_mut() {
+            *request_restart = true;
         }
+    }
+
+    /// Keeps the advertisement view in sync with the selected peripheral while it is open
+    fn sync_advertisement_view(
+        ctx: &Arc<Ctx>,
+        list_state: &mut StableListState<PeripheralId>,
+        peripherals: &StableIndexList<PeripheralId, HandledPeripheral>,
+        open: bool,
+    ) {
+        // the route lock has to be released before navigating
+        let shown_id = match ctx.get_active_route().deref() {
+            Route::AdvertisementView { peripheral } => Some(peripheral.id()),
+            _ => None,
+        };
 
-        if let Some(search) = self.search.as_ref() {
-            return peripheral.name.contains(search)
-                || peripheral
-                    .services_names
-                    .iter()
-                    .any(|name| name.contains(search));
+        if !open && shown_id.is_none() {
+            return;
         }
 
-        true
+        let selected = list_state
+            .selected()
+            .filter(|index| *index < peripherals.len())
+            .map(|index| &peripherals[index]);
+
+        match selected {
+            Some(peripheral) if shown_id == Some(peripheral.id()) => {}
+            Some(peripheral) => Route::AdvertisementView {
+                peripheral: peripheral.clone(),
+            }
+            .navigate(ctx),
+            None if shown_id.is_some() => Route::PeripheralList.navigate(ctx),
+            None => {}
+        }
     }
 }
 
@@ -71,25 +163,29 @@ impl AppRoute for PeripheralList {
     fn new(ctx: Arc<Ctx>) -> Self {
         let initial_search = ctx.args.device.clone();
 
-        PeripheralList {
+        let mut list = PeripheralList {
             first_match_done: false,
-            search_regex: match search_input::maybe_update_search_regexp(
-                initial_search.as_deref(),
-                None,
-                &ctx,
-            ) {
-                ShouldUpdate::NoUpdate => None,
-                ShouldUpdate::Update(regex) => regex,
-            },
+            filter_query: None,
+            filter_error: None,
             search: initial_search,
             focus: Focus::List,
             list_state: StableListState::default(),
             to_remove_unknowns: false,
             ctx,
-        }
+        };
+
+        list.update_filter_query(None);
+        list
     }
 
     fn handle_input(&mut self, key: &KeyEvent) -> HandleKeydownResult {
+        if matches!(self.focus, Focus::List) {
+            return match self.ctx.keymap.action(key, KEY_ACTIONS) {
+                Some(action) => self.handle_command(&Command::Action(action)),
+                None => HandleKeydownResult::Continue,
+            };
+        }
+
         let last_search = self.search.clone();
 
         if let Ok(Some(BleScan { peripherals, .. })) = &self.ctx.latest_scan.read().as_deref() {
@@ -101,71 +197,166 @@ impl AppRoute for PeripheralList {
             self.list_state
                 .stabilize_selected_index(&filtered_peripherals);
 
-            if GeneralOptions::handle_keystroke(&key.code, &self.ctx) {
-                return HandleKeydownResult::Handled;
+            search_input::handle_search_input(&mut self.search, key);
+
+            match self.ctx.keymap.input_action(key, SEARCH_ACTIONS) {
+                Some(Action::Confirm | Action::Down) => {
+                    self.list_state.select(&filtered_peripherals, Some(0));
+                    self.focus = Focus::List
+                }
+                Some(Action::Cancel | Action::Search) => {
+                    self.list_state.list_unselect(&filtered_peripherals);
+                    self.focus = Focus::List;
+                }
+                _ => (),
             }
+        }
 
-            match self.focus {
-                Focus::Search => {
-                    search_input::handle_search_input(&mut self.search, key);
+        self.update_filter_query(last_search);
 
-                    match key.code {
-                        KeyCode::Enter | KeyCode::Down => {
-                            self.list_state.select(&filtered_peripherals, Some(0));
-                            self.focus = Focus::List
-                        }
-                        KeyCode::Esc | KeyCode::Tab => {
-                            self.list_state.list_unselect(&filtered_peripherals);
-                            self.focus = Focus::List;
-                        }
-                        _ => (),
+        HandleKeydownResult::Continue
+    }
+
+    fn handle_command(&mut self, command: &Command) -> HandleKeydownResult {
+        if GeneralOptions::handle_command(command, &self.ctx) {
+            return HandleKeydownResult::Handled;
+        }
+
+        let ctx = Arc::clone(&self.ctx);
+        let scan = ctx.latest_scan.read();
+        let Ok(Some(BleScan { peripherals, .. })) = scan.as_deref() else {
+            return HandleKeydownResult::Continue;
+        };
+
+        let filtered_peripherals = peripherals
+            .iter()
+            .filter(|peripheral| self.filter_peripherals(peripheral))
+            .collect::<StableIndexList<PeripheralId, HandledPeripheral>>();
+
+        self.list_state
+            .stabilize_selected_index(&filtered_peripherals);
+
+        let action = match command {
+            Command::Action(action) if KEY_ACTIONS.contains(action) => *action,
+            Command::Filter(query) => {
+                let last_search = self.search.replace(query.clone());
+                self.focus = Focus::List;
+                self.update_filter_query(last_search);
+
+                return HandleKeydownResult::Handled;
+            }
+            Command::Connect(device) => {
+                let found = find_peripheral(peripherals, device);
+                drop(scan);
+
+                match found {
+                    Some(peripheral) => Route::PeripheralWaitingView {
+                        retry: Arc::new(AtomicU16::new(0)),
+                        peripheral,
                     }
-                }
-                Focus::List => {
-                    if let HandleInputResult::Selected(peripheral) =
-                        StableListState::handle_key_input(
-                            &mut self.list_state,
-                            &filtered_peripherals,
-                            &key.code,
-                        )
-                    {
-                        Route::PeripheralWaitingView {
-                            retry: Arc::new(AtomicU16::new(0)),
-                            peripheral: peripheral.clone(),
+                    .navigate(&ctx),
+                    None => {
+                        if let Ok(global_error) = ctx.global_error.lock().as_deref_mut() {
+                            *global_error = Some(Error::client(format!(
+                                "Device {device} is not found in the latest scan"
+                            )));
                         }
-                        .navigate(&self.ctx)
                     }
+                }
 
-                    match key.code {
-                        KeyCode::Char('/') | KeyCode::Tab => {
-                            self.focus = Focus::Search;
-                            self.list_state.list_unselect(&filtered_peripherals)
-                        }
-                        KeyCode::Char('r') => {
-                            if let Ok(request_restart) =
-                                self.ctx.request_scan_restart.lock().as_deref_mut()
-                            {
-                                *request_restart = true;
-                            }
-                        }
-                        KeyCode::Char('u') => self.to_remove_unknowns = !self.to_remove_unknowns,
-                        KeyCode::Char('s') => {
-                            // let mut sort_by_name = self.ctx.sort_by_name.lock().unwrap();
-                            // *sort_by_name = !*sort_by_name;
+                return HandleKeydownResult::Handled;
+            }
+            _ => return HandleKeydownResult::Continue,
+        };
+
+        if let HandleInputResult::Selected(peripheral) = StableListState::handle_key_input(
+            &mut self.list_state,
+            &filtered_peripherals,
+            Some(action),
+        ) {
+            Route::PeripheralWaitingView {
+                retry: Arc::new(AtomicU16::new(0)),
+                peripheral: peripheral.clone(),
+            }
+            .navigate(&ctx)
+        }
+
+        match action {
+            Action::Search => {
+                self.focus = Focus::Search;
+                self.list_state.list_unselect(&filtered_peripherals)
+            }
+            Action::RestartScan => self.request_scan_restart(),
+            Action::Adapters => {
+                Route::AdapterList.navigate(&ctx);
+                return HandleKeydownResult::Handled;
+            }
+            Action::Connections => {
+                Route::ConnectionsPanel.navigate(&ctx);
+                return HandleKeydownResult::Handled;
+            }
+            Action::Pin => {
+                if let Some(peripheral) = self
+                    .list_state
+                    .selected()
+                    .and_then(|index| filtered_peripherals.get(index))
+                {
+                    let result = self
+                        .ctx
+                        .pins
+                        .write()
+                        .map_err(Into::into)
+                        .and_then(|mut pins| pins.toggle_peripheral(peripheral));
+
+                    if let Err(e) = result {
+                        if let Ok(global_error) = ctx.global_error.lock().as_deref_mut() {
+                            *global_error = Some(e);
                         }
-                        _ => {}
                     }
                 }
             }
-        }
+            Action::Favorites => {
+                Route::FavoritesPanel.navigate(&ctx);
+                return HandleKeydownResult::Handled;
+            }
+            Action::ScanFilter => {
+                if let Ok(mut scan_options) = ctx.scan_options.write() {
+                    scan_options.enabled = !scan_options.enabled;
+                }
 
-        if let ShouldUpdate::Update(new_regex) =
-            search_input::maybe_update_search_regexp(self.search.as_deref(), last_search, &self.ctx)
-        {
-            self.search_regex = new_regex;
+                self.request_scan_restart();
+            }
+            Action::HideUnknown => self.to_remove_unknowns = !self.to_remove_unknowns,
+            Action::HideStale => {
+                if let Ok(mut scan_options) = ctx.scan_options.write() {
+                    scan_options.drop_stale = !scan_options.drop_stale;
+                }
+            }
+            Action::Inspect | Action::Cancel
+                if matches!(
+                    ctx.get_active_route().deref(),
+                    Route::AdvertisementView { .. }
+                ) =>
+            {
+                Route::PeripheralList.navigate(&ctx);
+                return HandleKeydownResult::Handled;
+            }
+            Action::Inspect => Self::sync_advertisement_view(
+                &ctx,
+                &mut self.list_state,
+                &filtered_peripherals,
+                true,
+            ),
+            _ => {}
         }
 
-        HandleKeydownResult::Continue
+        Self::sync_advertisement_view(&ctx, &mut self.list_state, &filtered_peripherals, false);
+
+        HandleKeydownResult::Handled
+    }
+
+    fn is_typing(&self) -> bool {
+        matches!(self.focus, Focus::Search)
     }
 
     fn render(
@@ -219,12 +410,12 @@ impl AppRoute for PeripheralList {
             Span::from(self.search.as_deref().unwrap_or("")),
         ]))
         .style(Style::default())
-        .block(tui::widgets::Block::from(BlendrBlock {
+        .block(search_input::search_block(
+            "Filter, e.g. name:/^Sensor/ AND rssi>-70 (press \"/\" to focus)",
+            self.filter_error.as_deref(),
+            matches!(self.focus, Focus::Search),
             route_active,
-            focused: matches!(self.focus, Focus::Search),
-            title: "Filter with regex (press \"/\" to focus)",
-            ..Default::default()
-        }));
+        ));
 
         f.render_widget(input, chunks[0]);
 
@@ -241,11 +432,19 @@ impl AppRoute for PeripheralList {
             self.first_match_done = true
         }
 
+        let peripheral_history = self.ctx.peripheral_history.read()?;
+        let adapters = self.ctx.adapters.read()?;
+        // tag peripherals with their adapter only when scanning on several of them
+        let tag_adapters = self.ctx.selected_adapters.read()?.len() > 1;
         let items: Vec<ListItem> = filtered_peripherals
             .into_iter()
             .enumerate()
             .map(|(i, peripheral)| {
                 let is_highlighted = Some(i) == self.list_state.selected();
+                let history = peripheral_history.get(&peripheral.id());
+                let is_stale =
+                    history.is_some_and(|history| history.is_stale(self.ctx.args.stale_after));
+
                 let mut spans = vec![];
                 if is_highlighted {
                     spans.push(Span::styled("> ", Style::default().fg(Color::Blue)));
@@ -253,6 +452,23 @@ impl AppRoute for PeripheralList {
                     spans.push(Span::from("  "));
                 }
 
+                // age column, time since the last advertisement
+                spans.push(Span::styled(
+                    format!(
+                        "{:>4} ",
+                        history.map_or_else(String::new, |history| format_age(history.age()))
+                    ),
+                    Style::default().fg(if is_stale {
+                        Color::Red
+                    } else {
+                        Color::DarkGray
+                    }),
+                ));
+
+                if pins::is_peripheral_pinned(&self.ctx, &peripheral.name, &peripheral.address) {
+                    spans.push(Span::styled("★ ", Style::default().fg(Color::Yellow)));
+                }
+
                 spans.push(Span::styled(
                     peripheral.name.as_str(),
                     if is_highlighted {
@@ -291,12 +507,59 @@ impl AppRoute for PeripheralList {
                     spans.push(Span::styled(")", style));
                 }
 
-                ListItem::new(Line::from(spans))
+                if let Some(state) = connections::state(&self.ctx, &peripheral.id()) {
+                    spans.push(Span::styled(
+                        format!(" {state}"),
+                        connection_state_style(state),
+                    ));
+                }
+
+                if tag_adapters {
+                    spans.push(Span::styled(
+                        format!(
+                            " [{}]",
+                            adapters
+                                .get(peripheral.adapter_index)
+                                .map_or_else(|| peripheral.adapter_index.to_string(), Clone::clone)
+                        ),
+                        Style::default().fg(Color::Magenta),
+                    ));
+                }
+
+                if let Some(history) = history {
+                    spans.push(Span::styled(
+                        format!(" {}", history.sparkline(RSSI_SPARKLINE_WIDTH)),
+                        Style::default().fg(Color::Yellow),
+                    ));
+                }
+
+                for beacon in &peripheral.beacons {
+                    spans.push(Span::styled(
+                        format!(" {beacon}"),
+                        Style::default().fg(Color::Cyan),
+                    ));
+                }
+
+                let item = ListItem::new(Line::from(spans));
+                if is_stale && !is_highlighted {
+                    item.style(Style::default().add_modifier(Modifier::DIM))
+                } else {
+                    item
+                }
             })
             .collect();
 
+        let has_connections = !self.ctx.connections.read()?.is_empty();
+
         // Create a List from all list items and highlight the currently selected one
-        let title = format!("Latest Scan on {}", sync_time.format("%H:%M:%S"));
+        let scan_options = self.ctx.scan_options.read()?;
+        let title = match scan_options.describe() {
+            Some(filter) => format!(
+                "Latest Scan on {} filtered by {filter}",
+                sync_time.format("%H:%M:%S")
+            ),
+            None => format!("Latest Scan on {}", sync_time.format("%H:%M:%S")),
+        };
         let items = List::new(items)
             .block(tui::widgets::Block::from(BlendrBlock {
                 route_active,
@@ -318,13 +581,31 @@ impl AppRoute for PeripheralList {
                 block::render_help(
                     Arc::clone(&self.ctx),
                     [
-                        Some(("q", "Quit", false)),
-                        Some(("u", "Hide unknown devices", self.to_remove_unknowns)),
-                        Some(("->", "Connect to device", false)),
-                        Some(("r", "Restart scan", false)),
-                        // FIXME genearl sort
-                        // Some(("s", "Sort by name", *self.ctx.sort_by_name.lock().unwrap())),
-                        Some(("h/j or arrows", "Navigate", false)),
+                        Some((&[Action::Quit], "Quit", false)),
+                        Some((&[Action::CommandPalette], "Commands", false)),
+                        Some((
+                            &[Action::HideUnknown],
+                            "Hide unknown devices",
+                            self.to_remove_unknowns,
+                        )),
+                        Some((
+                            &[Action::HideStale],
+                            "Drop stale devices",
+                            scan_options.drop_stale,
+                        )),
+                        Some((&[Action::Right], "Connect to device", false)),
+                        Some((&[Action::Inspect], "Inspect advertisement", false)),
+                        Some((&[Action::RestartScan], "Restart scan", false)),
+                        (adapters.len() > 1).then_some((&[Action::Adapters], "Adapters", false)),
+                        has_connections.then_some((&[Action::Connections], "Connections", false)),
+                        Some((&[Action::Pin], "Pin device", false)),
+                        Some((&[Action::Favorites], "Favorites", false)),
+                        scan_options.is_configured().then_some((
+                            &[Action::ScanFilter],
+                            "Scan filter",
+                            scan_options.enabled,
+                        )),
+                        Some((&[Action::Down, Action::Up], "Navigate", false)),
                     ],
                 ),
                 chunks[2],
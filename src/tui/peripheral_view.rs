@@ -1,21 +1,32 @@
-use crossterm::event::KeyCode;
-use regex::Regex;
+use btleplug::api::{CharPropFlags, Descriptor, Service};
 use tui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{List, ListItem, Paragraph},
 };
-use uuid::Uuid;
 
 use crate::{
-    bluetooth::{display_properties, ConnectedCharacteristic},
+    bluetooth::{
+        backend::GattAttribute,
+        ble_default_services::{SPECIAL_DESCRIPTORS_NAMES, SPECIAL_SERVICES_NAMES},
+        connections, display_properties,
+        gatt_decoders::decode_descriptor,
+        ConnectedCharacteristic, ConnectedPeripheral,
+    },
+    cli_args::GeneralSort,
+    pins,
     route::Route,
     tui::AppRoute,
     tui::{
+        command_palette::Command,
+        connections_panel::connection_state_style,
+        keymap::Action,
+        peripheral_list::SEARCH_ACTIONS,
         ui::{
-            block::{self, BlendrBlock},
-            list::{HandleInputResult, StableListState},
+            block::{self, BlendrBlock, Title},
+            filter_query::{FilterQuery, QueryTarget},
+            list::{StableListItem, StableListState},
             search_input::{self, ShouldUpdate},
         },
         HandleKeydownResult,
@@ -23,60 +34,485 @@ use crate::{
     Ctx, GeneralOptions,
 };
 use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
     ops::Deref,
-    sync::{Arc, RwLock},
+    sync::Arc,
 };
 
+pub(super) const KEY_ACTIONS: &[Action] = &[
+    Action::Search,
+    Action::Collapse,
+    Action::Expand,
+    Action::ToggleCollapse,
+    Action::CollapseAll,
+    Action::ExpandAll,
+    Action::Cancel,
+    Action::Left,
+    Action::Disconnect,
+    Action::Connections,
+    Action::ToggleNotifications,
+    Action::Pin,
+    Action::Down,
+    Action::Up,
+    Action::Right,
+    Action::Confirm,
+];
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Focus {
     Search,
     List,
 }
 
+#[derive(Debug, Clone)]
+enum GattNode<'a> {
+    Service {
+        service: &'a Service,
+        name: String,
+        characteristics_count: usize,
+    },
+    Characteristic(&'a ConnectedCharacteristic),
+    Descriptor {
+        descriptor: &'a Descriptor,
+        characteristic: &'a ConnectedCharacteristic,
+    },
+}
+
+/// Visible row of the GATT tree
+#[derive(Debug, Clone)]
+struct GattTreeItem<'a> {
+    id: GattAttribute,
+    node: GattNode<'a>,
+    /// Has children, no matter whether they are shown
+    expandable: bool,
+}
+
+impl StableListItem<GattAttribute> for GattTreeItem<'_> {
+    fn id(&self) -> GattAttribute {
+        self.id.clone()
+    }
+}
+
+impl<'a> GattTreeItem<'a> {
+    fn characteristic(&self) -> Option<&'a ConnectedCharacteristic> {
+        match self.node {
+            GattNode::Characteristic(characteristic)
+            | GattNode::Descriptor { characteristic, .. } => Some(characteristic),
+            GattNode::Service { .. } => None,
+        }
+    }
+
+    fn parent(&self) -> Option<GattAttribute> {
+        match &self.node {
+            GattNode::Service { .. } => None,
+            GattNode::Characteristic(characteristic) => {
+                Some(GattAttribute::Service(characteristic.service_uuid))
+            }
+            GattNode::Descriptor { characteristic, .. } => Some(characteristic_id(characteristic)),
+        }
+    }
+}
+
+fn characteristic_id(characteristic: &ConnectedCharacteristic) -> GattAttribute {
+    GattAttribute::Characteristic {
+        service_uuid: characteristic.service_uuid,
+        uuid: characteristic.uuid,
+    }
+}
+
+fn service_name(ctx: &Ctx, peripheral: &ConnectedPeripheral, uuid: &uuid::Uuid) -> String {
+    if let Some(custom_name) = ctx.config.service_name(
+        &peripheral.peripheral.name,
+        &peripheral.peripheral.address,
+        uuid,
+    ) {
+        return format!("{custom_name} ({uuid})");
+    }
+
+    SPECIAL_SERVICES_NAMES
+        .get(uuid)
+        .map_or_else(|| uuid.to_string(), |name| name.to_string())
+}
+
+/// Services without matching characteristics are still shown when the query matches the service itself
+struct ServiceTarget<'a> {
+    uuid: uuid::Uuid,
+    name: &'a str,
+}
+
+impl QueryTarget for ServiceTarget<'_> {
+    fn texts(&self) -> Vec<Cow<'_, str>> {
+        vec![Cow::from(self.name), Cow::from(self.uuid.to_string())]
+    }
+
+    fn names(&self) -> Vec<Cow<'_, str>> {
+        vec![]
+    }
+
+    fn service_uuids(&self) -> Vec<uuid::Uuid> {
+        vec![self.uuid]
+    }
+
+    fn service_names(&self) -> Vec<Cow<'_, str>> {
+        vec![Cow::from(self.name)]
+    }
+}
+
+fn toggle_collapsed(collapsed: &mut HashSet<GattAttribute>, id: &GattAttribute) {
+    if !collapsed.remove(id) {
+        collapsed.insert(id.clone());
+    }
+}
+
+/// Next Client Characteristic Configuration value: off, notifications, indications and off again
+fn next_client_configuration(properties: CharPropFlags, current: u16) -> u16 {
+    let mut options = vec![0];
+    if properties.contains(CharPropFlags::NOTIFY) {
+        options.push(0b01);
+    }
+    if properties.contains(CharPropFlags::INDICATE) {
+        options.push(0b10);
+    }
+
+    options
+        .iter()
+        .position(|bits| *bits == current)
+        .map_or(0, |index| options[(index + 1) % options.len()])
+}
+
+fn descriptor_value(descriptor: &Descriptor, value: Option<&Vec<u8>>) -> String {
+    match value.and_then(|data| decode_descriptor(&descriptor.uuid, data)) {
+        Some(Ok(fields)) if fields.len() == 1 => fields[0]
+            .value
+            .clone()
+            .unwrap_or_else(|reason| format!("malformed: {reason}")),
+        Some(Ok(fields)) => fields
+            .iter()
+            .map(|field| match &field.value {
+                Ok(value) => format!("{}: {value}", field.name),
+                Err(reason) => format!("{}: malformed {reason}", field.name),
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        Some(Err(reason)) => format!("malformed: {reason}"),
+        None => String::new(),
+    }
+}
+
+fn open_characteristic_view(
+    ctx: &Arc<Ctx>,
+    peripheral: ConnectedPeripheral,
+    characteristic: ConnectedCharacteristic,
+) {
+    let (history, update_mode) = connections::open_characteristic(
+        ctx,
+        &peripheral.peripheral.ble_peripheral.id(),
+        &characteristic,
+    );
+
+    Route::CharacteristicView {
+        update_mode,
+        characteristic,
+        peripheral,
+        history,
+        historical_view_index: Default::default(),
+    }
+    .navigate(ctx);
+}
+
+fn spawn_client_configuration_write(
+    ctx: &Arc<Ctx>,
+    peripheral: &ConnectedPeripheral,
+    characteristic: &ConnectedCharacteristic,
+) {
+    let bits = next_client_configuration(
+        characteristic.ble_characteristic.properties,
+        peripheral
+            .client_configuration(characteristic)
+            .unwrap_or_default(),
+    );
+
+    let ctx = Arc::clone(ctx);
+    let peripheral = peripheral.clone();
+    let characteristic = characteristic.clone();
+    tokio::spawn(async move {
+        if let Err(e) = peripheral
+            .write_client_configuration(&characteristic, bits)
+            .await
+        {
+            tracing::error!(?e, "Failed to write client characteristic configuration.");
+
+            if let Ok(global_error) = ctx.global_error.lock().as_deref_mut() {
+                *global_error = Some(e);
+            }
+        }
+    });
+}
+
 #[derive(Debug)]
 pub struct PeripheralView {
     ctx: Arc<Ctx>,
-    list_state: StableListState<Uuid>,
+    list_state: StableListState<GattAttribute>,
     focus: Focus,
     search: Option<String>,
-    search_regex: Option<Regex>,
+    filter_query: Option<FilterQuery>,
+    filter_error: Option<String>,
     first_match_done: bool,
+    /// Services and characteristics with hidden children, everything is expanded by default
+    collapsed: HashSet<GattAttribute>,
 }
 
 impl PeripheralView {
-    fn filter_characteristic(&self, characteristic: &ConnectedCharacteristic) -> bool {
-        let characteristic_name = characteristic.uuid.to_string();
-        let service_name = characteristic.service_uuid.to_string();
-
-        if let Some(regex) = self.search_regex.as_ref() {
-            let uuids_match = regex.is_match(&characteristic.uuid.to_string())
-                || regex.is_match(&characteristic.service_uuid.to_string());
-
-            let name_match = characteristic
-                .standard_gatt_char_name
-                .is_some_and(|name| regex.is_match(name));
-            let service_match = characteristic
-                .standard_gatt_service_name
-                .is_some_and(|name| regex.is_match(name));
-
-            return uuids_match || name_match || service_match;
+    /// The last valid query keeps filtering while the search has an error
+    fn update_filter_query(&mut self, last_search: Option<String>) {
+        if let ShouldUpdate::Update(query) =
+            search_input::maybe_update_filter_query(self.search.as_deref(), last_search, &self.ctx)
+        {
+            match query {
+                Ok(query) => {
+                    self.filter_query = query;
+                    self.filter_error = None;
+                }
+                Err(e) => self.filter_error = Some(e.to_string()),
+            }
+        }
+    }
+
+    fn matches_search(&self, target: &impl QueryTarget) -> bool {
+        self.filter_query
+            .as_ref()
+            .is_none_or(|query| query.matches(target))
+    }
+
+    /// Rows of the services, characteristics and descriptors that match the search and are not collapsed
+    fn build_tree<'a>(&self, peripheral: &'a ConnectedPeripheral) -> Vec<GattTreeItem<'a>> {
+        let sort = self
+            .ctx
+            .general_options
+            .read()
+            .map(|options| options.sort)
+            .unwrap_or_default();
+
+        let mut services = peripheral
+            .services
+            .iter()
+            .map(|service| (service, service_name(&self.ctx, peripheral, &service.uuid)))
+            .collect::<Vec<_>>();
+        match sort {
+            GeneralSort::Name => services.sort_by_key(|(_, name)| name.to_lowercase()),
+            GeneralSort::Pinned => services.sort_by_key(|(service, name)| {
+                let has_pinned = peripheral.characteristics.iter().any(|characteristic| {
+                    characteristic.service_uuid == service.uuid && characteristic.pinned
+                });
+
+                (!has_pinned, name.to_lowercase())
+            }),
+            // the order of the GATT table, if the handles are known
+            GeneralSort::DefaultSort => services.sort_by_key(|(service, _)| {
+                (
+                    peripheral
+                        .handle(&GattAttribute::Service(service.uuid))
+                        .unwrap_or(u16::MAX),
+                    service.uuid,
+                )
+            }),
+        }
+
+        let mut tree = vec![];
+        for (service, name) in services {
+            let characteristics = peripheral
+                .characteristics
+                .iter()
+                .filter(|characteristic| characteristic.service_uuid == service.uuid)
+                .collect::<Vec<_>>();
+            let matched_characteristics = characteristics
+                .iter()
+                .copied()
+                .filter(|characteristic| self.matches_search(*characteristic))
+                .collect::<Vec<_>>();
+
+            // services without characteristics are shown as well, the firmware may expose them by mistake
+            if matched_characteristics.is_empty()
+                && !self.matches_search(&ServiceTarget {
+                    uuid: service.uuid,
+                    name: &name,
+                })
+            {
+                continue;
+            }
+
+            let service_id = GattAttribute::Service(service.uuid);
+            let expanded = !self.collapsed.contains(&service_id);
+            tree.push(GattTreeItem {
+                id: service_id,
+                expandable: !characteristics.is_empty(),
+                node: GattNode::Service {
+                    service,
+                    name,
+                    characteristics_count: characteristics.len(),
+                },
+            });
+
+            if !expanded {
+                continue;
+            }
+
+            for characteristic in matched_characteristics {
+                let id = characteristic_id(characteristic);
+                let descriptors = &characteristic.ble_characteristic.descriptors;
+                let expanded = !self.collapsed.contains(&id);
+
+                tree.push(GattTreeItem {
+                    id,
+                    expandable: !descriptors.is_empty(),
+                    node: GattNode::Characteristic(characteristic),
+                });
+
+                if expanded {
+                    tree.extend(descriptors.iter().map(|descriptor| GattTreeItem {
+                        id: GattAttribute::from(descriptor),
+                        expandable: false,
+                        node: GattNode::Descriptor {
+                            descriptor,
+                            characteristic,
+                        },
+                    }));
+                }
+            }
         }
 
-        if let Some(search) = self.search.as_ref() {
-            let uuids_match = characteristic_name.contains(search) || service_name.contains(search);
+        tree
+    }
+
+    fn render_row(
+        &self,
+        item: &GattTreeItem,
+        peripheral: &ConnectedPeripheral,
+        descriptor_values: &BTreeMap<Descriptor, Vec<u8>>,
+        is_highlighted: bool,
+        width: u16,
+    ) -> ListItem<'static> {
+        let base_style = if is_highlighted {
+            Style::default()
+                .bg(Color::LightBlue)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        // highlighted row is drawn in the single color to stay readable
+        let styled = |style: Style| if is_highlighted { base_style } else { style };
 
-            let name_match = characteristic
-                .standard_gatt_char_name
-                .is_some_and(|name| name.contains(search));
+        let marker = match (item.expandable, self.collapsed.contains(&item.id)) {
+            (true, true) => "▸ ",
+            (true, false) => "▾ ",
+            (false, _) => "• ",
+        };
+
+        let mut spans = match &item.node {
+            GattNode::Service {
+                service,
+                name,
+                characteristics_count,
+            } => vec![
+                Span::styled(marker, base_style),
+                Span::styled("Service ", styled(Style::default().fg(Color::White))),
+                Span::styled(
+                    name.clone(),
+                    styled(
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .fg(Color::Rgb(251, 146, 60)),
+                    ),
+                ),
+                Span::styled(
+                    if service.primary {
+                        " primary"
+                    } else {
+                        " secondary"
+                    },
+                    styled(Style::default().fg(Color::DarkGray)),
+                ),
+                Span::styled(
+                    if *characteristics_count == 0 {
+                        " (empty)"
+                    } else {
+                        ""
+                    },
+                    styled(Style::default().fg(Color::DarkGray)),
+                ),
+            ],
+            GattNode::Characteristic(characteristic) => {
+                let mut spans = vec![
+                    Span::styled("  ", base_style),
+                    Span::styled(marker, base_style),
+                    Span::styled(
+                        characteristic.char_name().into_owned(),
+                        base_style.add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(
+                        if pins::is_characteristic_pinned(&self.ctx, characteristic) {
+                            " ★"
+                        } else {
+                            ""
+                        },
+                        styled(Style::default().fg(Color::Yellow)),
+                    ),
+                    Span::styled(
+                        format!(
+                            " [{}]",
+                            display_properties(characteristic.ble_characteristic.properties)
+                        ),
+                        styled(Style::default().fg(Color::Gray)),
+                    ),
+                ];
+
+                // values keep streaming in the background after the characteristic was opened
+                if connections::is_watched(
+                    &self.ctx,
+                    &peripheral.peripheral.ble_peripheral.id(),
+                    &characteristic.uuid,
+                ) {
+                    spans.push(Span::styled(
+                        " ●",
+                        styled(Style::default().fg(Color::Green)),
+                    ));
+                }
 
-            let service_match = characteristic
-                .standard_gatt_service_name
-                .is_some_and(|name| name.contains(search));
+                spans
+            }
+            GattNode::Descriptor { descriptor, .. } => vec![
+                Span::styled("      ", base_style),
+                Span::styled(
+                    SPECIAL_DESCRIPTORS_NAMES
+                        .get(&descriptor.uuid)
+                        .map_or_else(|| descriptor.uuid.to_string(), |name| name.to_string()),
+                    styled(Style::default().fg(Color::DarkGray)),
+                ),
+                Span::styled(
+                    format!(
+                        "  {}",
+                        descriptor_value(descriptor, descriptor_values.get(descriptor))
+                    ),
+                    styled(Style::default().fg(Color::White)),
+                ),
+            ],
+        };
 
-            return uuids_match || name_match || service_match;
+        if let Some(handle) = peripheral.handle(&item.id) {
+            spans.push(Span::styled(
+                format!("  0x{handle:04x}"),
+                styled(Style::default().fg(Color::DarkGray)),
+            ));
         }
 
-        true
+        let mut line = Line::from(spans);
+        // need to fill out the whole line to make highlight work as on general list items
+        let spacer = " ".repeat((width as usize).saturating_sub(line.width()));
+        line.spans.push(Span::styled(spacer, base_style));
+
+        ListItem::new(line).style(Style::default().fg(Color::Gray))
     }
 }
 
@@ -87,118 +523,240 @@ impl AppRoute for PeripheralView {
     {
         let initial_search = ctx.args.characteristic.clone();
 
-        PeripheralView {
-            search_regex: match search_input::maybe_update_search_regexp(
-                initial_search.as_deref(),
-                None,
-                &ctx,
-            ) {
-                ShouldUpdate::NoUpdate => None,
-                ShouldUpdate::Update(regex) => regex,
-            },
+        let mut view = PeripheralView {
+            filter_query: None,
+            filter_error: None,
             search: initial_search,
             focus: Focus::List,
             list_state: StableListState::default(),
             first_match_done: false,
+            collapsed: HashSet::new(),
             ctx,
-        }
+        };
+
+        view.update_filter_query(None);
+        view
     }
 
     fn handle_input(&mut self, key: &crossterm::event::KeyEvent) -> HandleKeydownResult {
+        if !self.is_typing() {
+            return match self.ctx.keymap.action(key, KEY_ACTIONS) {
+                Some(action) => self.handle_command(&Command::Action(action)),
+                None => HandleKeydownResult::Continue,
+            };
+        }
+
         let last_search = self.search.clone();
-        let active_route = self.ctx.get_active_route();
+        let ctx = Arc::clone(&self.ctx);
+        let active_route = ctx.get_active_route();
 
-        match active_route.deref() {
-            Route::PeripheralWaitingView { .. } => {
-                if matches!(key.code, KeyCode::Left | KeyCode::Char('d')) {
-                    drop(active_route);
-                    Route::PeripheralList.navigate(&self.ctx);
+        if let Route::PeripheralConnectedView(peripheral)
+        | Route::CharacteristicView { peripheral, .. } = active_route.deref()
+        {
+            let tree_items = self.build_tree(peripheral);
+            let tree = tree_items.iter().collect();
+
+            self.list_state.stabilize_selected_index(&tree);
+
+            search_input::handle_search_input(&mut self.search, key);
+
+            match self.ctx.keymap.input_action(key, SEARCH_ACTIONS) {
+                Some(Action::Confirm | Action::Down) => {
+                    let first = tree
+                        .iter()
+                        .position(|item| matches!(item.node, GattNode::Characteristic(_)));
+
+                    self.list_state.select(&tree, first.or(Some(0)));
+                    self.focus = Focus::List
+                }
+                Some(Action::Cancel | Action::Search) => {
+                    self.list_state.list_unselect(&tree);
+                    self.focus = Focus::List;
                 }
+                _ => (),
             }
-            Route::PeripheralConnectedView(peripheral)
-            | Route::CharacteristicView { peripheral, .. } => {
-                let filtered_chars = peripheral
-                    .characteristics
-                    .iter()
-                    .filter(|peripheral| self.filter_characteristic(peripheral))
-                    .collect();
+        }
 
-                self.list_state.stabilize_selected_index(&filtered_chars);
+        drop(active_route);
+        self.update_filter_query(last_search);
+
+        HandleKeydownResult::Continue
+    }
 
-                if GeneralOptions::handle_keystroke(&key.code, &self.ctx) {
+    fn handle_command(&mut self, command: &Command) -> HandleKeydownResult {
+        let ctx = Arc::clone(&self.ctx);
+        let active_route = ctx.get_active_route();
+
+        match active_route.deref() {
+            Route::PeripheralWaitingView { .. }
+                if matches!(command, Command::Action(Action::Left | Action::Disconnect)) =>
+            {
+                drop(active_route);
+                Route::PeripheralList.navigate(&ctx);
+
+                HandleKeydownResult::Continue
+            }
+            Route::PeripheralConnectedView(peripheral)
+            | Route::CharacteristicView { peripheral, .. } => {
+                if GeneralOptions::handle_command(command, &ctx) {
                     drop(active_route);
 
-                    let mut active_route = self.ctx.active_route.write();
-                    match active_route.as_deref_mut() {
-                        Ok(Route::PeripheralConnectedView(peripheral)) => {
-                            peripheral.apply_sort(&self.ctx);
-                        }
-                        _ => (),
+                    if let Ok(Route::PeripheralConnectedView(peripheral)) =
+                        ctx.active_route.write().as_deref_mut()
+                    {
+                        peripheral.apply_sort(&ctx);
                     }
 
                     return HandleKeydownResult::Handled;
                 }
 
-                match self.focus {
-                    Focus::Search => {
-                        search_input::handle_search_input(&mut self.search, key);
+                let action = match command {
+                    Command::Action(action) if KEY_ACTIONS.contains(action) => *action,
+                    Command::Filter(query) => {
+                        drop(active_route);
 
-                        match key.code {
-                            KeyCode::Enter | KeyCode::Down => {
-                                self.list_state.select(&filtered_chars, Some(0));
-                                self.focus = Focus::List
-                            }
-                            KeyCode::Esc | KeyCode::Tab => {
-                                self.list_state.list_unselect(&filtered_chars);
-                                self.focus = Focus::List;
-                            }
-                            _ => (),
+                        let last_search = self.search.replace(query.clone());
+                        self.focus = Focus::List;
+                        self.update_filter_query(last_search);
+
+                        return HandleKeydownResult::Handled;
+                    }
+                    _ => return HandleKeydownResult::Continue,
+                };
+
+                let tree_items = self.build_tree(peripheral);
+                let tree = tree_items.iter().collect();
+
+                self.list_state.stabilize_selected_index(&tree);
+
+                let selected = self.list_state.selected().and_then(|index| tree.get(index));
+
+                match (action, selected) {
+                    (Action::Search, _) => {
+                        self.focus = Focus::Search;
+                        self.list_state.list_unselect(&tree);
+                    }
+                    // connection stays alive in the background
+                    (Action::Left | Action::Cancel, _) => {
+                        drop(active_route);
+                        Route::PeripheralList.navigate(&self.ctx);
+                        return HandleKeydownResult::Handled;
+                    }
+                    (Action::Disconnect, _) => {
+                        let id = peripheral.peripheral.ble_peripheral.id();
+                        drop(active_route);
+
+                        connections::spawn_disconnect(&self.ctx, id);
+                        Route::PeripheralList.navigate(&self.ctx);
+                        return HandleKeydownResult::Handled;
+                    }
+                    (Action::Connections, _) => {
+                        drop(active_route);
+                        Route::ConnectionsPanel.navigate(&self.ctx);
+                        return HandleKeydownResult::Handled;
+                    }
+                    (Action::ToggleNotifications, Some(item)) => {
+                        if let Some(characteristic) = item.characteristic() {
+                            spawn_client_configuration_write(&self.ctx, peripheral, characteristic);
                         }
                     }
-                    Focus::List => {
-                        match key.code {
-                            KeyCode::Char('/') | KeyCode::Tab => {
-                                self.focus = Focus::Search;
-                                self.list_state.list_unselect(&filtered_chars);
+                    (Action::Pin, Some(item)) => {
+                        if let Some(characteristic) = item.characteristic() {
+                            let result =
+                                self.ctx
+                                    .pins
+                                    .write()
+                                    .map_err(Into::into)
+                                    .and_then(|mut pins| {
+                                        pins.toggle_characteristic(
+                                            &peripheral.peripheral,
+                                            characteristic,
+                                        )
+                                    });
+                            drop(active_route);
+
+                            if let Err(e) = result {
+                                if let Ok(global_error) =
+                                    self.ctx.global_error.lock().as_deref_mut()
+                                {
+                                    *global_error = Some(e);
+                                }
                             }
-                            KeyCode::Left | KeyCode::Char('d') => {
-                                drop(active_route);
-                                Route::PeripheralList.navigate(&self.ctx);
-                                return HandleKeydownResult::Handled;
+
+                            if let Ok(Route::PeripheralConnectedView(peripheral)) =
+                                self.ctx.active_route.write().as_deref_mut()
+                            {
+                                peripheral.apply_sort(&self.ctx);
                             }
-                            _ => {}
-                        }
 
-                        if let HandleInputResult::Selected(selected_char) =
-                            self.list_state.handle_key_input(&filtered_chars, &key.code)
+                            return HandleKeydownResult::Handled;
+                        }
+                    }
+                    (Action::Down, _) => self.list_state.list_select_next(&tree),
+                    (Action::Up, _) => self.list_state.list_select_previous(&tree),
+                    // collapse the node or jump to its parent
+                    (Action::Collapse, Some(item)) => {
+                        if item.expandable && !self.collapsed.contains(&item.id) {
+                            self.collapsed.insert(item.id.clone());
+                        } else if let Some(parent) = item.parent() {
+                            self.list_state.select_id(parent);
+                        }
+                    }
+                    (Action::Expand, Some(item))
+                        if item.expandable && self.collapsed.contains(&item.id) =>
+                    {
+                        self.collapsed.remove(&item.id);
+                    }
+                    (Action::ToggleCollapse, Some(item)) if item.expandable => {
+                        toggle_collapsed(&mut self.collapsed, &item.id)
+                    }
+                    (Action::CollapseAll, selected) => {
+                        self.collapsed.extend(
+                            peripheral
+                                .services
+                                .iter()
+                                .map(|service| GattAttribute::Service(service.uuid)),
+                        );
+
+                        if let Some(characteristic) =
+                            selected.and_then(|item| item.characteristic())
                         {
-                            let char_clone = selected_char.clone();
-                            let peripheral_clone = peripheral.clone();
+                            self.list_state
+                                .select_id(GattAttribute::Service(characteristic.service_uuid));
+                        }
+                    }
+                    (Action::ExpandAll, _) => self.collapsed.clear(),
+                    // expanded nodes and leaf characteristics move on or open as the flat list did
+                    (Action::Expand, Some(item)) if item.expandable => {
+                        self.list_state.list_select_next(&tree)
+                    }
+                    (Action::Right | Action::Confirm | Action::Expand, Some(item)) => {
+                        if let Some(characteristic) = item.characteristic() {
+                            let characteristic = characteristic.clone();
+                            let peripheral = peripheral.clone();
 
                             drop(active_route);
+                            open_characteristic_view(&self.ctx, peripheral, characteristic);
 
-                            Route::CharacteristicView {
-                                characteristic: char_clone,
-                                peripheral: peripheral_clone,
-                                history: Arc::new(RwLock::new(vec![])),
-                                historical_view_index: Default::default(),
-                            }
-                            .navigate(&self.ctx);
+                            return HandleKeydownResult::Continue;
                         }
                     }
+                    _ => {}
                 }
-            }
 
-            _ => (),
-        };
-
-        if let ShouldUpdate::Update(new_regex) =
-            search_input::maybe_update_search_regexp(self.search.as_deref(), last_search, &self.ctx)
-        {
-            self.search_regex = new_regex;
+                HandleKeydownResult::Handled
+            }
+            _ => HandleKeydownResult::Continue,
         }
+    }
 
-        HandleKeydownResult::Continue
+    /// The search is hidden while connecting
+    fn is_typing(&self) -> bool {
+        matches!(self.focus, Focus::Search)
+            && !matches!(
+                self.ctx.get_active_route().deref(),
+                Route::PeripheralWaitingView { .. }
+            )
     }
 
     fn render(
@@ -244,13 +802,10 @@ impl AppRoute for PeripheralView {
             }
         };
 
-        let filtered_chars = connection
-            .characteristics
-            .iter()
-            .filter(|characteristic| self.filter_characteristic(characteristic))
-            .collect();
+        let tree_items = self.build_tree(connection);
+        let tree = tree_items.iter().collect();
 
-        self.list_state.stabilize_selected_index(&filtered_chars);
+        self.list_state.stabilize_selected_index(&tree);
 
         let chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -269,72 +824,29 @@ impl AppRoute for PeripheralView {
             Span::from(self.search.as_deref().unwrap_or("")),
         ]))
         .style(Style::default())
-        .block(tui::widgets::Block::from(BlendrBlock {
+        .block(search_input::search_block(
+            "Filter services or characteristics, e.g. prop:notify",
+            self.filter_error.as_deref(),
+            matches!(self.focus, Focus::Search),
             route_active,
-            focused: matches!(self.focus, Focus::Search),
-            title: "Filter services or characteristics",
-            ..Default::default()
-        }));
+        ));
 
         f.render_widget(input, chunks[0]);
 
-        let items: Vec<ListItem> = filtered_chars
+        let peripheral_id = connection.peripheral.ble_peripheral.id();
+        let descriptor_values = connection.descriptor_values.read()?.clone();
+        let selected = self.list_state.selected();
+        let items: Vec<ListItem> = tree
             .iter()
             .enumerate()
-            .map(|(i, char)| {
-                let mut spans = vec![];
-
-                // render service as a part of list but highlight only notification on selection
-                let previous_char = filtered_chars.get(i.wrapping_sub(1));
-                if i == 0
-                    || previous_char
-                        .is_some_and(|prev_char| prev_char.service_uuid != char.service_uuid)
-                {
-                    spans.push(Line::from(vec![
-                        Span::styled("Service ", Style::default().fg(Color::White)),
-                        Span::styled(
-                            char.service_name(),
-                            Style::default()
-                                .add_modifier(Modifier::BOLD)
-                                .fg(Color::Rgb(251, 146, 60)),
-                        ),
-                    ]));
-                }
-
-                let is_highlighted = Some(i) == self.list_state.selected();
-                let base_style = is_highlighted
-                    .then(|| {
-                        Style::default()
-                            .bg(Color::LightBlue)
-                            .fg(Color::Black)
-                            .add_modifier(Modifier::BOLD)
-                    })
-                    .unwrap_or_default();
-
-                let char_name = char.char_name();
-                let mut char_line = Line::from(vec![
-                    Span::styled(if is_highlighted { ">" } else { "•" }, base_style),
-                    Span::styled("  ", base_style),
-                    Span::styled(char_name, base_style.add_modifier(Modifier::BOLD)),
-                    Span::styled(
-                        format!(
-                            " [{}]",
-                            display_properties(char.ble_characteristic.properties)
-                        ),
-                        base_style.fg(Color::Gray),
-                    ),
-                ]);
-
-                let mut spacer = String::new();
-                // need to fill out the whole line to make highlight work as on general list items
-                for _ in 0..(chunks[1].width as usize).saturating_sub(char_line.width()) {
-                    spacer.push(' ');
-                }
-
-                char_line.spans.push(Span::styled(spacer, base_style));
-                spans.push(char_line);
-
-                ListItem::new(spans).style(Style::default().fg(Color::Gray))
+            .map(|(i, item)| {
+                self.render_row(
+                    item,
+                    connection,
+                    &descriptor_values,
+                    Some(i) == selected,
+                    chunks[1].width,
+                )
             })
             .collect();
 
@@ -342,10 +854,16 @@ impl AppRoute for PeripheralView {
         let items = List::new(items).block(tui::widgets::Block::from(BlendrBlock {
             route_active,
             focused: matches!(self.focus, Focus::List),
-            title: format!(
-                " Device {} ({}) ",
-                connection.peripheral.name, connection.peripheral.address
-            ),
+            title: Title::new(vec![
+                Span::raw(format!(
+                    " Device {} ({}) ",
+                    connection.peripheral.name, connection.peripheral.address
+                )),
+                match connections::state(&self.ctx, &peripheral_id) {
+                    Some(state) => Span::styled(format!("{state} "), connection_state_style(state)),
+                    None => Span::raw(""),
+                },
+            ]),
             ..Default::default()
         }));
 
@@ -356,33 +874,91 @@ impl AppRoute for PeripheralView {
                 block::render_help(
                     Arc::clone(&self.ctx),
                     [
-                        Some(("/", "Search", false)),
-                        Some(("<- | d", "Disconnect from device", false)),
-                        Some(("->", "View characteristic", false)),
-                        Some(("r", "Reconnect to device scan", false)),
+                        Some((&[Action::Search], "Search", false)),
+                        Some((&[Action::CommandPalette], "Commands", false)),
+                        Some((&[Action::Left, Action::Cancel], "Back to devices", false)),
+                        Some((&[Action::Disconnect], "Disconnect from device", false)),
+                        Some((&[Action::Right], "View characteristic", false)),
+                        Some((
+                            &[Action::Collapse, Action::Expand, Action::ToggleCollapse],
+                            "Collapse/expand",
+                            false,
+                        )),
+                        Some((
+                            &[Action::CollapseAll, Action::ExpandAll],
+                            "Collapse/expand all",
+                            false,
+                        )),
+                        Some((
+                            &[Action::ToggleNotifications],
+                            "Toggle notifications (CCCD)",
+                            false,
+                        )),
+                        Some((&[Action::Pin], "Pin characteristic", false)),
+                        Some((&[Action::Connections], "Connections", false)),
                     ],
                 ),
                 chunks[2],
             );
         }
 
-        if !self.first_match_done && filtered_chars.len() == 1 {
+        let matched_characteristics = connection
+            .characteristics
+            .iter()
+            .filter(|characteristic| self.matches_search(*characteristic))
+            .collect::<Vec<_>>();
+
+        if !self.first_match_done && matched_characteristics.len() == 1 {
             let peripheral = connection.clone();
-            let characteristic = filtered_chars[0].clone();
+            let characteristic = matched_characteristics[0].clone();
 
-            self.list_state.select(&filtered_chars, Some(0));
+            self.list_state
+                .select_id(characteristic_id(&characteristic));
             drop(active_route);
 
-            Route::CharacteristicView {
-                characteristic,
-                peripheral,
-                history: Arc::new(RwLock::new(vec![])),
-                historical_view_index: Default::default(),
-            }
-            .navigate(&self.ctx);
-            self.first_match_done = true
+            open_characteristic_view(&self.ctx, peripheral, characteristic);
+            self.first_match_done = true;
+            return Ok(());
+        }
+
+        // jump from the favorites panel
+        let connection_id = connection.peripheral.ble_peripheral.id();
+        let open_on_connect = self
+            .ctx
+            .open_on_connect
+            .lock()?
+            .take_if(|(id, _)| *id == connection_id);
+        if let Some((_, attribute)) = open_on_connect {
+            let Some(characteristic) = connection
+                .characteristics
+                .iter()
+                .find(|characteristic| characteristic_id(characteristic) == attribute)
+                .cloned()
+            else {
+                return Err(crate::error::Error::client(format!(
+                    "Pinned characteristic is not found on {}",
+                    connection.peripheral.name
+                )));
+            };
+
+            let peripheral = connection.clone();
+            self.list_state
+                .select_id(characteristic_id(&characteristic));
+            drop(active_route);
+
+            open_characteristic_view(&self.ctx, peripheral, characteristic);
         }
 
         Ok(())
     }
 }
+
+#[test]
+fn cycles_client_configuration() {
+    let both = CharPropFlags::NOTIFY | CharPropFlags::INDICATE;
+    assert_eq!(next_client_configuration(both, 0), 0b01);
+    assert_eq!(next_client_configuration(both, 0b01), 0b10);
+    assert_eq!(next_client_configuration(both, 0b10), 0);
+    assert_eq!(next_client_configuration(CharPropFlags::NOTIFY, 0b01), 0);
+    assert_eq!(next_client_configuration(CharPropFlags::INDICATE, 0b11), 0);
+}
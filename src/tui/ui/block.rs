@@ -1,4 +1,4 @@
-use crate::{cli_args, Ctx};
+use crate::{cli_args, tui::keymap::Action, Ctx};
 use std::sync::Arc;
 use tui::{
     style::{Color, Modifier, Style},
@@ -64,18 +64,24 @@ impl<'a, TTitle: Into<Title<'a>> + Default> From<BlendrBlock<'a, TTitle>>
     }
 }
 
+/// Help line of the view, keys of the actions are taken from the keymap
 pub fn render_help<const N: usize>(
     ctx: Arc<Ctx>,
-    help: [Option<(&str, &str, bool)>; N],
+    help: [Option<(&[Action], &str, bool)>; N],
 ) -> impl Widget {
     const SPACING: &str = "    ";
     let general_options_guard = &ctx.general_options.read();
     let general_options = general_options_guard.as_ref().unwrap();
 
+    let sort_key = ctx.keymap.label(&[Action::Sort]);
     let general_options_spans = [
         Span::from("Sort by: "),
         Span::styled(
-            "[n]ame",
+            if sort_key == "n" {
+                String::from("[n]ame")
+            } else {
+                format!("[{sort_key}] name")
+            },
             if general_options.sort == cli_args::GeneralSort::Name {
                 Style::default()
                     .fg(Color::Cyan)
@@ -85,6 +91,15 @@ pub fn render_help<const N: usize>(
             },
         ),
         Span::from(" | "),
+        Span::styled(
+            "pinned",
+            if general_options.sort == cli_args::GeneralSort::Pinned {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            },
+        ),
+        Span::from(" | "),
         Span::styled(
             "default",
             if general_options.sort == cli_args::GeneralSort::DefaultSort {
@@ -99,7 +114,8 @@ pub fn render_help<const N: usize>(
     let spans: Vec<_> = help
         .into_iter()
         .flatten()
-        .map(|(key, text, bold)| {
+        .map(|(actions, text, bold)| {
+            let key = ctx.keymap.label(actions);
             let mut key_span = Span::from(format!("[{key}] {text}{SPACING}"));
 
             if bold {
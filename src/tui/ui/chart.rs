@@ -0,0 +1,99 @@
+use crate::tui::{ui::BlendrBlock, TerminalBackend};
+use tui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::Span,
+    Frame,
+};
+
+/// Summary of the plotted values usually displayed in the chart title
+pub struct SeriesStats {
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+}
+
+pub fn format_axis_value(value: f64) -> String {
+    if value.abs() >= 1000.0 || value.fract() == 0.0 {
+        format!("{value:.0}")
+    } else {
+        format!("{value:.2}")
+    }
+}
+
+/// Renders values as a line chart with time on the X axis, renders nothing if there are no values
+pub fn render_time_series(
+    f: &mut Frame<TerminalBackend>,
+    area: Rect,
+    route_active: bool,
+    values: &[(chrono::DateTime<chrono::Local>, f64)],
+    color: Color,
+    title: impl FnOnce(&SeriesStats) -> String,
+) {
+    let (Some((first_time, _)), Some((last_time, _))) = (values.first(), values.last()) else {
+        return;
+    };
+
+    let points = values
+        .iter()
+        .map(|(time, number)| {
+            let seconds = (*time - *first_time).num_milliseconds() as f64 / 1000.0;
+            (seconds, *number)
+        })
+        .collect::<Vec<_>>();
+
+    let stats = SeriesStats {
+        min: values
+            .iter()
+            .map(|(_, number)| *number)
+            .fold(f64::INFINITY, f64::min),
+        max: values
+            .iter()
+            .map(|(_, number)| *number)
+            .fold(f64::NEG_INFINITY, f64::max),
+        avg: values.iter().map(|(_, number)| number).sum::<f64>() / values.len() as f64,
+    };
+
+    // keep flat lines visible in the middle of the chart
+    let padding = if stats.max > stats.min {
+        (stats.max - stats.min) * 0.1
+    } else {
+        stats.max.abs().max(1.0) * 0.1
+    };
+    let y_bounds = [stats.min - padding, stats.max + padding];
+    let x_max = ((*last_time - *first_time).num_milliseconds() as f64 / 1000.0).max(1.0);
+
+    let datasets = vec![tui::widgets::Dataset::default()
+        .marker(tui::symbols::Marker::Braille)
+        .graph_type(tui::widgets::GraphType::Line)
+        .style(Style::default().fg(color))
+        .data(&points)];
+
+    let chart = tui::widgets::Chart::new(datasets)
+        .block(tui::widgets::Block::from(BlendrBlock {
+            route_active,
+            title: title(&stats),
+            ..Default::default()
+        }))
+        .x_axis(
+            tui::widgets::Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([0.0, x_max])
+                .labels(vec![
+                    Span::from(first_time.format("%H:%M:%S").to_string()),
+                    Span::from(last_time.format("%H:%M:%S").to_string()),
+                ]),
+        )
+        .y_axis(
+            tui::widgets::Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds(y_bounds)
+                .labels(vec![
+                    Span::from(format_axis_value(y_bounds[0])),
+                    Span::from(format_axis_value((y_bounds[0] + y_bounds[1]) / 2.0)),
+                    Span::from(format_axis_value(y_bounds[1])),
+                ]),
+        );
+
+    f.render_widget(chart, area);
+}
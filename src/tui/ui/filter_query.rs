@@ -0,0 +1,670 @@
+use crate::{
+    bluetooth::{ble_default_services::parse_ble_uuid, ConnectedCharacteristic, HandledPeripheral},
+    cli_args::parse_company_id,
+    error::{Error, Result},
+};
+use btleplug::api::CharPropFlags;
+use regex::Regex;
+use std::borrow::Cow;
+use uuid::Uuid;
+
+const PROPERTIES: &[(&str, CharPropFlags)] = &[
+    ("broadcast", CharPropFlags::BROADCAST),
+    ("read", CharPropFlags::READ),
+    ("write", CharPropFlags::WRITE),
+    (
+        "write_without_response",
+        CharPropFlags::WRITE_WITHOUT_RESPONSE,
+    ),
+    ("notify", CharPropFlags::NOTIFY),
+    ("indicate", CharPropFlags::INDICATE),
+    ("signed_write", CharPropFlags::AUTHENTICATED_SIGNED_WRITES),
+    ("extended", CharPropFlags::EXTENDED_PROPERTIES),
+];
+
+/// Anything the search runs against, fields it does not have never match
+pub trait QueryTarget {
+    /// Texts matched by the terms without a field
+    fn texts(&self) -> Vec<Cow<'_, str>>;
+    fn names(&self) -> Vec<Cow<'_, str>>;
+    fn service_uuids(&self) -> Vec<Uuid>;
+    fn service_names(&self) -> Vec<Cow<'_, str>>;
+    fn address(&self) -> Option<String> {
+        None
+    }
+    fn rssi(&self) -> Option<i16> {
+        None
+    }
+    fn properties(&self) -> CharPropFlags {
+        CharPropFlags::empty()
+    }
+    fn manufacturer_ids(&self) -> Vec<u16> {
+        vec![]
+    }
+}
+
+impl QueryTarget for HandledPeripheral {
+    fn texts(&self) -> Vec<Cow<'_, str>> {
+        std::iter::once(Cow::from(self.name.as_str()))
+            .chain(
+                self.services_names
+                    .iter()
+                    .map(|name| Cow::from(name.as_ref())),
+            )
+            .chain(
+                self.beacons
+                    .iter()
+                    .map(|beacon| Cow::from(beacon.to_string())),
+            )
+            .collect()
+    }
+
+    fn names(&self) -> Vec<Cow<'_, str>> {
+        vec![Cow::from(self.name.as_str())]
+    }
+
+    fn service_uuids(&self) -> Vec<Uuid> {
+        self.services
+            .iter()
+            .chain(self.service_data.keys())
+            .copied()
+            .collect()
+    }
+
+    fn service_names(&self) -> Vec<Cow<'_, str>> {
+        self.services_names
+            .iter()
+            .map(|name| Cow::from(name.as_ref()))
+            .collect()
+    }
+
+    fn address(&self) -> Option<String> {
+        Some(self.address.to_string())
+    }
+
+    fn rssi(&self) -> Option<i16> {
+        self.rssi
+    }
+
+    fn manufacturer_ids(&self) -> Vec<u16> {
+        self.manufacturer_data.keys().copied().collect()
+    }
+}
+
+impl QueryTarget for ConnectedCharacteristic {
+    fn texts(&self) -> Vec<Cow<'_, str>> {
+        [
+            Some(Cow::from(self.uuid.to_string())),
+            Some(Cow::from(self.service_uuid.to_string())),
+            self.custom_char_name.as_deref().map(Cow::from),
+            self.custom_service_name.as_deref().map(Cow::from),
+            self.standard_gatt_char_name.map(Cow::from),
+            self.standard_gatt_service_name.map(Cow::from),
+            self.user_description.as_deref().map(Cow::from),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    fn names(&self) -> Vec<Cow<'_, str>> {
+        [
+            self.custom_char_name.as_deref(),
+            self.standard_gatt_char_name,
+            self.user_description.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(Cow::from)
+        .collect()
+    }
+
+    fn service_uuids(&self) -> Vec<Uuid> {
+        vec![self.service_uuid]
+    }
+
+    fn service_names(&self) -> Vec<Cow<'_, str>> {
+        [
+            self.custom_service_name.as_deref(),
+            self.standard_gatt_service_name,
+        ]
+        .into_iter()
+        .flatten()
+        .map(Cow::from)
+        .collect()
+    }
+
+    fn properties(&self) -> CharPropFlags {
+        self.ble_characteristic.properties
+    }
+}
+
+#[derive(Debug)]
+pub enum TextMatcher {
+    Regex(Regex),
+    /// Plain text that is not a valid regex
+    Contains(String),
+}
+
+impl TextMatcher {
+    /// "/regex/" has to be a valid regex, everything else falls back to a plain text search
+    fn parse(value: &str, regex_flags: &str) -> Result<Self> {
+        if let Some(regex) = value
+            .strip_prefix('/')
+            .and_then(|value| value.strip_suffix('/'))
+        {
+            return Regex::new(&format!("{regex_flags}{regex}"))
+                .map(TextMatcher::Regex)
+                .map_err(|_| Error::client(format!("Invalid regex /{regex}/")));
+        }
+
+        let value = value.replace('"', "");
+        Ok(match Regex::new(&format!("{regex_flags}{value}")) {
+            Ok(regex) => TextMatcher::Regex(regex),
+            Err(_) => TextMatcher::Contains(value),
+        })
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            TextMatcher::Regex(regex) => regex.is_match(text),
+            TextMatcher::Contains(value) => text.contains(value.as_str()),
+        }
+    }
+
+    fn any_match<'a>(&self, texts: impl IntoIterator<Item = Cow<'a, str>>) -> bool {
+        texts.into_iter().any(|text| self.is_match(&text))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    fn compare(self, value: i16, expected: i16) -> bool {
+        match self {
+            Comparison::Less => value < expected,
+            Comparison::LessOrEqual => value <= expected,
+            Comparison::Equal => value == expected,
+            Comparison::NotEqual => value != expected,
+            Comparison::GreaterOrEqual => value >= expected,
+            Comparison::Greater => value > expected,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Term {
+    Text(TextMatcher),
+    Name(TextMatcher),
+    Service(Uuid),
+    ServiceName(TextMatcher),
+    /// Address glob with "*" compiled to a regex
+    Address(Regex),
+    Property(CharPropFlags),
+    Manufacturer(u16),
+    Rssi(Comparison, i16),
+}
+
+impl Term {
+    fn parse(word: &str, regex_flags: &str) -> Result<Self> {
+        let lowercase = word.to_lowercase();
+        if let Some(rssi) = lowercase
+            .strip_prefix("rssi")
+            .filter(|rest| rest.starts_with(['<', '>', '=', '!', ':']))
+        {
+            let (comparison, value) = [
+                ("<=", Comparison::LessOrEqual),
+                (">=", Comparison::GreaterOrEqual),
+                ("!=", Comparison::NotEqual),
+                ("<", Comparison::Less),
+                (">", Comparison::Greater),
+                ("=", Comparison::Equal),
+                (":", Comparison::Equal),
+            ]
+            .into_iter()
+            .find_map(|(operator, comparison)| Some((comparison, rssi.strip_prefix(operator)?)))
+            .ok_or_else(|| Error::client(format!("Invalid comparison \"{word}\"")))?;
+
+            return value
+                .parse()
+                .map(|value| Term::Rssi(comparison, value))
+                .map_err(|_| Error::client(format!("Invalid RSSI \"{value}\", e.g. rssi>-60")));
+        }
+
+        let Some((field, value)) = word.split_once(':') else {
+            return TextMatcher::parse(word, regex_flags).map(Term::Text);
+        };
+
+        let field = field.to_lowercase();
+        if !["name", "service", "addr", "prop", "mfg"].contains(&field.as_str()) {
+            // e.g. a part of the address or of a regex
+            return TextMatcher::parse(word, regex_flags).map(Term::Text);
+        }
+
+        if value.is_empty() {
+            return Err(Error::client(format!("{field}: needs a value")));
+        }
+
+        match field.as_str() {
+            "name" => TextMatcher::parse(value, regex_flags).map(Term::Name),
+            "service" => match parse_ble_uuid(&value.replace('"', "")) {
+                Ok(uuid) => Ok(Term::Service(uuid)),
+                Err(_) => TextMatcher::parse(value, regex_flags).map(Term::ServiceName),
+            },
+            "addr" => {
+                let glob = value
+                    .replace('"', "")
+                    .split('*')
+                    .map(regex::escape)
+                    .collect::<Vec<_>>()
+                    .join(".*");
+
+                Regex::new(&format!("(?i)^{glob}$"))
+                    .map(Term::Address)
+                    .map_err(|_| Error::client(format!("Invalid address \"{value}\"")))
+            }
+            "prop" => {
+                let property = value.to_lowercase().replace('-', "_");
+                PROPERTIES
+                    .iter()
+                    .find(|(name, _)| *name == property)
+                    .map(|(_, flag)| Term::Property(*flag))
+                    .ok_or_else(|| {
+                        Error::client(format!(
+                            "Unknown property \"{value}\", expected {}",
+                            PROPERTIES
+                                .iter()
+                                .map(|(name, _)| *name)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ))
+                    })
+            }
+            _ => parse_company_id(value)
+                .map(Term::Manufacturer)
+                .map_err(Error::client),
+        }
+    }
+
+    fn matches(&self, target: &impl QueryTarget) -> bool {
+        match self {
+            Term::Text(matcher) => matcher.any_match(target.texts()),
+            Term::Name(matcher) => matcher.any_match(target.names()),
+            Term::Service(uuid) => target.service_uuids().contains(uuid),
+            Term::ServiceName(matcher) => matcher.any_match(target.service_names()),
+            Term::Address(regex) => target
+                .address()
+                .is_some_and(|address| regex.is_match(&address)),
+            Term::Property(flag) => target.properties().contains(*flag),
+            Term::Manufacturer(id) => target.manufacturer_ids().contains(id),
+            Term::Rssi(comparison, expected) => target
+                .rssi()
+                .is_some_and(|rssi| comparison.compare(rssi, *expected)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word(String),
+}
+
+/// Splits the query by whitespace and parentheses, quoted texts and "/regex/" are kept whole
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = query.chars().peekable();
+
+    while let Some(&char) = chars.peek() {
+        match char {
+            char if char.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+                continue;
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+                continue;
+            }
+            _ => (),
+        }
+
+        let mut word = String::new();
+        // parentheses inside of the word are the part of it, e.g. a regex group
+        let mut depth = 0;
+        while let Some(&char) = chars.peek() {
+            match char {
+                char if char.is_whitespace() => break,
+                ')' if depth == 0 => break,
+                '"' => {
+                    word.push(chars.next().unwrap_or_default());
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some(char) => word.push(char),
+                            None => return Err(Error::client("Missing closing quote")),
+                        }
+                    }
+                    word.push('"');
+                    continue;
+                }
+                '/' if word.is_empty() || word.ends_with(':') => {
+                    word.push(chars.next().unwrap_or_default());
+                    loop {
+                        match chars.next() {
+                            Some('/') => break,
+                            Some('\\') => {
+                                word.push('\\');
+                                word.extend(chars.next());
+                            }
+                            Some(char) => word.push(char),
+                            None => return Err(Error::client("Missing closing / of the regex")),
+                        }
+                    }
+                    word.push('/');
+                    continue;
+                }
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => (),
+            }
+
+            word.extend(chars.next());
+        }
+
+        tokens.push(match word.as_str() {
+            "AND" => Token::And,
+            "OR" => Token::Or,
+            "NOT" => Token::Not,
+            _ => Token::Word(word),
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// Search of the device list and the device view, e.g. `rssi>-60 AND (name:/^Sensor/ OR mfg:0x004c)`.
+/// Terms next to each other are joined with AND, NOT binds tighter than AND and AND tighter than OR.
+#[derive(Debug)]
+pub enum FilterQuery {
+    Term(Term),
+    Not(Box<FilterQuery>),
+    And(Vec<FilterQuery>),
+    Or(Vec<FilterQuery>),
+}
+
+struct Parser<'a> {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+    regex_flags: &'a str,
+}
+
+impl Parser<'_> {
+    fn or(&mut self) -> Result<FilterQuery> {
+        let mut queries = vec![self.and()?];
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            queries.push(self.and()?);
+        }
+
+        Ok(FilterQuery::flatten(queries, FilterQuery::Or))
+    }
+
+    fn and(&mut self) -> Result<FilterQuery> {
+        let mut queries = vec![self.not()?];
+        loop {
+            match self.tokens.peek() {
+                Some(Token::And) => {
+                    self.tokens.next();
+                }
+                Some(Token::Open | Token::Not | Token::Word(_)) => (),
+                _ => break,
+            }
+
+            queries.push(self.not()?);
+        }
+
+        Ok(FilterQuery::flatten(queries, FilterQuery::And))
+    }
+
+    fn not(&mut self) -> Result<FilterQuery> {
+        match self.tokens.next() {
+            Some(Token::Not) => Ok(FilterQuery::Not(Box::new(self.not()?))),
+            Some(Token::Open) => {
+                let query = self.or()?;
+                match self.tokens.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(Error::client("Missing )")),
+                }
+            }
+            Some(Token::Word(word)) => Term::parse(&word, self.regex_flags).map(FilterQuery::Term),
+            Some(Token::Close) => Err(Error::client("Unexpected )")),
+            Some(Token::And | Token::Or) => {
+                Err(Error::client("AND and OR need a term on both sides"))
+            }
+            None => Err(Error::client("The query ends too early")),
+        }
+    }
+}
+
+impl FilterQuery {
+    /// Empty query filters nothing
+    pub fn parse(query: &str, regex_flags: &str) -> Result<Option<Self>> {
+        let tokens = tokenize(query)?;
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+            regex_flags,
+        };
+        let query = parser.or()?;
+
+        match parser.tokens.next() {
+            None => Ok(Some(query)),
+            Some(Token::Close) => Err(Error::client("Unexpected )")),
+            Some(_) => Err(Error::client("AND and OR need a term on both sides")),
+        }
+    }
+
+    fn flatten(mut queries: Vec<FilterQuery>, join: fn(Vec<FilterQuery>) -> FilterQuery) -> Self {
+        if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            join(queries)
+        }
+    }
+
+    pub fn matches(&self, target: &impl QueryTarget) -> bool {
+        match self {
+            FilterQuery::Term(term) => term.matches(target),
+            FilterQuery::Not(query) => !query.matches(target),
+            FilterQuery::And(queries) => queries.iter().all(|query| query.matches(target)),
+            FilterQuery::Or(queries) => queries.iter().any(|query| query.matches(target)),
+        }
+    }
+}
+
+#[cfg(test)]
+struct TestDevice {
+    name: &'static str,
+    address: &'static str,
+    rssi: i16,
+    services: Vec<Uuid>,
+    manufacturer_ids: Vec<u16>,
+}
+
+#[cfg(test)]
+impl QueryTarget for TestDevice {
+    fn texts(&self) -> Vec<Cow<'_, str>> {
+        vec![Cow::from(self.name)]
+    }
+
+    fn names(&self) -> Vec<Cow<'_, str>> {
+        vec![Cow::from(self.name)]
+    }
+
+    fn service_uuids(&self) -> Vec<Uuid> {
+        self.services.clone()
+    }
+
+    fn service_names(&self) -> Vec<Cow<'_, str>> {
+        vec![]
+    }
+
+    fn address(&self) -> Option<String> {
+        Some(self.address.to_owned())
+    }
+
+    fn rssi(&self) -> Option<i16> {
+        Some(self.rssi)
+    }
+
+    fn manufacturer_ids(&self) -> Vec<u16> {
+        self.manufacturer_ids.clone()
+    }
+}
+
+#[test]
+fn parses_filter_queries() {
+    let sensor = TestDevice {
+        name: "Sensor 42",
+        address: "AA:BB:CC:DD:EE:01",
+        rssi: -55,
+        services: vec![parse_ble_uuid("180d").unwrap()],
+        manufacturer_ids: vec![],
+    };
+    let beacon = TestDevice {
+        name: "iBeacon (tag)",
+        address: "11:22:33:44:55:66",
+        rssi: -80,
+        services: vec![],
+        manufacturer_ids: vec![0x004c],
+    };
+
+    let matching = |query: &str| {
+        let query = FilterQuery::parse(query, "(?i)").unwrap().unwrap();
+        [&sensor, &beacon]
+            .into_iter()
+            .filter(|device| query.matches(*device))
+            .map(|device| device.name)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(matching("rssi>-60"), ["Sensor 42"]);
+    assert_eq!(matching("RSSI<=-80"), ["iBeacon (tag)"]);
+    assert_eq!(matching("name:/^sensor/"), ["Sensor 42"]);
+    assert_eq!(matching("service:180d"), ["Sensor 42"]);
+    assert_eq!(matching("addr:aa:bb*"), ["Sensor 42"]);
+    assert_eq!(matching("addr:*55:66"), ["iBeacon (tag)"]);
+    assert_eq!(matching("mfg:0x004c"), ["iBeacon (tag)"]);
+    assert_eq!(matching("mfg:76"), ["iBeacon (tag)"]);
+    assert!(matching("prop:notify").is_empty());
+    assert_eq!(matching("NOT mfg:0x004c"), ["Sensor 42"]);
+    assert_eq!(
+        matching("rssi>-60 OR mfg:0x004c"),
+        ["Sensor 42", "iBeacon (tag)"]
+    );
+    // implicit AND, and AND binds tighter than OR
+    assert_eq!(
+        matching("rssi>-60 name:beacon OR mfg:0x004c"),
+        ["iBeacon (tag)"]
+    );
+    assert!(matching("rssi>-60 AND (name:beacon OR mfg:0x004c)").is_empty());
+    assert_eq!(matching("NOT (rssi>-60 OR name:x)"), ["iBeacon (tag)"]);
+    // bare words are regexes, or plain text when they are not valid regexes
+    assert_eq!(matching("(tag)"), ["iBeacon (tag)"]);
+    assert_eq!(matching("\"Sensor 42\""), ["Sensor 42"]);
+    assert_eq!(matching("name:/sensor \\d+/"), ["Sensor 42"]);
+
+    assert!(FilterQuery::parse("  ", "").unwrap().is_none());
+    for invalid in [
+        "rssi>strong",
+        "rssi=>-60",
+        "name:/[/",
+        "name:/unclosed",
+        "prop:fly",
+        "mfg:apple",
+        "addr:",
+        "\"unclosed",
+        "(rssi>-60",
+        "rssi>-60)",
+        "rssi>-60 AND",
+        "OR name:x",
+        "NOT",
+    ] {
+        assert!(FilterQuery::parse(invalid, "").is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn filters_characteristics() {
+    let characteristic = |uuid: &str, service: &str, properties, custom_name: Option<&str>| {
+        let uuid = parse_ble_uuid(uuid).unwrap();
+        let service_uuid = parse_ble_uuid(service).unwrap();
+
+        ConnectedCharacteristic {
+            ble_characteristic: btleplug::api::Characteristic {
+                uuid,
+                service_uuid,
+                properties,
+                descriptors: Default::default(),
+            },
+            standard_gatt_char_name: None,
+            standard_gatt_service_name: None,
+            custom_char_name: custom_name.map(str::to_owned),
+            custom_service_name: custom_name.map(|_| String::from("Living room")),
+            user_description: None,
+            pinned: false,
+            uuid,
+            service_uuid,
+        }
+    };
+    let characteristics = [
+        characteristic(
+            "2a37",
+            "180d",
+            CharPropFlags::NOTIFY,
+            Some("Room temperature"),
+        ),
+        characteristic("2a19", "180f", CharPropFlags::READ, None),
+    ];
+
+    let matching = |query: &str| {
+        let query = FilterQuery::parse(query, "(?i)").unwrap().unwrap();
+        characteristics
+            .iter()
+            .filter(|characteristic| query.matches(*characteristic))
+            .map(|characteristic| characteristic.uuid)
+            .collect::<Vec<_>>()
+    };
+    let heart_rate = characteristics[0].uuid;
+    let battery_level = characteristics[1].uuid;
+
+    assert_eq!(matching("prop:notify"), [heart_rate]);
+    assert_eq!(matching("prop:read"), [battery_level]);
+    assert_eq!(matching("service:180f"), [battery_level]);
+    assert_eq!(matching("NOT service:180f"), [heart_rate]);
+    // names of the names map file are searched as any other name
+    assert_eq!(matching("temperature"), [heart_rate]);
+    assert_eq!(matching("living"), [heart_rate]);
+    assert_eq!(matching("name:room"), [heart_rate]);
+    assert_eq!(matching("service:living"), [heart_rate]);
+    assert_eq!(matching("2a19"), [battery_level]);
+}
@@ -0,0 +1,29 @@
+use ansi_to_tui::IntoText;
+use tui::text::Line;
+
+/// Renders bytes with hexyl and converts its colored output into the tui lines
+pub fn hex_dump_lines(data: &[u8], width: u16) -> Vec<Line<'static>> {
+    let mut hexyl_output_buf = Vec::new();
+    {
+        let mut writer = std::io::Cursor::new(&mut hexyl_output_buf);
+        let mut printer = hexyl::PrinterBuilder::new(&mut writer)
+            .show_color(true)
+            .num_panels(if width > 70 { 2 } else { 1 })
+            .with_border_style(hexyl::BorderStyle::Unicode)
+            .build();
+
+        printer.print_all(data).unwrap();
+    }
+
+    match hexyl_output_buf.into_text() {
+        Ok(output) => output.lines,
+        Err(_) => {
+            tracing::error!(
+                ?hexyl_output_buf,
+                "Failed to parse and display hexyl output"
+            );
+
+            vec![]
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crossterm::event::KeyCode;
+use crate::tui::keymap::Action;
 use std::{collections::HashMap, hash::Hash, ops::Index};
 use tui::widgets::ListState;
 
@@ -121,6 +121,11 @@ impl<TId: Eq + Hash> StableListState<TId> {
         }
     }
 
+    /// Selects the item by its id, the index is resolved on the next stabilization
+    pub fn select_id(&mut self, id: TId) {
+        self.selected_id = Some(id);
+    }
+
     pub fn selected(&mut self) -> Option<usize> {
         self.unstable_state.selected()
     }
@@ -167,11 +172,11 @@ impl<TId: Eq + Hash> StableListState<TId> {
     pub fn handle_key_input<'a, 'b: 'a, TItem: StableListItem<TId> + Clone>(
         &mut self,
         list: &'a StableIndexList<'b, TId, TItem>,
-        keycode: &KeyCode,
+        action: Option<Action>,
     ) -> HandleInputResult<&'b TItem> {
-        match keycode {
-            KeyCode::Left | KeyCode::Char('h') => self.list_unselect(list),
-            KeyCode::Right | KeyCode::Char('l') | KeyCode::Enter => {
+        match action {
+            Some(Action::Left) => self.list_unselect(list),
+            Some(Action::Right | Action::Confirm) => {
                 if let Some(selected_index) = self.selected() {
                     if selected_index >= list.vec.len() {
                         return HandleInputResult::None;
@@ -180,8 +185,8 @@ impl<TId: Eq + Hash> StableListState<TId> {
                     return HandleInputResult::Selected(list.vec[selected_index]);
                 }
             }
-            KeyCode::Down | KeyCode::Char('j') => self.list_select_next(list),
-            KeyCode::Up | KeyCode::Char('k') => self.list_select_previous(list),
+            Some(Action::Down) => self.list_select_next(list),
+            Some(Action::Up) => self.list_select_previous(list),
             _ => (),
         };
 
@@ -1,7 +1,12 @@
 pub mod block;
+pub mod chart;
+pub mod filter_query;
+pub mod hex;
 pub mod list;
 pub mod search_input;
 
 pub use block::*;
+pub use chart::*;
+pub use hex::*;
 pub use list::*;
 pub use search_input::*;
@@ -1,6 +1,13 @@
 use regex::Regex;
 
-use crate::Ctx;
+use crate::{
+    tui::ui::{filter_query::FilterQuery, BlendrBlock, Title},
+    Ctx,
+};
+use tui::{
+    style::{Color, Style},
+    text::Span,
+};
 
 use crossterm::event::KeyCode;
 
@@ -22,25 +29,43 @@ pub enum ShouldUpdate<T> {
     NoUpdate,
 }
 
-/// Do not parse and compile regex if nothing changes
-pub fn maybe_update_search_regexp(
+/// Compiles user search query applying the regex flags from args
+pub fn build_search_regex(search: &str, ctx: &Ctx) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("{}{search}", ctx.args.regex_flags))
+}
+
+/// Do not parse the query if nothing changes
+pub fn maybe_update_filter_query(
     search: Option<&str>,
     last_search: Option<String>,
     ctx: &Ctx,
-) -> ShouldUpdate<Option<Regex>> {
+) -> ShouldUpdate<crate::error::Result<Option<FilterQuery>>> {
     match search {
-        None => ShouldUpdate::Update(None),
-        Some(search) if search.is_empty() => ShouldUpdate::Update(None),
+        None => ShouldUpdate::Update(Ok(None)),
         Some(search) if Some(search) != last_search.as_deref() => {
-            let regex = Regex::new(&format!("{}{search}", ctx.args.regex_flags))
-                .map_err(|e| {
-                    tracing::error!(?e, "Failed to create regex");
-                    e
-                })
-                .ok();
-
-            ShouldUpdate::Update(regex)
+            ShouldUpdate::Update(FilterQuery::parse(search, &ctx.args.regex_flags))
         }
         _ => ShouldUpdate::NoUpdate,
     }
 }
+
+/// Block of the search input, the error of an invalid query replaces the title in red
+pub fn search_block<'a>(
+    title: &'a str,
+    error: Option<&'a str>,
+    focused: bool,
+    route_active: bool,
+) -> tui::widgets::Block<'a> {
+    let title = match error {
+        Some(error) => Span::styled(format!(" {error} "), Style::default().fg(Color::Red)),
+        None => Span::from(format!(" {title} ")),
+    };
+
+    tui::widgets::Block::from(BlendrBlock {
+        route_active,
+        focused,
+        title: Title::new(vec![title]),
+        color: error.map(|_| Color::Red),
+        ..Default::default()
+    })
+}